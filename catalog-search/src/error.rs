@@ -0,0 +1,88 @@
+//! Errors returned by the wasm API. In JS they are `Error` objects with a
+//! stable `code` next to the human-readable `message`, so the UI can react
+//! per error class.
+
+use crate::DecodeError;
+use serde::Serialize;
+use wasm_bindgen::JsValue;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The options object doesn't match [`crate::SearchOptions`].
+    InvalidOptions,
+    /// A scoring weight is negative or not a number.
+    InvalidWeights,
+    /// The catalog bytes are empty, usually a failed fetch.
+    EmptyCatalog,
+    /// The catalog bytes are corrupt or not a catalog at all.
+    InvalidCatalog,
+    /// The catalog was written by an unsupported format version.
+    UnsupportedVersion,
+    /// No catalog was added under the given name.
+    UnknownCatalog,
+    /// The facet field isn't one `facet_values` supports.
+    UnknownFacet,
+    /// A result couldn't be converted to a JS value.
+    Serialization,
+}
+
+impl ErrorCode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::InvalidOptions => "invalid_options",
+            Self::InvalidWeights => "invalid_weights",
+            Self::EmptyCatalog => "empty_catalog",
+            Self::InvalidCatalog => "invalid_catalog",
+            Self::UnsupportedVersion => "unsupported_version",
+            Self::UnknownCatalog => "unknown_catalog",
+            Self::UnknownFacet => "unknown_facet",
+            Self::Serialization => "serialization",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SearchError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl SearchError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for SearchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for SearchError {}
+
+impl From<DecodeError> for SearchError {
+    fn from(err: DecodeError) -> Self {
+        let code = match err {
+            DecodeError::Empty => ErrorCode::EmptyCatalog,
+            DecodeError::UnsupportedVersion { .. } => ErrorCode::UnsupportedVersion,
+            DecodeError::MissingHeader
+            | DecodeError::Bincode(_)
+            | DecodeError::Unrecognized { .. } => ErrorCode::InvalidCatalog,
+        };
+        Self::new(code, err.to_string())
+    }
+}
+
+impl From<SearchError> for JsValue {
+    fn from(err: SearchError) -> Self {
+        let error = js_sys::Error::new(&err.message);
+        // Setting a property on a fresh Error object can't fail.
+        let _ = js_sys::Reflect::set(&error, &"code".into(), &err.code.as_str().into());
+        error.into()
+    }
+}
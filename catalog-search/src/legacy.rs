@@ -0,0 +1,138 @@
+//! Frozen copies of earlier catalog layouts, kept so old blobs can be migrated
+//! to the current model. Never change these structs; add a new version instead.
+
+use crate::model::{Catalog, Furniture, Variation};
+use serde::{Deserialize, Serialize};
+
+/// Layout before `Catalog::meta` was added (format version 2). Its items
+/// still match the current [`Furniture`]; freeze a copy here if that changes.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CatalogV2 {
+    pub items: Vec<Furniture>,
+}
+
+/// Layout before `Furniture::category` was added (format version 1, which
+/// was also written without a header).
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CatalogV1 {
+    pub items: Vec<FurnitureV1>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FurnitureV1 {
+    pub id: String,
+    #[serde(default)]
+    pub integration_id: Option<String>,
+    #[serde(default)]
+    pub integration_type: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub slug: Option<String>,
+    #[serde(default)]
+    pub specifications: Option<String>,
+    #[serde(default)]
+    pub price: Option<f64>,
+    #[serde(default)]
+    pub weight: Option<f64>,
+    #[serde(default)]
+    pub sku: Option<String>,
+    #[serde(default)]
+    pub status: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+    #[serde(default)]
+    pub project_id: Option<String>,
+    #[serde(default)]
+    pub depth: Option<f64>,
+    #[serde(default)]
+    pub height: Option<f64>,
+    #[serde(default)]
+    pub width: Option<f64>,
+    #[serde(default)]
+    pub description_text: Option<String>,
+    #[serde(default)]
+    pub quick_description: Option<String>,
+    #[serde(default)]
+    pub has_variations: Option<bool>,
+    #[serde(default)]
+    pub quick_specifications: Option<String>,
+    #[serde(default)]
+    pub priority: Option<i64>,
+    #[serde(default)]
+    pub is_promotional: Option<bool>,
+    #[serde(default)]
+    pub promotional_price: Option<f64>,
+    #[serde(default)]
+    pub variations: Vec<Variation>,
+    #[serde(default)]
+    pub searchable_text: String,
+}
+
+/// Clears precomputed `searchable_text` so [`crate::prepare_catalog`]
+/// rebuilds it. Text stored by older versions was derived with an earlier
+/// tokenizer and normalizer (accents kept, older stopwords) and would no
+/// longer match normalized queries.
+pub fn without_searchable_text(mut catalog: Catalog) -> Catalog {
+    for furniture in &mut catalog.items {
+        furniture.searchable_text.clear();
+    }
+    catalog
+}
+
+impl From<CatalogV2> for Catalog {
+    fn from(catalog: CatalogV2) -> Self {
+        without_searchable_text(Catalog {
+            items: catalog.items,
+            meta: None,
+        })
+    }
+}
+
+impl From<CatalogV1> for Catalog {
+    fn from(catalog: CatalogV1) -> Self {
+        Catalog {
+            items: catalog.items.into_iter().map(Furniture::from).collect(),
+            meta: None,
+        }
+    }
+}
+
+impl From<FurnitureV1> for Furniture {
+    fn from(item: FurnitureV1) -> Self {
+        Furniture {
+            id: item.id,
+            integration_id: item.integration_id,
+            integration_type: item.integration_type,
+            name: item.name,
+            slug: item.slug,
+            specifications: item.specifications,
+            price: item.price,
+            weight: item.weight,
+            sku: item.sku,
+            status: item.status,
+            created_at: item.created_at,
+            updated_at: item.updated_at,
+            project_id: item.project_id,
+            depth: item.depth,
+            height: item.height,
+            width: item.width,
+            description_text: item.description_text,
+            quick_description: item.quick_description,
+            has_variations: item.has_variations,
+            quick_specifications: item.quick_specifications,
+            priority: item.priority,
+            is_promotional: item.is_promotional,
+            promotional_price: item.promotional_price,
+            category: None,
+            variations: item.variations,
+            // Rebuilt by `prepare_catalog`; see `without_searchable_text`.
+            searchable_text: String::new(),
+            updated_at_ms: None,
+            created_at_ms: None,
+        }
+    }
+}
@@ -1,271 +1,1422 @@
+pub mod error;
+pub mod html;
+mod legacy;
 pub mod model;
+pub mod raw;
+pub mod search;
+pub mod slug;
 
+use crate::error::{ErrorCode, SearchError};
 use crate::model::{Catalog, Furniture};
+pub use crate::search::SearchEngine;
+use crate::search::compare_priority;
 use bincode::Options;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use serde_wasm_bindgen::Serializer;
+use std::collections::{HashMap, HashSet};
+use unicode_normalization::{UnicodeNormalization, char::is_combining_mark};
 use wasm_bindgen::prelude::*;
 
+/// The search engine exposed to JS: a [`SearchEngine`] over the primary
+/// catalog plus the catalogs added with `add_catalog`. Native callers get the
+/// primary engine's methods through `Deref`.
 #[wasm_bindgen]
 pub struct CatalogSearch {
-    catalog: Catalog,
+    engine: SearchEngine,
+    /// Catalogs registered with `add_catalog`, by name.
+    named: HashMap<String, SearchEngine>,
 }
 
+/// Options accepted by the constructor. From JS, pass a plain object such as
+/// `{ fields: ["name", "color"], ranking: "bm25" }`; every key is optional.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct SearchOptions {
+    /// Fields to search. `None` searches every field.
+    pub fields: Option<Vec<SearchField>>,
+    pub ranking: RankingAlgorithm,
+    pub weights: ScoringWeights,
+    /// Words ignored in queries and indexed text. `None` uses
+    /// [`DEFAULT_STOPWORDS`]; an empty list disables stopword filtering.
+    pub stopwords: Option<Vec<String>>,
+    /// Extra words each query word matches, such as
+    /// `{ "couch": ["sofa", "settee"] }`. A word matching only through a
+    /// synonym earns [`ScoringWeights::synonym`] of its credit.
+    pub synonyms: HashMap<String, Vec<String>>,
+    /// Also expand each listed synonym to its key and to the other words of
+    /// its entry, so `sofa` matches `couch` too.
+    pub bidirectional_synonyms: bool,
+    /// Read `bytes` as a headerless blob written before catalogs carried a
+    /// version header (see [`decode_catalog_legacy`]).
+    pub legacy_format: bool,
+    /// Hide products that aren't available from `search` and the
+    /// priority listing.
+    pub in_stock_only: bool,
+    /// Statuses (case-insensitive) that count as available. `None` treats
+    /// every status except [`OUT_OF_STOCK_STATUS`] as available.
+    pub available_statuses: Option<Vec<String>>,
+    /// Treat products without a status as unavailable when `in_stock_only`
+    /// is on. By default they're shown.
+    pub hide_missing_status: bool,
+    pub empty_query: EmptyQueryBehavior,
+    /// How many recent queries keep their results cached, for typeahead
+    /// repeating itself. `None` uses [`DEFAULT_RESULT_CACHE_SIZE`]; `0`
+    /// disables the cache.
+    pub result_cache_size: Option<usize>,
+    /// Drop matches scoring below this before the result cap applies. The
+    /// score is the active scorer's raw one (additive points or BM25), so
+    /// thresholds don't carry over between scorers. `None` keeps every
+    /// match; scores can be negative, so `0` isn't the same.
+    pub min_score: Option<f32>,
+    /// Most results a query with search terms returns. `None` uses
+    /// [`DEFAULT_MAX_RESULTS`]; `0` means no cap.
+    pub max_results: Option<usize>,
+    /// Lift recently created products. Off by default.
+    pub recency_boost: Option<RecencyBoost>,
+}
+
+/// Bonus for products whose `created_at` falls within the last
+/// `window_days`: `max_bonus` for one created now, falling linearly to
+/// nothing at the end of the window. Products without a readable
+/// `created_at` get no bonus.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct RecencyBoost {
+    pub window_days: f64,
+    pub max_bonus: f32,
+}
+
+impl RecencyBoost {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.window_days.is_finite() || self.window_days <= 0.0 {
+            return Err(format!(
+                "recency_boost.window_days must be a positive number, got {}",
+                self.window_days
+            ));
+        }
+        if !self.max_bonus.is_finite() || self.max_bonus < 0.0 {
+            return Err(format!(
+                "recency_boost.max_bonus must be a non-negative number, got {}",
+                self.max_bonus
+            ));
+        }
+        Ok(())
+    }
+
+    /// Bonus for a product created at `created_ms`, as of `now_ms`.
+    pub(crate) fn bonus(&self, created_ms: Option<i64>, now_ms: i64) -> f32 {
+        let Some(created_ms) = created_ms else {
+            return 0.0;
+        };
+        let window_ms = self.window_days * 86_400_000.0;
+        // Future timestamps count as brand new.
+        let age_ms = (now_ms - created_ms).max(0) as f64;
+        if age_ms >= window_ms {
+            return 0.0;
+        }
+        self.max_bonus * (1.0 - age_ms / window_ms) as f32
+    }
+}
+
+pub const DEFAULT_EMPTY_QUERY_LIMIT: usize = 32;
+pub const DEFAULT_MAX_RESULTS: usize = 50;
+pub const DEFAULT_RESULT_CACHE_SIZE: usize = 64;
+
+/// What a query without search terms returns. From JS, pass
+/// `{ top_by_priority: 48 }` or `"all"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmptyQueryBehavior {
+    /// The first `n` products by priority.
+    TopByPriority(usize),
+    /// Every product, by priority, for clients that paginate themselves.
+    All,
+}
+
+impl Default for EmptyQueryBehavior {
+    fn default() -> Self {
+        Self::TopByPriority(DEFAULT_EMPTY_QUERY_LIMIT)
+    }
+}
+
+impl EmptyQueryBehavior {
+    fn limit(self) -> usize {
+        match self {
+            Self::TopByPriority(limit) => limit,
+            Self::All => usize::MAX,
+        }
+    }
+}
+
+pub const OUT_OF_STOCK_STATUS: &str = "out_of_stock";
+
+/// Portuguese and English function words that add noise to matching.
+pub const DEFAULT_STOPWORDS: &[&str] = &[
+    "a", "o", "as", "os", "um", "uma", "uns", "umas", "de", "da", "do", "das", "dos", "em", "na",
+    "no", "nas", "nos", "para", "por", "com", "e", "ou", "ao", "aos", "the", "an", "of", "and",
+    "or", "for", "with", "in", "on", "to", "at", "by",
+];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RankingAlgorithm {
+    /// One point per matched token plus name/slug bonuses and priority.
+    #[default]
+    Additive,
+    /// Okapi BM25 over the searchable text; priority only breaks ties.
+    Bm25,
+}
+
+/// Tunable scorer weights. `name`, `slug` and `priority` only apply to
+/// [`RankingAlgorithm::Additive`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScoringWeights {
+    /// Credit for a query term found as a whole word.
+    pub term: f32,
+    /// Fraction of the full credit earned by a term that only appears as
+    /// part of a longer word.
+    pub substring: f32,
+    /// Bonus per term found in the product name.
+    pub name: f32,
+    /// Bonus per term found in the slug.
+    pub slug: f32,
+    /// Multiplier for the priority score (lower `priority` ranks higher).
+    pub priority: f32,
+    /// Added to the score of matching products with `is_promotional` set,
+    /// with either scorer. `0` leaves promotions unranked.
+    pub promo: f32,
+    /// Fraction of a term's credit earned when only one of its synonyms
+    /// matches.
+    pub synonym: f32,
+}
+
+impl Default for ScoringWeights {
+    fn default() -> Self {
+        Self {
+            term: 1.0,
+            substring: 0.75,
+            name: 1.0,
+            slug: 0.5,
+            priority: 1.0,
+            promo: 0.0,
+            synonym: 0.8,
+        }
+    }
+}
+
+impl ScoringWeights {
+    pub fn validate(&self) -> Result<(), String> {
+        let fields = [
+            ("term", self.term),
+            ("substring", self.substring),
+            ("name", self.name),
+            ("slug", self.slug),
+            ("priority", self.priority),
+            ("promo", self.promo),
+            ("synonym", self.synonym),
+        ];
+        for (name, value) in fields {
+            if !value.is_finite() || value < 0.0 {
+                return Err(format!(
+                    "weight {name} must be a non-negative number, got {value}"
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A product as returned to JS. Fields serialize in declaration order and
+/// variations by their `order` field, then id. Clients snapshot that JSON,
+/// so the order is part of the output contract: add new fields at the end
+/// (before `score_breakdown`) instead of reordering.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProductResult {
+    pub id: String,
+    pub name: Option<String>,
+    pub slug: Option<String>,
+    pub description: Option<String>,
+    pub quick_description: Option<String>,
+    pub quick_specifications: Option<String>,
+    pub price: Option<f64>,
+    pub is_promotional: Option<bool>,
+    pub promotional_price: Option<f64>,
+    pub discount_percent: Option<f64>,
+    pub priority: Option<i64>,
+    pub category: Option<String>,
+    pub variations: Vec<VariationResult>,
+    /// Variations whose own fields matched a query term, so the UI can
+    /// preselect them. Empty when only product-level fields matched.
+    pub matched_variation_ids: Vec<String>,
+    pub score: f32,
+    /// Price to show as "from": the product's `price`, or the lowest
+    /// variation price when the product has none. `None` without any price.
+    pub from_price: Option<f64>,
+    /// Per-token score contributions, only filled in debug searches.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score_breakdown: Option<ScoreBreakdown>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ScoreBreakdown {
+    pub tokens: Vec<TokenScore>,
+    pub priority: f32,
+    pub promo: f32,
+    pub recency: f32,
+}
+
+/// Contribution of one query token: `text` is the match against the whole
+/// searchable text, `name` and `slug` are the bonuses for those fields.
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenScore {
+    pub token: String,
+    pub text: f32,
+    pub name: f32,
+    pub slug: f32,
+}
+
+/// One category bucket of [`CatalogSearch::search_grouped`].
 #[derive(Debug, Serialize)]
-struct ProductResult {
-    id: String,
-    name: Option<String>,
-    slug: Option<String>,
-    description: Option<String>,
-    quick_description: Option<String>,
-    quick_specifications: Option<String>,
-    price: Option<f64>,
-    is_promotional: Option<bool>,
-    promotional_price: Option<f64>,
-    priority: Option<i64>,
-    variations: Vec<VariationResult>,
-    score: f32,
+pub struct ResultGroup {
+    pub count: usize,
+    pub results: Vec<ProductResult>,
+}
+
+pub const UNCATEGORIZED: &str = "uncategorized";
+
+/// Field names of a serialized [`ProductResult`], accepted by
+/// [`project_results`].
+pub const PRODUCT_RESULT_FIELDS: &[&str] = &[
+    "id",
+    "name",
+    "slug",
+    "description",
+    "quick_description",
+    "quick_specifications",
+    "price",
+    "is_promotional",
+    "promotional_price",
+    "discount_percent",
+    "priority",
+    "category",
+    "variations",
+    "matched_variation_ids",
+    "score",
+    "from_price",
+    "score_breakdown",
+];
+
+/// Keeps only `fields` of each result, so list views don't transfer
+/// descriptions and variations they never show. Returns the projected
+/// objects and the requested names that aren't result fields.
+pub fn project_results(
+    results: &[ProductResult],
+    fields: &[String],
+) -> (Vec<serde_json::Map<String, serde_json::Value>>, Vec<String>) {
+    let (known, unknown): (Vec<&String>, Vec<&String>) = fields
+        .iter()
+        .partition(|field| PRODUCT_RESULT_FIELDS.contains(&field.as_str()));
+    let projected = results
+        .iter()
+        .map(|result| {
+            let serde_json::Value::Object(mut full) =
+                serde_json::to_value(result).expect("results serialize to JSON")
+            else {
+                unreachable!("results serialize to objects");
+            };
+            known
+                .iter()
+                .filter_map(|field| full.remove_entry(field.as_str()))
+                .collect()
+        })
+        .collect();
+    (projected, unknown.into_iter().cloned().collect())
+}
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = console)]
+    fn warn(message: &str);
+}
+
+/// One entry of [`SearchEngine::facet_values`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FacetValue {
+    pub value: String,
+    pub count: usize,
+}
+
+/// One autocomplete candidate from [`SearchEngine::suggest`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Suggestion {
+    pub id: String,
+    pub name: Option<String>,
+    pub slug: Option<String>,
+    /// The field the prefix was found in, for highlighting.
+    pub field: SuggestField,
+    /// Whether the prefix starts a word rather than sitting inside one.
+    pub word_start: bool,
+    pub score: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SuggestField {
+    Name,
+    Slug,
 }
 
+/// Search results plus a spelling suggestion when nothing matched.
 #[derive(Debug, Serialize)]
-struct VariationResult {
-    id: String,
-    name: Option<String>,
-    price: Option<f64>,
-    color: Option<String>,
-    secondary_color: Option<String>,
-    quick_description: Option<String>,
-    quick_specifications: Option<String>,
-    is_promotional: Option<bool>,
-    promotional_price: Option<f64>,
+pub struct SearchResponse {
+    pub results: Vec<ProductResult>,
+    pub suggestion: Option<String>,
+}
+
+/// Field order is part of the output contract, as for [`ProductResult`].
+#[derive(Debug, Clone, Serialize)]
+pub struct VariationResult {
+    pub id: String,
+    pub name: Option<String>,
+    pub price: Option<f64>,
+    pub color: Option<String>,
+    pub secondary_color: Option<String>,
+    pub quick_description: Option<String>,
+    pub quick_specifications: Option<String>,
+    pub is_promotional: Option<bool>,
+    pub promotional_price: Option<f64>,
+    pub discount_percent: Option<f64>,
+}
+
+// TypeScript shapes of the plain objects exchanged with JS. Methods still
+// return JSON-compatible values; these only type them in the generated
+// `.d.ts`. Keep in sync with the serde types above.
+#[wasm_bindgen(typescript_custom_section)]
+const TS_TYPES: &str = r#"
+export type SearchField =
+  | "name" | "slug" | "description" | "quick_description"
+  | "quick_specifications" | "specifications" | "variation_name"
+  | "variation_quick_description" | "variation_quick_specifications"
+  | "color" | "secondary_color";
+
+export interface ScoringWeights {
+  term?: number;
+  substring?: number;
+  name?: number;
+  slug?: number;
+  priority?: number;
+  promo?: number;
+  synonym?: number;
+}
+
+export interface SearchOptions {
+  fields?: SearchField[] | null;
+  ranking?: "additive" | "bm25";
+  weights?: ScoringWeights;
+  stopwords?: string[] | null;
+  synonyms?: Record<string, string[]>;
+  bidirectional_synonyms?: boolean;
+  legacy_format?: boolean;
+  in_stock_only?: boolean;
+  available_statuses?: string[] | null;
+  hide_missing_status?: boolean;
+  empty_query?: { top_by_priority: number } | "all";
+  result_cache_size?: number;
+  min_score?: number | null;
+  max_results?: number | null;
+  recency_boost?: { window_days: number; max_bonus: number } | null;
+}
+
+export interface VariationResult {
+  id: string;
+  name: string | null;
+  price: number | null;
+  color: string | null;
+  secondary_color: string | null;
+  quick_description: string | null;
+  quick_specifications: string | null;
+  is_promotional: boolean | null;
+  promotional_price: number | null;
+  discount_percent: number | null;
+}
+
+export interface TokenScore {
+  token: string;
+  text: number;
+  name: number;
+  slug: number;
+}
+
+export interface ScoreBreakdown {
+  tokens: TokenScore[];
+  priority: number;
+  promo: number;
+  recency: number;
+}
+
+export interface ProductResult {
+  id: string;
+  name: string | null;
+  slug: string | null;
+  description: string | null;
+  quick_description: string | null;
+  quick_specifications: string | null;
+  price: number | null;
+  is_promotional: boolean | null;
+  promotional_price: number | null;
+  discount_percent: number | null;
+  priority: number | null;
+  category: string | null;
+  variations: VariationResult[];
+  matched_variation_ids: string[];
+  score: number;
+  from_price: number | null;
+  score_breakdown?: ScoreBreakdown;
+}
+
+export interface SearchResponse {
+  results: ProductResult[];
+  suggestion: string | null;
+}
+
+export interface Suggestion {
+  id: string;
+  name: string | null;
+  slug: string | null;
+  field: "name" | "slug";
+  word_start: boolean;
+  score: number;
+}
+
+export interface ResultGroup {
+  count: number;
+  results: ProductResult[];
+}
+
+export interface FacetValue {
+  value: string;
+  count: number;
+}
+
+export type ErrorCode =
+  | "invalid_options" | "invalid_weights" | "empty_catalog" | "invalid_catalog"
+  | "unsupported_version" | "unknown_catalog" | "unknown_facet" | "serialization";
+
+/** What every method throws: an `Error` with a stable `code`. */
+export interface CatalogSearchError extends Error {
+  code: ErrorCode;
+}
+
+export interface CatalogMeta {
+  generated_at: string;
+  product_count: number;
+  variation_count: number;
+  generator_version: string;
 }
+"#;
 
 #[wasm_bindgen]
 impl CatalogSearch {
+    /// `options` is an optional [`SearchOptions`] object.
     #[wasm_bindgen(constructor)]
-    pub fn new(bytes: &[u8]) -> Result<CatalogSearch, JsValue> {
-        let mut catalog = decode_catalog(bytes).map_err(to_js_error)?;
-        prepare_catalog(&mut catalog);
-        Ok(Self { catalog })
+    pub fn new(
+        bytes: &[u8],
+        #[wasm_bindgen(unchecked_param_type = "SearchOptions | null | undefined")] options: JsValue,
+    ) -> Result<CatalogSearch, SearchError> {
+        let options: SearchOptions = if options.is_undefined() || options.is_null() {
+            SearchOptions::default()
+        } else {
+            serde_wasm_bindgen::from_value(options)
+                .map_err(|err| SearchError::new(ErrorCode::InvalidOptions, err.to_string()))?
+        };
+        options
+            .weights
+            .validate()
+            .map_err(|err| SearchError::new(ErrorCode::InvalidWeights, err))?;
+        if let Some(boost) = &options.recency_boost {
+            boost
+                .validate()
+                .map_err(|err| SearchError::new(ErrorCode::InvalidOptions, err))?;
+        }
+        let catalog = if options.legacy_format {
+            decode_catalog_legacy(bytes)
+        } else {
+            decode_catalog_any(bytes)
+        }?;
+        Ok(Self::with_options(catalog, options))
     }
 
-    #[wasm_bindgen(js_name = "all")]
-    pub fn all_js(&self) -> Result<JsValue, JsValue> {
-        let items = self
-            .catalog
-            .items
-            .iter()
-            .map(|furniture| build_result(furniture, 0.0))
-            .collect::<Vec<_>>();
-        to_js_value(&items)
+    /// An engine with no products, for use before the catalog blob arrives.
+    /// Searches return nothing and `meta()` is `null`.
+    #[wasm_bindgen(js_name = "empty")]
+    pub fn empty() -> CatalogSearch {
+        Self::from_catalog(Catalog::empty())
     }
 
-    #[wasm_bindgen]
-    pub fn search(&self, query: &str) -> Result<JsValue, JsValue> {
-        let trimmed = query.trim().to_lowercase();
+    /// Replaces the scorer weights for subsequent searches. Keys left out of
+    /// `weights` fall back to their defaults.
+    #[wasm_bindgen(js_name = "set_weights")]
+    pub fn set_weights_js(
+        &mut self,
+        #[wasm_bindgen(unchecked_param_type = "ScoringWeights")] weights: JsValue,
+    ) -> Result<(), SearchError> {
+        let weights: ScoringWeights = serde_wasm_bindgen::from_value(weights)
+            .map_err(|err| SearchError::new(ErrorCode::InvalidWeights, err.to_string()))?;
+        self.set_weights(weights)
+    }
 
-        if trimmed.is_empty() {
-            return self.top_by_priority(32);
-        }
+    /// Decodes `bytes` and registers the catalog under `name` (replacing
+    /// any catalog with that name), indexed with this engine's options.
+    /// Pass the name to `search`/`all` to query it; it stays loaded until
+    /// `remove_catalog`, so switching back and forth doesn't decode again.
+    #[wasm_bindgen(js_name = "add_catalog")]
+    pub fn add_catalog_js(&mut self, name: &str, bytes: &[u8]) -> Result<(), SearchError> {
+        let catalog = if self.engine.options().legacy_format {
+            decode_catalog_legacy(bytes)
+        } else {
+            decode_catalog_any(bytes)
+        }?;
+        self.add_catalog(name, catalog);
+        Ok(())
+    }
 
-        let tokens: Vec<&str> = trimmed.split_whitespace().collect();
+    /// Frees the catalog registered under `name`; returns whether one was.
+    pub fn remove_catalog(&mut self, name: &str) -> bool {
+        self.named.remove(name).is_some()
+    }
 
-        if tokens.is_empty() {
-            return self.top_by_priority(32);
-        }
+    /// Names of the catalogs added with `add_catalog`, sorted.
+    pub fn catalog_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.named.keys().cloned().collect();
+        names.sort();
+        names
+    }
 
-        let mut matches: Vec<ProductResult> = Vec::new();
+    /// Every product of `catalog` (a name given to `add_catalog`), or of the
+    /// primary catalog when omitted.
+    #[wasm_bindgen(js_name = "all", unchecked_return_type = "ProductResult[]")]
+    pub fn all_js(&self, catalog: Option<String>) -> Result<JsValue, SearchError> {
+        let engine = self.engine(catalog.as_deref())?;
+        to_js_value(&engine.all())
+    }
 
-        for furniture in &self.catalog.items {
-            if furniture.searchable_text.is_empty() {
-                continue;
-            }
+    /// The `limit` most recently updated products of `catalog` (or the
+    /// primary catalog), newest first.
+    #[wasm_bindgen(js_name = "recent", unchecked_return_type = "ProductResult[]")]
+    pub fn recent_js(&self, limit: usize, catalog: Option<String>) -> Result<JsValue, SearchError> {
+        let engine = self.engine(catalog.as_deref())?;
+        to_js_value(&engine.recent(limit))
+    }
 
-            if let Some(score) = compute_score(furniture, &tokens) {
-                matches.push(build_result(furniture, score));
-            }
+    /// Up to `limit` products whose name or slug contains `prefix`, best
+    /// first, for autocomplete.
+    #[wasm_bindgen(js_name = "suggest", unchecked_return_type = "Suggestion[]")]
+    pub fn suggest_js(
+        &self,
+        prefix: &str,
+        limit: usize,
+        catalog: Option<String>,
+    ) -> Result<JsValue, SearchError> {
+        let engine = self.engine(catalog.as_deref())?;
+        to_js_value(&engine.suggest(prefix, limit))
+    }
+
+    /// Number of products matching `query`, for filter badges. Cheaper than
+    /// `search` and not capped at one page.
+    #[wasm_bindgen(js_name = "count")]
+    pub fn count_js(&self, query: &str, catalog: Option<String>) -> Result<usize, SearchError> {
+        Ok(self.engine(catalog.as_deref())?.count(query))
+    }
+
+    /// Pass `debug = true` to attach a `score_breakdown` to every result,
+    /// and a `catalog` name given to `add_catalog` to search that catalog
+    /// instead of the primary one.
+    #[wasm_bindgen(unchecked_return_type = "ProductResult[]")]
+    pub fn search(
+        &self,
+        query: &str,
+        debug: Option<bool>,
+        catalog: Option<String>,
+    ) -> Result<JsValue, SearchError> {
+        let engine = self.engine(catalog.as_deref())?;
+        to_js_value(&engine.run_search(query, debug.unwrap_or(false)))
+    }
+
+    /// Like `search`, leaving out the products whose ids are in `exclude`
+    /// (e.g. the product being viewed, or items already in the cart). An
+    /// empty or absent list excludes nothing.
+    #[wasm_bindgen(
+        js_name = "search_excluding",
+        unchecked_return_type = "ProductResult[]"
+    )]
+    pub fn search_excluding_js(
+        &self,
+        query: &str,
+        exclude: Option<Vec<String>>,
+        catalog: Option<String>,
+    ) -> Result<JsValue, SearchError> {
+        let engine = self.engine(catalog.as_deref())?;
+        to_js_value(&engine.search_excluding(query, &exclude.unwrap_or_default()))
+    }
+
+    /// Like `search`, returning only the listed `fields` of each product
+    /// (e.g. `["id", "name", "price"]`). Unknown names are ignored with a
+    /// console warning; an absent list returns full products.
+    #[wasm_bindgen(
+        js_name = "search_fields",
+        unchecked_return_type = "Partial<ProductResult>[]"
+    )]
+    pub fn search_fields_js(
+        &self,
+        query: &str,
+        fields: Option<Vec<String>>,
+        catalog: Option<String>,
+    ) -> Result<JsValue, SearchError> {
+        let engine = self.engine(catalog.as_deref())?;
+        let results = engine.search_results(query);
+        let Some(fields) = fields else {
+            return to_js_value(&results);
+        };
+        let (projected, unknown) = project_results(&results, &fields);
+        if !unknown.is_empty() {
+            warn(&format!(
+                "search_fields: ignoring unknown fields: {}",
+                unknown.join(", ")
+            ));
         }
+        to_js_value(&projected)
+    }
 
-        matches.sort_by(|a, b| {
-            b.score
-                .partial_cmp(&a.score)
-                .unwrap_or(std::cmp::Ordering::Equal)
-                .then_with(|| compare_priority(a.priority, b.priority))
-                .then_with(|| compare_name(&a.name, &b.name))
-        });
+    /// Same results as `search`, encoded with `encode_results` instead of
+    /// built as JS objects, which is much cheaper for long result lists.
+    /// Decode them with `decodeProductResults` from
+    /// `catalog-search-results.js`.
+    #[wasm_bindgen(js_name = "search_bin")]
+    pub fn search_bin_js(
+        &self,
+        query: &str,
+        catalog: Option<String>,
+    ) -> Result<Vec<u8>, SearchError> {
+        let engine = self.engine(catalog.as_deref())?;
+        encode_results(&engine.search_results(query))
+            .map_err(|err| SearchError::new(ErrorCode::Serialization, err.to_string()))
+    }
 
-        matches.truncate(50);
+    /// Returns `{ results, suggestion }`, where `suggestion` is a corrected
+    /// query when `results` is empty and a close catalog term exists.
+    #[wasm_bindgen(
+        js_name = "search_with_suggestion",
+        unchecked_return_type = "SearchResponse"
+    )]
+    pub fn search_with_suggestion_js(
+        &self,
+        query: &str,
+        catalog: Option<String>,
+    ) -> Result<JsValue, SearchError> {
+        let engine = self.engine(catalog.as_deref())?;
+        to_js_value(&engine.search_with_suggestion(query))
+    }
 
-        to_js_value(&matches)
+    /// Returns `{ [category]: { count, results } }` for the same results as
+    /// `search`, keeping relevance order within each category.
+    #[wasm_bindgen(
+        js_name = "search_grouped",
+        unchecked_return_type = "Record<string, ResultGroup>"
+    )]
+    pub fn search_grouped_js(
+        &self,
+        query: &str,
+        catalog: Option<String>,
+    ) -> Result<JsValue, SearchError> {
+        let engine = self.engine(catalog.as_deref())?;
+        to_js_value(&engine.search_grouped(query))
     }
-}
 
-impl CatalogSearch {
-    fn top_by_priority(&self, limit: usize) -> Result<JsValue, JsValue> {
-        let mut items: Vec<ProductResult> = self
-            .catalog
-            .items
-            .iter()
-            .map(|item| build_result(item, priority_score(item.priority)))
-            .collect();
+    /// Returns the product with this slug, or `null`.
+    #[wasm_bindgen(
+        js_name = "get_by_slug",
+        unchecked_return_type = "ProductResult | null"
+    )]
+    pub fn get_by_slug_js(
+        &self,
+        slug: &str,
+        catalog: Option<String>,
+    ) -> Result<JsValue, SearchError> {
+        let engine = self.engine(catalog.as_deref())?;
+        to_js_value(&engine.get_by_slug(slug))
+    }
 
-        items.sort_by(|a, b| {
-            compare_priority(a.priority, b.priority).then_with(|| compare_name(&a.name, &b.name))
-        });
+    /// Returns the product with this id, or `null`.
+    #[wasm_bindgen(js_name = "get_by_id", unchecked_return_type = "ProductResult | null")]
+    pub fn get_by_id_js(&self, id: &str, catalog: Option<String>) -> Result<JsValue, SearchError> {
+        let engine = self.engine(catalog.as_deref())?;
+        to_js_value(&engine.get_by_id(id))
+    }
+
+    /// Returns up to `limit` products similar to the one with this id, most
+    /// similar first; empty for unknown ids.
+    #[wasm_bindgen(js_name = "related", unchecked_return_type = "ProductResult[]")]
+    pub fn related_js(
+        &self,
+        product_id: &str,
+        limit: usize,
+        catalog: Option<String>,
+    ) -> Result<JsValue, SearchError> {
+        let engine = self.engine(catalog.as_deref())?;
+        to_js_value(&engine.related(product_id, limit))
+    }
+
+    /// Returns the values of one facet field (`color`, `secondary_color` or
+    /// `category`) with how many products have each, sorted by value.
+    #[wasm_bindgen(js_name = "facet_values", unchecked_return_type = "FacetValue[]")]
+    pub fn facet_values_js(
+        &self,
+        field: &str,
+        catalog: Option<String>,
+    ) -> Result<JsValue, SearchError> {
+        let engine = self.engine(catalog.as_deref())?;
+        to_js_value(&engine.facet_values(field)?)
+    }
 
-        items.truncate(limit);
-        to_js_value(&items)
+    /// Returns `{ generated_at, product_count, variation_count,
+    /// generator_version }`, or `null` for catalogs built without metadata.
+    #[wasm_bindgen(js_name = "meta", unchecked_return_type = "CatalogMeta | null")]
+    pub fn meta_js(&self, catalog: Option<String>) -> Result<JsValue, SearchError> {
+        let engine = self.engine(catalog.as_deref())?;
+        to_js_value(&engine.meta())
     }
 }
 
-fn compute_score(furniture: &Furniture, tokens: &[&str]) -> Option<f32> {
-    if tokens.is_empty() {
-        return Some(priority_score(furniture.priority));
+impl CatalogSearch {
+    /// Builds a search index from an already decoded catalog. This is the
+    /// entry point for native callers (the server, CLI tooling) that can't go
+    /// through the `JsValue`-based constructor.
+    pub fn from_catalog(catalog: Catalog) -> Self {
+        Self::with_options(catalog, SearchOptions::default())
     }
 
-    let base = furniture.searchable_text.as_str();
-    if base.is_empty() {
-        return None;
+    /// Like [`Self::from_catalog`], but rebuilds every item's searchable text
+    /// from `fields` only, discarding any precomputed text.
+    pub fn with_fields(catalog: Catalog, fields: &[SearchField]) -> Self {
+        Self::from_engine(SearchEngine::with_fields(catalog, fields))
     }
 
-    let mut score = 0.0;
+    pub fn with_options(catalog: Catalog, options: SearchOptions) -> Self {
+        Self::from_engine(SearchEngine::with_options(catalog, options))
+    }
 
-    for token in tokens {
-        if !base.contains(token) {
-            return None;
+    fn from_engine(engine: SearchEngine) -> Self {
+        Self {
+            engine,
+            named: HashMap::new(),
         }
+    }
 
-        score += 1.0;
-
-        if let Some(name) = furniture.name.as_ref() {
-            if name.to_lowercase().contains(token) {
-                score += 1.0;
-            }
+    /// Rejects negative or non-finite weights, leaving the current ones.
+    /// Applies to the catalogs added with `add_catalog` too.
+    pub fn set_weights(&mut self, weights: ScoringWeights) -> Result<(), SearchError> {
+        self.engine.set_weights(weights)?;
+        for engine in self.named.values_mut() {
+            engine.set_weights(weights)?;
         }
+        Ok(())
+    }
 
-        if let Some(slug) = furniture.slug.as_ref() {
-            if slug.to_lowercase().contains(token) {
-                score += 0.5;
-            }
+    /// Registers `catalog` under `name`, replacing any catalog with that
+    /// name. It is indexed with the options (and current weights) of this
+    /// engine.
+    pub fn add_catalog(&mut self, name: &str, catalog: Catalog) {
+        let engine = SearchEngine::with_options(catalog, self.engine.options().clone());
+        self.named.insert(name.to_string(), engine);
+    }
+
+    /// The engine for the catalog registered under `name`, or the primary
+    /// catalog's for `None`.
+    pub fn engine(&self, name: Option<&str>) -> Result<&SearchEngine, SearchError> {
+        match name {
+            None => Ok(&self.engine),
+            Some(name) => self.named.get(name).ok_or_else(|| {
+                SearchError::new(
+                    ErrorCode::UnknownCatalog,
+                    format!("no catalog named '{name}'; add it with add_catalog first"),
+                )
+            }),
         }
     }
+}
 
-    score += priority_score(furniture.priority);
-    Some(score)
+impl std::ops::Deref for CatalogSearch {
+    type Target = SearchEngine;
+
+    fn deref(&self) -> &SearchEngine {
+        &self.engine
+    }
 }
 
-fn build_result(furniture: &Furniture, score: f32) -> ProductResult {
-    ProductResult {
-        id: furniture.id.clone(),
-        name: furniture.name.clone(),
-        slug: furniture.slug.clone(),
-        description: furniture.description_text.clone(),
-        quick_description: furniture.quick_description.clone(),
-        quick_specifications: furniture.quick_specifications.clone(),
-        price: furniture.price,
-        is_promotional: furniture.is_promotional,
-        promotional_price: furniture.promotional_price,
-        priority: furniture.priority,
-        variations: furniture
-            .variations
-            .iter()
-            .map(|variation| VariationResult {
-                id: variation.id.clone(),
-                name: variation.name.clone(),
-                price: variation.price,
-                color: variation.color.clone(),
-                secondary_color: variation.secondary_color.clone(),
-                quick_description: variation.quick_description.clone(),
-                quick_specifications: variation.quick_specifications.clone(),
-                is_promotional: variation.is_promotional,
-                promotional_price: variation.promotional_price,
-            })
-            .collect(),
-        score,
+/// The current time in milliseconds since the Unix epoch.
+fn now_ms() -> i64 {
+    #[cfg(target_arch = "wasm32")]
+    let now = js_sys::Date::now() as i64;
+    #[cfg(not(target_arch = "wasm32"))]
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as i64);
+    now
+}
+
+/// Milliseconds since the Unix epoch for an RFC 3339 timestamp, falling back
+/// to RFC 2822, offset-less date-times (read as UTC) and plain dates.
+fn timestamp_ms(value: &str) -> Option<i64> {
+    use chrono::{DateTime, NaiveDate, NaiveDateTime};
+
+    let value = value.trim();
+    if let Ok(parsed) = DateTime::parse_from_rfc3339(value) {
+        return Some(parsed.timestamp_millis());
+    }
+    if let Ok(parsed) = DateTime::parse_from_rfc2822(value) {
+        return Some(parsed.timestamp_millis());
+    }
+    for format in ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"] {
+        if let Ok(parsed) = NaiveDateTime::parse_from_str(value, format) {
+            return Some(parsed.and_utc().timestamp_millis());
+        }
     }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|parsed| parsed.and_utc().timestamp_millis())
+}
+
+fn to_js_value<T: Serialize>(value: &T) -> Result<JsValue, SearchError> {
+    value
+        .serialize(&Serializer::json_compatible())
+        .map_err(|err| SearchError::new(ErrorCode::Serialization, err.to_string()))
 }
 
-fn priority_score(priority: Option<i64>) -> f32 {
-    priority.map(|value| (-value) as f32).unwrap_or(0.0)
+/// Leading bytes of every encoded catalog, followed by a little-endian u16
+/// format version and the bincode body.
+pub const CATALOG_MAGIC: [u8; 4] = *b"MCAT";
+/// Bump whenever the bincode layout of [`Catalog`] changes, and keep a frozen
+/// copy of the previous layout in `legacy` so older blobs can be migrated.
+/// Also bump it when the derivation of `searchable_text` changes (tokenizing,
+/// normalization, stopwords), and clear the text of older versions on decode
+/// so it's rebuilt. Version 4 shares version 3's layout; version 3 text was
+/// built before queries were diacritic-folded.
+pub const CATALOG_VERSION: u16 = 4;
+const HEADER_LEN: usize = CATALOG_MAGIC.len() + 2;
+
+pub fn encode_catalog(catalog: &Catalog) -> bincode::Result<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(HEADER_LEN);
+    bytes.extend_from_slice(&CATALOG_MAGIC);
+    bytes.extend_from_slice(&CATALOG_VERSION.to_le_bytes());
+    bincode_options().serialize_into(&mut bytes, catalog)?;
+    Ok(bytes)
 }
 
-fn compare_priority(a: Option<i64>, b: Option<i64>) -> std::cmp::Ordering {
-    match (a, b) {
-        (Some(x), Some(y)) => x.cmp(&y),
-        (Some(_), None) => std::cmp::Ordering::Less,
-        (None, Some(_)) => std::cmp::Ordering::Greater,
-        (None, None) => std::cmp::Ordering::Equal,
+/// Decodes a versioned bincode catalog, migrating older versions.
+pub fn decode_catalog(bytes: &[u8]) -> Result<Catalog, DecodeError> {
+    if bytes.is_empty() {
+        return Err(DecodeError::Empty);
+    }
+    let body = bytes
+        .strip_prefix(&CATALOG_MAGIC)
+        .ok_or(DecodeError::MissingHeader)?;
+    let (version, body) = body
+        .split_first_chunk::<2>()
+        .ok_or(DecodeError::MissingHeader)?;
+
+    match u16::from_le_bytes(*version) {
+        CATALOG_VERSION => Ok(bincode_options().deserialize(body)?),
+        3 => Ok(legacy::without_searchable_text(
+            bincode_options().deserialize(body)?,
+        )),
+        2 => Ok(bincode_options()
+            .deserialize::<legacy::CatalogV2>(body)?
+            .into()),
+        1 => Ok(bincode_options()
+            .deserialize::<legacy::CatalogV1>(body)?
+            .into()),
+        found => Err(DecodeError::UnsupportedVersion { found }),
     }
 }
 
-fn compare_name(a: &Option<String>, b: &Option<String>) -> std::cmp::Ordering {
-    match (a, b) {
-        (Some(x), Some(y)) => x.to_lowercase().cmp(&y.to_lowercase()),
-        (Some(_), None) => std::cmp::Ordering::Less,
-        (None, Some(_)) => std::cmp::Ordering::Greater,
-        (None, None) => std::cmp::Ordering::Equal,
+/// Decodes a headerless blob written before catalogs were versioned. Tries
+/// the version 2 layout first, then version 1. Only use this for blobs known
+/// to predate the header: a mismatched layout can't always be detected.
+pub fn decode_catalog_legacy(bytes: &[u8]) -> Result<Catalog, DecodeError> {
+    if bytes.is_empty() {
+        return Err(DecodeError::Empty);
+    }
+    let strict = bincode_options().reject_trailing_bytes();
+    match strict.deserialize::<legacy::CatalogV2>(bytes) {
+        Ok(catalog) => Ok(catalog.into()),
+        Err(_) => Ok(strict.deserialize::<legacy::CatalogV1>(bytes)?.into()),
     }
 }
 
-fn to_js_value<T: Serialize>(value: &T) -> Result<JsValue, JsValue> {
-    value
-        .serialize(&Serializer::json_compatible())
-        .map_err(|err| JsValue::from_str(&err.to_string()))
+fn bincode_options() -> impl Options + Copy {
+    bincode::options().with_fixint_encoding()
 }
 
-fn to_js_error<E: std::fmt::Display>(err: E) -> JsValue {
-    JsValue::from_str(&err.to_string())
+/// Layout version of [`encode_results`], written as its first two bytes
+/// (little-endian). Bump it whenever `ProductResult` or `VariationResult`
+/// change, along with the decoder in `static/catalog-search-results.js`.
+pub const RESULTS_FORMAT_VERSION: u16 = 1;
+
+/// Compact encoding of search results for `search_bin`: the format version,
+/// then the results as bincode with fixed-width little-endian integers.
+/// Score breakdowns have no place in the layout and are rejected.
+pub fn encode_results(results: &[ProductResult]) -> bincode::Result<Vec<u8>> {
+    if results
+        .iter()
+        .any(|result| result.score_breakdown.is_some())
+    {
+        return Err(Box::new(bincode::ErrorKind::Custom(
+            "score breakdowns can't be encoded as binary results".into(),
+        )));
+    }
+    let mut bytes = RESULTS_FORMAT_VERSION.to_le_bytes().to_vec();
+    bincode_options().serialize_into(&mut bytes, results)?;
+    Ok(bytes)
 }
 
-pub fn encode_catalog(catalog: &Catalog) -> bincode::Result<Vec<u8>> {
-    bincode::options().with_fixint_encoding().serialize(catalog)
+/// Human-readable alternative to [`encode_catalog`]; [`decode_catalog_any`]
+/// reads either format.
+pub fn encode_catalog_json(catalog: &Catalog) -> serde_json::Result<Vec<u8>> {
+    serde_json::to_vec(catalog)
+}
+
+/// Decodes a versioned bincode catalog, or JSON when the header is missing.
+pub fn decode_catalog_any(bytes: &[u8]) -> Result<Catalog, DecodeError> {
+    if bytes.is_empty() {
+        return Err(DecodeError::Empty);
+    }
+    if bytes.starts_with(&CATALOG_MAGIC) {
+        return decode_catalog(bytes);
+    }
+    serde_json::from_slice(bytes).map_err(|json| DecodeError::Unrecognized { json })
+}
+
+#[derive(Debug)]
+pub enum DecodeError {
+    /// No bytes at all, e.g. the blob wasn't fetched.
+    Empty,
+    /// The bytes don't start with [`CATALOG_MAGIC`] and a version.
+    MissingHeader,
+    /// Written by a newer (or unknown) format version.
+    UnsupportedVersion {
+        found: u16,
+    },
+    Bincode(bincode::Error),
+    /// The bytes are neither a versioned bincode nor a JSON catalog.
+    Unrecognized {
+        json: serde_json::Error,
+    },
+}
+
+impl From<bincode::Error> for DecodeError {
+    fn from(err: bincode::Error) -> Self {
+        Self::Bincode(err)
+    }
 }
 
-pub fn decode_catalog(bytes: &[u8]) -> bincode::Result<Catalog> {
-    bincode::options().with_fixint_encoding().deserialize(bytes)
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(
+                f,
+                "catalog data is empty; check that the catalog blob was fetched and served correctly"
+            ),
+            Self::MissingHeader => write!(
+                f,
+                "catalog has no version header; rebuild it or load it as a legacy headerless blob"
+            ),
+            Self::UnsupportedVersion { found } => write!(
+                f,
+                "catalog format version {found} is not supported (expected {CATALOG_VERSION}); rebuild it with matching catalog-tools"
+            ),
+            Self::Bincode(err) => write!(
+                f,
+                "catalog data is truncated or corrupt, or its layout doesn't match its format version ({err}); rebuild it with catalog-tools"
+            ),
+            Self::Unrecognized { json } => write!(
+                f,
+                "catalog is neither a versioned bincode blob nor JSON ({json}); headerless blobs from older builds must be loaded as legacy"
+            ),
+        }
+    }
 }
 
+impl std::error::Error for DecodeError {}
+
 pub fn prepare_catalog(catalog: &mut Catalog) {
+    prepare_catalog_with_options(catalog, &PrepareOptions::default());
+}
+
+/// Lossy cleanups [`prepare_catalog_with_options`] can apply. All off by
+/// default, which matches [`prepare_catalog`].
+#[derive(Debug, Clone, Default)]
+pub struct PrepareOptions {
+    /// Drop variations with no name, color or price.
+    pub drop_empty_variations: bool,
+    /// Collapse products sharing a non-empty `integration_id` into the one
+    /// with the highest priority (then most variations), merging variations.
+    pub dedupe_by_integration_id: bool,
+    /// Reduce HTML in `description_text` to plain text with
+    /// [`html::strip_html`], for exports whose descriptions carry markup.
+    pub strip_html: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PrepareReport {
+    pub dropped_variations: usize,
+    /// Products removed because they duplicated another `integration_id`.
+    pub merged_products: usize,
+}
+
+pub fn prepare_catalog_with_options(
+    catalog: &mut Catalog,
+    options: &PrepareOptions,
+) -> PrepareReport {
+    let mut report = PrepareReport::default();
+
+    if options.dedupe_by_integration_id {
+        report.merged_products = dedupe_by_integration_id(catalog);
+    }
+
     for furniture in &mut catalog.items {
+        if options.drop_empty_variations {
+            let before = furniture.variations.len();
+            furniture.variations.retain(|variation| {
+                variation.name.is_some() || variation.color.is_some() || variation.price.is_some()
+            });
+            let dropped = before - furniture.variations.len();
+            if dropped > 0 {
+                report.dropped_variations += dropped;
+                furniture.has_variations = Some(!furniture.variations.is_empty());
+            }
+        }
+
+        if options.strip_html {
+            furniture.description_text = furniture
+                .description_text
+                .as_deref()
+                .map(html::strip_html)
+                .filter(|text| !text.is_empty());
+        }
+
+        sort_variations(furniture);
+        furniture.updated_at_ms = furniture.updated_at.as_deref().and_then(timestamp_ms);
+        furniture.created_at_ms = furniture.created_at.as_deref().and_then(timestamp_ms);
         if furniture.searchable_text.trim().is_empty() {
-            furniture.searchable_text = build_searchable_text(furniture);
+            furniture.searchable_text =
+                build_searchable_text(furniture, &SearchField::ALL, &default_stopwords());
+        }
+    }
+
+    report
+}
+
+fn dedupe_by_integration_id(catalog: &mut Catalog) -> usize {
+    let items = &mut catalog.items;
+    let mut groups: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (idx, furniture) in items.iter().enumerate() {
+        if let Some(integration_id) = furniture.integration_id.as_deref()
+            && !integration_id.trim().is_empty()
+        {
+            groups.entry(integration_id.trim()).or_default().push(idx);
+        }
+    }
+    let groups: Vec<Vec<usize>> = groups
+        .into_values()
+        .filter(|indices| indices.len() > 1)
+        .collect();
+
+    let mut removed = HashSet::new();
+    for indices in groups {
+        let winner = indices
+            .iter()
+            .copied()
+            .min_by(|&a, &b| {
+                compare_priority(items[a].priority, items[b].priority)
+                    .then_with(|| items[b].variations.len().cmp(&items[a].variations.len()))
+            })
+            .expect("group has at least two items");
+
+        for idx in indices.into_iter().filter(|&idx| idx != winner) {
+            for mut variation in std::mem::take(&mut items[idx].variations) {
+                if !items[winner]
+                    .variations
+                    .iter()
+                    .any(|v| v.id == variation.id)
+                {
+                    variation.furniture_id = Some(items[winner].id.clone());
+                    items[winner].variations.push(variation);
+                }
+            }
+            removed.insert(idx);
+        }
+
+        let winner = &mut items[winner];
+        winner.has_variations = Some(!winner.variations.is_empty());
+        // Rebuilt below so the merged variations become searchable.
+        winner.searchable_text.clear();
+    }
+
+    let mut idx = 0;
+    items.retain(|_| {
+        let keep = !removed.contains(&idx);
+        idx += 1;
+        keep
+    });
+    removed.len()
+}
+
+/// Orders variations by their `order` field, missing values last, then by id.
+fn sort_variations(furniture: &mut Furniture) {
+    furniture
+        .variations
+        .sort_by(|a, b| compare_priority(a.order, b.order).then_with(|| a.id.cmp(&b.id)));
+}
+
+/// A field that can contribute to an item's searchable text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchField {
+    Name,
+    Slug,
+    Description,
+    QuickDescription,
+    QuickSpecifications,
+    Specifications,
+    VariationName,
+    VariationQuickDescription,
+    VariationQuickSpecifications,
+    Color,
+    SecondaryColor,
+}
+
+impl SearchField {
+    /// The default field set, in indexing order.
+    pub const ALL: [SearchField; 11] = [
+        Self::Name,
+        Self::Slug,
+        Self::Description,
+        Self::QuickDescription,
+        Self::QuickSpecifications,
+        Self::Specifications,
+        Self::VariationName,
+        Self::VariationQuickDescription,
+        Self::VariationQuickSpecifications,
+        Self::Color,
+        Self::SecondaryColor,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let field = match name.trim() {
+            "name" => Self::Name,
+            "slug" => Self::Slug,
+            "description" => Self::Description,
+            "quick_description" => Self::QuickDescription,
+            "quick_specifications" => Self::QuickSpecifications,
+            "specifications" => Self::Specifications,
+            "variation_name" => Self::VariationName,
+            "variation_quick_description" => Self::VariationQuickDescription,
+            "variation_quick_specifications" => Self::VariationQuickSpecifications,
+            "color" => Self::Color,
+            "secondary_color" => Self::SecondaryColor,
+            _ => return None,
+        };
+        Some(field)
+    }
+
+    pub fn parse_list(names: &[String]) -> Result<Vec<Self>, String> {
+        names
+            .iter()
+            .map(|name| {
+                Self::from_name(name).ok_or_else(|| format!("unknown search field '{name}'"))
+            })
+            .collect()
+    }
+}
+
+/// A field [`CatalogSearch::facet_values`] can list. Colors come from the
+/// variations, the category from the product.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FacetField {
+    Color,
+    SecondaryColor,
+    Category,
+}
+
+impl FacetField {
+    const NAMES: [&str; 3] = ["color", "secondary_color", "category"];
+
+    fn from_name(name: &str) -> Option<Self> {
+        let field = match name.trim() {
+            "color" => Self::Color,
+            "secondary_color" => Self::SecondaryColor,
+            "category" => Self::Category,
+            _ => return None,
+        };
+        Some(field)
+    }
+
+    fn values(self, furniture: &Furniture) -> Box<dyn Iterator<Item = &str> + '_> {
+        match self {
+            Self::Color => Box::new(
+                furniture
+                    .variations
+                    .iter()
+                    .filter_map(|variation| variation.color.as_deref()),
+            ),
+            Self::SecondaryColor => Box::new(
+                furniture
+                    .variations
+                    .iter()
+                    .filter_map(|variation| variation.secondary_color.as_deref()),
+            ),
+            Self::Category => Box::new(furniture.category.as_deref().into_iter()),
         }
     }
 }
 
-fn build_searchable_text(furniture: &Furniture) -> String {
+fn default_stopwords() -> HashSet<String> {
+    DEFAULT_STOPWORDS
+        .iter()
+        .map(|word| word.to_string())
+        .collect()
+}
+
+fn build_searchable_text(
+    furniture: &Furniture,
+    fields: &[SearchField],
+    stopwords: &HashSet<String>,
+) -> String {
     let mut parts: Vec<String> = Vec::new();
-    push_lower(&mut parts, furniture.name.as_deref());
-    push_lower(&mut parts, furniture.slug.as_deref());
-    push_lower(&mut parts, furniture.description_text.as_deref());
-    push_lower(&mut parts, furniture.quick_description.as_deref());
-    push_lower(&mut parts, furniture.quick_specifications.as_deref());
-    push_lower(&mut parts, furniture.specifications.as_deref());
+    // Walk ALL rather than `fields` so the text keeps a stable field order.
+    for field in SearchField::ALL
+        .iter()
+        .filter(|field| fields.contains(field))
+    {
+        match field {
+            SearchField::Name => push_normalized(&mut parts, furniture.name.as_deref()),
+            SearchField::Slug => push_normalized(&mut parts, furniture.slug.as_deref()),
+            SearchField::Description => {
+                push_normalized(&mut parts, furniture.description_text.as_deref())
+            }
+            SearchField::QuickDescription => {
+                push_normalized(&mut parts, furniture.quick_description.as_deref())
+            }
+            SearchField::QuickSpecifications => {
+                push_normalized(&mut parts, furniture.quick_specifications.as_deref())
+            }
+            SearchField::Specifications => {
+                push_normalized(&mut parts, furniture.specifications.as_deref())
+            }
+            _ => {}
+        }
+    }
 
     for variation in &furniture.variations {
-        push_lower(&mut parts, variation.name.as_deref());
-        push_lower(&mut parts, variation.quick_description.as_deref());
-        push_lower(&mut parts, variation.quick_specifications.as_deref());
-        push_lower(&mut parts, variation.color.as_deref());
-        push_lower(&mut parts, variation.secondary_color.as_deref());
+        for field in SearchField::ALL
+            .iter()
+            .filter(|field| fields.contains(field))
+        {
+            let value = match field {
+                SearchField::VariationName => variation.name.as_deref(),
+                SearchField::VariationQuickDescription => variation.quick_description.as_deref(),
+                SearchField::VariationQuickSpecifications => {
+                    variation.quick_specifications.as_deref()
+                }
+                SearchField::Color => variation.color.as_deref(),
+                SearchField::SecondaryColor => variation.secondary_color.as_deref(),
+                _ => continue,
+            };
+            push_normalized(&mut parts, value);
+        }
     }
 
-    parts.join(" ")
+    parts
+        .iter()
+        .flat_map(|part| part.split_whitespace())
+        .filter(|word| !stopwords.contains(*word))
+        .collect::<Vec<_>>()
+        .join(" ")
 }
 
-fn push_lower(parts: &mut Vec<String>, value: Option<&str>) {
+fn push_normalized(parts: &mut Vec<String>, value: Option<&str>) {
     if let Some(text) = value {
         let trimmed = text.trim();
         if !trimmed.is_empty() {
-            parts.push(trimmed.to_lowercase());
+            parts.push(normalize(trimmed));
+        }
+    }
+}
+
+/// Splits normalized text into terms on whitespace, punctuation and the
+/// `|` / `•` separators used in specifications. Hyphenated words also yield
+/// their joined form, so "soft-close" produces "soft", "close" and "softclose".
+fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split_whitespace().flat_map(|word| {
+        let parts: Vec<&str> = word
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|part| !part.is_empty())
+            .collect();
+        let joined = (parts.len() > 1).then(|| parts.concat());
+        parts
+            .into_iter()
+            .map(str::to_string)
+            .chain(joined)
+            .collect::<Vec<_>>()
+    })
+}
+
+fn token_set(text: &str) -> HashSet<String> {
+    tokenize(text).collect()
+}
+
+/// Levenshtein distance over chars.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, ca) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
         }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+fn term_frequencies(text: &str) -> HashMap<String, u32> {
+    let mut terms = HashMap::new();
+    for token in tokenize(text) {
+        *terms.entry(token).or_default() += 1;
     }
+    terms
+}
+
+/// Lowercases and strips diacritics (NFD, then drops combining marks) so that
+/// "sofa" matches "Sofá". Applied to both the indexed text and the query.
+fn normalize(text: &str) -> String {
+    text.nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .collect::<String>()
+        .to_lowercase()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::model::{Catalog, Furniture, Variation};
+    use crate::model::{Catalog, CatalogMeta, Furniture, ValidationIssueKind, Variation};
+    use crate::raw::{RawFurniture, attach_variations};
+    use crate::search::{compare_name, discount_percent, from_price};
 
     fn sample_catalog() -> Catalog {
         Catalog {
@@ -285,22 +1436,1346 @@ mod tests {
                 }],
                 ..Default::default()
             }],
+            meta: None,
         }
     }
 
     #[test]
     fn encode_decode_roundtrip() {
-        let catalog = sample_catalog();
+        let mut catalog = sample_catalog();
+        let meta = CatalogMeta {
+            generated_at: "2024-05-01T12:00:00Z".into(),
+            product_count: 1,
+            variation_count: 1,
+            generator_version: "catalog-tools 0.1.0".into(),
+        };
+        catalog.meta = Some(meta.clone());
         let bytes = encode_catalog(&catalog).expect("encode");
         let decoded = decode_catalog(&bytes).expect("decode");
         assert_eq!(decoded.items.len(), 1);
+        assert_eq!(CatalogSearch::from_catalog(decoded).meta(), Some(&meta));
     }
 
     #[test]
-    fn prepare_catalog_builds_searchable_text() {
+    fn binary_results_start_with_the_format_version_and_count() {
+        let search = CatalogSearch::from_catalog(sample_catalog());
+        let bytes = encode_results(&search.search_results("chair")).expect("encode");
+        assert_eq!(bytes[..2], RESULTS_FORMAT_VERSION.to_le_bytes());
+        assert_eq!(bytes[2..10], 1u64.to_le_bytes());
+        assert_eq!(bytes[10..18], 1u64.to_le_bytes());
+        assert_eq!(&bytes[18..19], b"1");
+
+        assert!(encode_results(&search.search_debug("chair")).is_err());
+    }
+
+    #[test]
+    fn imported_catalogs_store_the_text_runtime_indexing_builds() {
+        let furniture: Vec<RawFurniture> = serde_json::from_str(
+            r#"[{ "id": "7", "name": "Poltrona Lina", "slug": "poltrona-lina",
+                  "description": { "blocks": [{ "text": "Assento em linho" }] },
+                  "quickSpecifications": "Estrutura de carvalho" }]"#,
+        )
+        .unwrap();
+        let variations: Vec<Variation> = serde_json::from_str(
+            r#"[{ "id": "v7", "furnitureId": "7", "name": "Natural",
+                  "color": "Areia", "secondaryColor": "Verde Musgo" }]"#,
+        )
+        .unwrap();
+        let mut items: Vec<Furniture> = furniture
+            .into_iter()
+            .map(RawFurniture::into_furniture)
+            .collect();
+        attach_variations(&mut items, variations);
+        let mut catalog = Catalog { items, meta: None };
+        prepare_catalog(&mut catalog);
+
+        // What `catalog-tools from-json` writes...
+        let stored = decode_catalog(&encode_catalog(&catalog).unwrap()).unwrap();
+        let stored_text = &stored.items[0].searchable_text;
+        assert!(stored_text.contains("areia verde musgo"), "{stored_text}");
+        assert!(stored_text.contains("linho"), "{stored_text}");
+        // ...matches what the engine builds when it indexes from scratch.
+        let runtime = SearchEngine::with_fields(stored.clone(), &SearchField::ALL);
+        assert_eq!(&runtime.catalog().items[0].searchable_text, stored_text);
+    }
+
+    #[test]
+    fn validate_accepts_prepared_catalogs() {
         let mut catalog = sample_catalog();
-        catalog.items[0].searchable_text.clear();
+        prepare_catalog(&mut catalog);
+        assert!(catalog.validate().is_empty());
+    }
+
+    #[test]
+    fn validate_reports_broken_products_and_variations() {
+        let mut catalog = sample_catalog();
+        let mut duplicate = catalog.items[0].clone();
+        duplicate.variations[0].furniture_id = Some("2".into());
+        catalog.items.push(duplicate);
+        catalog.items.push(Furniture {
+            id: " ".into(),
+            variations: vec![Variation::default()],
+            ..Default::default()
+        });
+        prepare_catalog(&mut catalog);
+
+        let issues: Vec<(ValidationIssueKind, String)> = catalog
+            .validate()
+            .into_iter()
+            .map(|issue| (issue.kind, issue.id))
+            .collect();
+        assert_eq!(
+            issues,
+            [
+                (ValidationIssueKind::DuplicateId, "1".to_string()),
+                (ValidationIssueKind::DuplicateId, "v1".to_string()),
+                (ValidationIssueKind::OrphanVariation, "v1".to_string()),
+                (ValidationIssueKind::EmptyId, " ".to_string()),
+                (ValidationIssueKind::MissingText, " ".to_string()),
+                (ValidationIssueKind::EmptyId, String::new()),
+            ]
+        );
+        assert!(
+            catalog.validate()[2]
+                .message
+                .contains("attached to product '1' but names '2'")
+        );
+    }
+
+    #[test]
+    fn decode_rejects_unknown_versions_and_migrates_older_versions() {
+        let mut bytes = encode_catalog(&sample_catalog()).expect("encode");
+        bytes[CATALOG_MAGIC.len()..HEADER_LEN].copy_from_slice(&99u16.to_le_bytes());
+        assert!(matches!(
+            decode_catalog(&bytes),
+            Err(DecodeError::UnsupportedVersion { found: 99 })
+        ));
+
+        let v2 = legacy::CatalogV2 {
+            items: sample_catalog().items,
+        };
+        let headerless = bincode_options().serialize(&v2).expect("encode v2");
+        assert!(matches!(
+            decode_catalog(&headerless),
+            Err(DecodeError::MissingHeader)
+        ));
+        assert_eq!(
+            decode_catalog_legacy(&headerless).expect("legacy").items[0].id,
+            "1"
+        );
+        let mut versioned = CATALOG_MAGIC.to_vec();
+        versioned.extend_from_slice(&2u16.to_le_bytes());
+        versioned.extend_from_slice(&headerless);
+        let decoded = decode_catalog(&versioned).expect("migrate v2");
+        assert_eq!(decoded.items[0].id, "1");
+        assert!(decoded.meta.is_none());
+
+        let v1 = legacy::CatalogV1 {
+            items: vec![legacy::FurnitureV1 {
+                id: "old".into(),
+                name: Some("Old Chair".into()),
+                ..Default::default()
+            }],
+        };
+        let body = bincode_options().serialize(&v1).expect("encode v1");
+        let mut versioned = CATALOG_MAGIC.to_vec();
+        versioned.extend_from_slice(&1u16.to_le_bytes());
+        versioned.extend_from_slice(&body);
+        for decoded in [decode_catalog(&versioned), decode_catalog_legacy(&body)] {
+            let decoded = decoded.expect("migrate v1");
+            assert_eq!(decoded.items[0].name.as_deref(), Some("Old Chair"));
+            assert_eq!(decoded.items[0].category, None);
+        }
+    }
+
+    #[test]
+    fn blobs_with_stale_searchable_text_are_reindexed() {
+        // What catalog-tools precomputed before queries were diacritic-folded:
+        // lowercased, accents kept.
+        let v1 = legacy::CatalogV1 {
+            items: vec![legacy::FurnitureV1 {
+                id: "1".into(),
+                name: Some("Sofá de Linho".into()),
+                searchable_text: "sofá de linho".into(),
+                ..Default::default()
+            }],
+        };
+        let headerless = bincode_options().serialize(&v1).expect("encode v1");
+
+        let mut v3 = Catalog {
+            items: vec![Furniture {
+                id: "1".into(),
+                name: Some("Sofá de Linho".into()),
+                ..Default::default()
+            }],
+            meta: None,
+        };
+        v3.items[0].searchable_text = "sofá de linho".into();
+        let mut versioned = CATALOG_MAGIC.to_vec();
+        versioned.extend_from_slice(&3u16.to_le_bytes());
+        versioned.extend_from_slice(&bincode_options().serialize(&v3).expect("encode v3"));
+
+        for decoded in [
+            decode_catalog_legacy(&headerless),
+            decode_catalog(&versioned),
+        ] {
+            let search = SearchEngine::from_catalog(decoded.expect("decode"));
+            let results = search.search_results("sofa linho");
+            assert_eq!(results.len(), 1);
+            assert_eq!(results[0].id, "1");
+        }
+    }
+
+    #[test]
+    fn decode_any_reads_json_catalogs() {
+        let catalog = sample_catalog();
+        let json = encode_catalog_json(&catalog).expect("encode json");
+        let decoded = decode_catalog_any(&json).expect("decode json");
+        assert_eq!(decoded.items[0].id, "1");
+
+        let bytes = encode_catalog(&catalog).expect("encode");
+        assert_eq!(decode_catalog_any(&bytes).expect("decode").items.len(), 1);
+        assert!(decode_catalog_any(b"not a catalog").is_err());
+    }
+
+    #[test]
+    fn prepare_catalog_builds_searchable_text() {
+        let mut catalog = sample_catalog();
+        catalog.items[0].searchable_text.clear();
         prepare_catalog(&mut catalog);
         assert!(!catalog.items[0].searchable_text.is_empty());
     }
+
+    #[test]
+    fn search_ignores_diacritics() {
+        let mut catalog = sample_catalog();
+        catalog.items[0].name = Some("Sofá Poltrona".into());
+        let search = CatalogSearch::from_catalog(catalog);
+
+        let results = search.search_results("sofa");
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].name.as_deref(), Some("Sofá Poltrona"));
+        assert_eq!(search.search_results("SOFÁ").len(), 1);
+    }
+
+    #[test]
+    fn search_matches_spec_terms_individually() {
+        let mut catalog = sample_catalog();
+        catalog.items[0].quick_specifications =
+            Some("Soft-close drawers | Solid wood frame • Modular design".into());
+        let search = CatalogSearch::from_catalog(catalog);
+
+        for query in ["soft-close", "softclose", "drawers frame", "wood, modular"] {
+            assert_eq!(search.search_results(query).len(), 1, "query {query:?}");
+        }
+        assert!(search.search_results("softclose steel").is_empty());
+    }
+
+    #[test]
+    fn search_applies_numeric_ranges() {
+        let search = CatalogSearch::from_catalog(sample_catalog());
+
+        assert_eq!(search.search_results("chair price:<200").len(), 1);
+        assert!(search.search_results("chair price:>200").is_empty());
+        assert_eq!(search.search_results("price:150..199").len(), 1);
+        assert!(search.search_results("weight:..10").is_empty());
+        assert!(search.search_results("color:brown").is_empty());
+    }
+
+    #[test]
+    fn lookup_by_slug_prefers_highest_priority() {
+        let mut catalog = sample_catalog();
+        let mut duplicate = catalog.items[0].clone();
+        duplicate.id = "2".into();
+        duplicate.priority = Some(1);
+        catalog.items[0].priority = Some(5);
+        catalog.items.push(duplicate);
+        let search = CatalogSearch::from_catalog(catalog);
+
+        assert_eq!(search.get_by_slug("sample-chair").unwrap().id, "2");
+        assert_eq!(search.get_by_id("1").unwrap().id, "1");
+        assert!(search.get_by_slug("missing").is_none());
+    }
+
+    #[test]
+    fn search_respects_configured_fields() {
+        let fields = SearchField::parse_list(&["name".into(), "color".into()]).unwrap();
+        let search = CatalogSearch::with_fields(sample_catalog(), &fields);
+
+        assert_eq!(search.search_results("chair brown").len(), 1);
+        assert!(search.search_results("reading").is_empty());
+        assert!(SearchField::parse_list(&["price".into()]).is_err());
+    }
+
+    #[test]
+    fn debug_search_breaks_down_scores() {
+        let search = CatalogSearch::from_catalog(sample_catalog());
+
+        assert!(search.search_results("chair")[0].score_breakdown.is_none());
+
+        let results = search.search_debug("chair");
+        let breakdown = results[0].score_breakdown.as_ref().unwrap();
+        assert_eq!(breakdown.tokens.len(), 1);
+        assert_eq!(breakdown.tokens[0].token, "chair");
+        assert_eq!(breakdown.tokens[0].name, 1.0);
+        assert_eq!(breakdown.tokens[0].slug, 0.5);
+        let total: f32 = breakdown
+            .tokens
+            .iter()
+            .map(|t| t.text + t.name + t.slug)
+            .sum();
+        assert_eq!(results[0].score, total + breakdown.priority);
+    }
+
+    #[test]
+    fn bm25_prefers_short_precise_documents() {
+        let mut catalog = sample_catalog();
+        let mut long = catalog.items[0].clone();
+        long.id = "2".into();
+        long.name = Some("Reading Lamp".into());
+        long.slug = Some("reading-lamp".into());
+        long.description_text = Some(format!("walnut {}", "plain filler text ".repeat(40)));
+        catalog.items[0].description_text = Some("walnut walnut".into());
+        catalog.items[0].priority = Some(10);
+        long.priority = Some(0);
+        catalog.items.push(long);
+
+        let options = SearchOptions {
+            ranking: RankingAlgorithm::Bm25,
+            ..Default::default()
+        };
+        let search = CatalogSearch::with_options(catalog, options);
+        let results = search.search_results("walnut");
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].id, "1");
+        assert!(results[0].score > results[1].score);
+    }
+
+    #[test]
+    fn stopwords_are_ignored() {
+        let mut catalog = sample_catalog();
+        catalog.items[0].name = Some("Mesa de Jantar".into());
+        let search = CatalogSearch::from_catalog(catalog.clone());
+
+        assert_eq!(search.search_results("a mesa de jantar").len(), 1);
+        assert!(!search.catalog().items[0].searchable_text.contains(" de "));
+        // Only stopwords: fall back to the priority listing.
+        assert_eq!(search.search_results("de a").len(), 1);
+
+        let options = SearchOptions {
+            stopwords: Some(vec!["mesa".into()]),
+            ..Default::default()
+        };
+        let search = CatalogSearch::with_options(catalog, options);
+        assert_eq!(search.search_results("mesa de jantar").len(), 1);
+        assert!(
+            search.catalog().items[0]
+                .searchable_text
+                .starts_with("de jantar")
+        );
+    }
+
+    #[test]
+    fn synonyms_match_with_reduced_credit() {
+        let synonyms = |entries: &[(&str, &str)]| {
+            entries
+                .iter()
+                .map(|(word, synonym)| (word.to_string(), vec![synonym.to_string()]))
+                .collect()
+        };
+        let options = SearchOptions {
+            synonyms: synonyms(&[("Couch", "chair")]),
+            ..Default::default()
+        };
+        let search = CatalogSearch::with_options(sample_catalog(), options);
+
+        let direct = search.search_results("chair")[0].score;
+        let results = search.search_debug("couch");
+        assert_eq!(results.len(), 1);
+        assert!(results[0].score < direct);
+        let breakdown = results[0].score_breakdown.as_ref().unwrap();
+        assert_eq!(breakdown.tokens[0].token, "chair");
+        assert_eq!(search.search_results("comfy couch").len(), 1);
+        // Every query word still has to match, through a synonym or not.
+        assert!(search.search_results("couch velvet").is_empty());
+
+        let options = SearchOptions {
+            synonyms: synonyms(&[("chair", "couch")]),
+            ..Default::default()
+        };
+        let one_way = CatalogSearch::with_options(sample_catalog(), options.clone());
+        assert!(one_way.search_results("couch").is_empty());
+        let both_ways = CatalogSearch::with_options(
+            sample_catalog(),
+            SearchOptions {
+                bidirectional_synonyms: true,
+                ..options
+            },
+        );
+        assert_eq!(both_ways.search_results("couch").len(), 1);
+    }
+
+    #[test]
+    fn suggest_ranks_word_starts_above_mid_word_matches() {
+        let product = |id: &str, name: &str, slug: &str, priority: i64| Furniture {
+            id: id.into(),
+            name: Some(name.into()),
+            slug: Some(slug.into()),
+            priority: Some(priority),
+            ..Default::default()
+        };
+        let catalog = Catalog {
+            items: vec![
+                product("1", "Redwood Table", "redwood-table", 0),
+                product("2", "Wood Chair", "wood-chair", 5),
+                product("3", "Oak Shelf", "shelf-woodgrain", 0),
+                product("4", "Wooden Bench", "wooden-bench", 2),
+            ],
+            meta: None,
+        };
+        let search = CatalogSearch::from_catalog(catalog);
+
+        let suggestions = search.suggest("WOOD", 10);
+        let ranked: Vec<(&str, SuggestField, bool)> = suggestions
+            .iter()
+            .map(|s| (s.id.as_str(), s.field, s.word_start))
+            .collect();
+        assert_eq!(
+            ranked,
+            [
+                ("4", SuggestField::Name, true),
+                ("2", SuggestField::Name, true),
+                ("3", SuggestField::Slug, true),
+                ("1", SuggestField::Name, false),
+            ]
+        );
+        assert!(suggestions[2].score > suggestions[3].score);
+        assert_eq!(search.suggest("wood", 2).len(), 2);
+        assert!(search.suggest("  ", 10).is_empty());
+    }
+
+    #[test]
+    fn recency_boost_decays_over_its_window() {
+        let boost = RecencyBoost {
+            window_days: 30.0,
+            max_bonus: 2.0,
+        };
+        let day = 86_400_000;
+        let now = 1_700_000_000_000;
+        assert_eq!(boost.bonus(Some(now), now), 2.0);
+        assert!((boost.bonus(Some(now - 15 * day), now) - 1.0).abs() < 1e-6);
+        assert_eq!(boost.bonus(Some(now - 30 * day), now), 0.0);
+        assert_eq!(boost.bonus(None, now), 0.0);
+        assert!(
+            RecencyBoost {
+                window_days: 0.0,
+                ..boost
+            }
+            .validate()
+            .is_err()
+        );
+
+        let created = |days_ago: i64| {
+            chrono::DateTime::from_timestamp_millis(now_ms() - days_ago * day)
+                .unwrap()
+                .to_rfc3339()
+        };
+        let mut catalog = sample_catalog();
+        catalog.items[0].created_at = Some(created(90));
+        let mut newer = catalog.items[0].clone();
+        newer.id = "2".into();
+        newer.name = Some("Sample Chair II".into());
+        newer.created_at = Some(created(1));
+        catalog.items.push(newer);
+
+        let plain = CatalogSearch::from_catalog(catalog.clone());
+        assert_eq!(plain.search_results("chair")[0].id, "1");
+        let options = SearchOptions {
+            recency_boost: Some(boost),
+            ..Default::default()
+        };
+        let boosted = CatalogSearch::with_options(catalog, options);
+        let results = boosted.search_debug("chair");
+        assert_eq!(results[0].id, "2");
+        let recency = |idx: usize| results[idx].score_breakdown.as_ref().unwrap().recency;
+        assert!(recency(0) > 1.8);
+        assert_eq!(recency(1), 0.0);
+    }
+
+    #[test]
+    fn cached_results_drop_the_recency_bonus_once_the_window_closes() {
+        use std::sync::atomic::{AtomicI64, Ordering};
+
+        static NOW: AtomicI64 = AtomicI64::new(0);
+        let day = 86_400_000;
+        let created = 1_700_000_000_000;
+
+        let mut catalog = sample_catalog();
+        let mut newer = catalog.items[0].clone();
+        newer.id = "2".into();
+        newer.name = Some("Sample Chair II".into());
+        newer.created_at =
+            chrono::DateTime::from_timestamp_millis(created).map(|created| created.to_rfc3339());
+        catalog.items.push(newer);
+        let options = SearchOptions {
+            recency_boost: Some(RecencyBoost {
+                window_days: 30.0,
+                max_bonus: 2.0,
+            }),
+            ..Default::default()
+        };
+        let mut search = SearchEngine::with_options(catalog, options);
+        search.set_clock(|| NOW.load(Ordering::Relaxed));
+
+        NOW.store(created + day, Ordering::Relaxed);
+        assert_eq!(search.search_results("chair")[0].id, "2");
+        assert_eq!(search.search_results("chair")[0].id, "2");
+
+        NOW.store(created + 31 * day, Ordering::Relaxed);
+        assert_eq!(search.search_results("chair")[0].id, "1");
+    }
+
+    #[test]
+    fn empty_results_suggest_closest_term() {
+        let search = CatalogSearch::from_catalog(sample_catalog());
+
+        let response = search.search_with_suggestion("comfy chiar");
+        assert!(response.results.is_empty());
+        assert_eq!(response.suggestion.as_deref(), Some("comfy chair"));
+
+        assert!(search.search_with_suggestion("chair").suggestion.is_none());
+        assert!(search.search_with_suggestion("xyzzyq").suggestion.is_none());
+    }
+
+    #[test]
+    fn grouped_search_buckets_by_category() {
+        let mut catalog = sample_catalog();
+        let mut sofa = catalog.items[0].clone();
+        sofa.id = "2".into();
+        sofa.category = Some("Sofa".into());
+        catalog.items.push(sofa);
+        let search = CatalogSearch::from_catalog(catalog);
+
+        let groups = search.search_grouped("chair");
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups["Sofa"].count, 1);
+        assert_eq!(groups["Sofa"].results[0].id, "2");
+        assert_eq!(groups[UNCATEGORIZED].results[0].id, "1");
+    }
+
+    #[test]
+    fn prepare_can_drop_empty_variations() {
+        let mut catalog = sample_catalog();
+        catalog.items[0].variations.push(Variation {
+            id: "empty".into(),
+            ..Default::default()
+        });
+
+        let mut lossless = catalog.clone();
+        prepare_catalog(&mut lossless);
+        assert_eq!(lossless.items[0].variations.len(), 2);
+
+        let options = PrepareOptions {
+            drop_empty_variations: true,
+            ..Default::default()
+        };
+        let report = prepare_catalog_with_options(&mut catalog, &options);
+        assert_eq!(report.dropped_variations, 1);
+        assert_eq!(catalog.items[0].variations.len(), 1);
+        assert_eq!(catalog.items[0].has_variations, Some(true));
+    }
+
+    #[test]
+    fn prepare_can_merge_products_sharing_integration_id() {
+        let mut catalog = sample_catalog();
+        catalog.items[0].integration_id = Some("ext-1".into());
+        catalog.items[0].priority = Some(5);
+        catalog.items[0].variations.push(Variation {
+            id: "v3".into(),
+            furniture_id: Some("1".into()),
+            ..Default::default()
+        });
+        let mut duplicate = catalog.items[0].clone();
+        duplicate.id = "2".into();
+        duplicate.priority = Some(1);
+        duplicate.variations = vec![
+            Variation {
+                id: "v1".into(),
+                furniture_id: Some("2".into()),
+                ..Default::default()
+            },
+            Variation {
+                id: "v2".into(),
+                furniture_id: Some("2".into()),
+                color: Some("Teal".into()),
+                ..Default::default()
+            },
+        ];
+        catalog.items.push(duplicate);
+        catalog.items.push(Furniture {
+            id: "3".into(),
+            name: Some("Side Table".into()),
+            ..Default::default()
+        });
+
+        let options = PrepareOptions {
+            dedupe_by_integration_id: true,
+            ..Default::default()
+        };
+        let report = prepare_catalog_with_options(&mut catalog, &options);
+
+        assert_eq!(report.merged_products, 1);
+        let ids: Vec<&str> = catalog.items.iter().map(|f| f.id.as_str()).collect();
+        assert_eq!(ids, vec!["2", "3"]);
+        let variation_ids: Vec<&str> = catalog.items[0]
+            .variations
+            .iter()
+            .map(|v| v.id.as_str())
+            .collect();
+        assert_eq!(variation_ids, vec!["v1", "v2", "v3"]);
+        assert!(catalog.items[0].searchable_text.contains("teal"));
+        // Moved variations point at the product they now belong to.
+        assert!(catalog.validate().is_empty(), "{:?}", catalog.validate());
+    }
+
+    #[test]
+    fn variations_are_sorted_by_order() {
+        let mut catalog = sample_catalog();
+        catalog.items[0].variations = [("c", None), ("b", Some(2)), ("z", Some(0)), ("a", None)]
+            .into_iter()
+            .map(|(id, order)| Variation {
+                id: id.into(),
+                order,
+                ..Default::default()
+            })
+            .collect();
+        let search = CatalogSearch::from_catalog(catalog);
+
+        let ids = |result: &ProductResult| -> Vec<String> {
+            result.variations.iter().map(|v| v.id.clone()).collect()
+        };
+        let expected = vec!["z", "b", "a", "c"];
+        assert_eq!(ids(&search.all()[0]), expected);
+        assert_eq!(ids(&search.search_results("chair")[0]), expected);
+        assert_eq!(ids(&search.get_by_id("1").unwrap()), expected);
+    }
+
+    #[test]
+    fn in_stock_only_hides_unavailable_products() {
+        let mut catalog = sample_catalog();
+        catalog.items[0].status = Some("OUT_OF_STOCK".into());
+        for (id, status) in [("2", Some("active")), ("3", None)] {
+            let mut item = catalog.items[0].clone();
+            item.id = id.into();
+            item.status = status.map(Into::into);
+            catalog.items.push(item);
+        }
+        let ids = |results: Vec<ProductResult>| -> Vec<String> {
+            let mut ids: Vec<String> = results.into_iter().map(|result| result.id).collect();
+            ids.sort();
+            ids
+        };
+
+        let search = CatalogSearch::from_catalog(catalog.clone());
+        assert_eq!(ids(search.search_results("chair")), vec!["1", "2", "3"]);
+
+        let in_stock = CatalogSearch::with_options(
+            catalog.clone(),
+            SearchOptions {
+                in_stock_only: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(ids(in_stock.search_results("chair")), vec!["2", "3"]);
+        assert_eq!(ids(in_stock.top_by_priority(10)), vec!["2", "3"]);
+
+        let strict = CatalogSearch::with_options(
+            catalog,
+            SearchOptions {
+                in_stock_only: true,
+                available_statuses: Some(vec!["Active".into()]),
+                hide_missing_status: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(ids(strict.search_results("chair")), vec!["2"]);
+        assert_eq!(ids(strict.top_by_priority(10)), vec!["2"]);
+    }
+
+    #[test]
+    fn count_matches_search_without_the_page_cap() {
+        let mut catalog = sample_catalog();
+        for idx in 2..=60 {
+            let mut item = catalog.items[0].clone();
+            item.id = idx.to_string();
+            item.price = Some(100.0 + idx as f64);
+            catalog.items.push(item);
+        }
+        let search = CatalogSearch::from_catalog(catalog);
+
+        assert_eq!(search.count("chair"), 60);
+        assert_eq!(search.search_results("chair").len(), 50);
+        assert_eq!(search.count("chair price:<=110"), 9);
+        assert_eq!(search.search_results("chair price:<=110").len(), 9);
+        assert_eq!(search.count("missing"), 0);
+        assert_eq!(search.count(""), 60);
+    }
+
+    #[test]
+    fn weights_can_be_changed_after_construction() {
+        let mut search = CatalogSearch::from_catalog(sample_catalog());
+        let default_score = search.search_results("chair")[0].score;
+
+        let weights = ScoringWeights {
+            name: 3.0,
+            ..Default::default()
+        };
+        search.set_weights(weights).expect("valid weights");
+        assert_eq!(search.search_results("chair")[0].score, default_score + 2.0);
+
+        let invalid = ScoringWeights {
+            term: -1.0,
+            ..Default::default()
+        };
+        assert!(search.set_weights(invalid).is_err());
+        assert_eq!(search.weights(), weights);
+    }
+
+    #[test]
+    fn empty_query_limit_is_configurable() {
+        let mut catalog = sample_catalog();
+        for idx in 2..=60 {
+            let mut item = catalog.items[0].clone();
+            item.id = idx.to_string();
+            catalog.items.push(item);
+        }
+
+        let search = CatalogSearch::from_catalog(catalog.clone());
+        assert_eq!(search.search_results("").len(), DEFAULT_EMPTY_QUERY_LIMIT);
+
+        let search = CatalogSearch::with_options(
+            catalog,
+            SearchOptions {
+                empty_query: EmptyQueryBehavior::TopByPriority(48),
+                ..Default::default()
+            },
+        );
+        assert_eq!(search.search_results("").len(), 48);
+        assert_eq!(search.search_results("the").len(), 48);
+    }
+
+    #[test]
+    fn empty_query_can_return_every_product() {
+        let mut catalog = sample_catalog();
+        for idx in 2..=60 {
+            let mut item = catalog.items[0].clone();
+            item.id = idx.to_string();
+            item.priority = Some(idx);
+            catalog.items.push(item);
+        }
+        let search = CatalogSearch::with_options(
+            catalog,
+            SearchOptions {
+                empty_query: EmptyQueryBehavior::All,
+                ..Default::default()
+            },
+        );
+
+        let results = search.search_results("");
+        assert_eq!(results.len(), 60);
+        assert_eq!(results[0].id, "2");
+        assert_eq!(results.len(), search.count(""));
+        assert_eq!(search.search_results("chair").len(), 50);
+        let options: SearchOptions =
+            serde_json::from_str(r#"{ "empty_query": { "top_by_priority": 12 } }"#).unwrap();
+        assert_eq!(options.empty_query, EmptyQueryBehavior::TopByPriority(12));
+    }
+
+    #[test]
+    fn related_ranks_by_shared_terms_and_category() {
+        let mut catalog = sample_catalog();
+        catalog.items[0].category = Some("Chairs".into());
+        let mut armchair = catalog.items[0].clone();
+        armchair.id = "2".into();
+        armchair.name = Some("Reading Armchair".into());
+        armchair.slug = Some("reading-armchair".into());
+        armchair.searchable_text.clear();
+        let mut lamp = armchair.clone();
+        lamp.id = "3".into();
+        lamp.name = Some("Reading Lamp".into());
+        lamp.slug = Some("reading-lamp".into());
+        lamp.description_text = Some("Brass lamp for reading".into());
+        lamp.quick_description = None;
+        lamp.quick_specifications = None;
+        lamp.category = Some("Lighting".into());
+        let mut table = lamp.clone();
+        table.id = "4".into();
+        table.name = Some("Oak Table".into());
+        table.slug = Some("oak-table".into());
+        table.description_text = None;
+        table.variations.clear();
+        catalog.items.extend([armchair, lamp, table]);
+        let search = CatalogSearch::from_catalog(catalog);
+
+        let related: Vec<String> = search
+            .related("1", 10)
+            .into_iter()
+            .map(|result| result.id)
+            .collect();
+        assert_eq!(related, vec!["2", "3"]);
+        assert_eq!(search.related("1", 1).len(), 1);
+        assert!(search.related("missing", 10).is_empty());
+    }
+
+    #[test]
+    fn discount_percent_requires_a_lower_promotion() {
+        assert_eq!(
+            discount_percent(Some(true), Some(200.0), Some(150.0)),
+            Some(25.0)
+        );
+        assert_eq!(
+            discount_percent(Some(true), Some(300.0), Some(199.0)),
+            Some(34.0)
+        );
+        assert_eq!(
+            discount_percent(Some(false), Some(200.0), Some(150.0)),
+            None
+        );
+        assert_eq!(discount_percent(Some(true), Some(200.0), Some(250.0)), None);
+        assert_eq!(discount_percent(Some(true), Some(0.0), Some(0.0)), None);
+        assert_eq!(discount_percent(Some(true), None, Some(150.0)), None);
+    }
+
+    #[test]
+    fn html_descriptions_are_reduced_to_plain_text() {
+        assert_eq!(
+            html::strip_html(
+                "<p>Sofa <b>Lisboa</b></p>\n<ul><li>3 lugares</li><li>Linho &amp; algod&#227;o</li></ul>"
+            ),
+            "Sofa Lisboa 3 lugares Linho & algodão"
+        );
+        assert_eq!(
+            html::strip_html("<style>p { color: red }</style>Price < 200 &gt; 100 &bogus;"),
+            "Price < 200 > 100 &bogus;"
+        );
+        assert_eq!(html::strip_html("plain text"), "plain text");
+
+        let mut catalog = sample_catalog();
+        catalog.items[0].description_text = Some("<p>A <em>comfy</em> chair</p>".into());
+        let mut raw = catalog.clone();
+        prepare_catalog(&mut raw);
+        assert_eq!(
+            raw.items[0].description_text.as_deref(),
+            Some("<p>A <em>comfy</em> chair</p>")
+        );
+
+        catalog.items[0].searchable_text.clear();
+        let options = PrepareOptions {
+            strip_html: true,
+            ..Default::default()
+        };
+        prepare_catalog_with_options(&mut catalog, &options);
+        assert_eq!(
+            catalog.items[0].description_text.as_deref(),
+            Some("A comfy chair")
+        );
+        assert!(!catalog.items[0].searchable_text.contains('<'));
+    }
+
+    #[test]
+    fn timestamps_in_common_formats_are_parsed() {
+        let expected = Some(1_714_564_800_000);
+        for value in [
+            "2024-05-01T12:00:00Z",
+            "2024-05-01T09:00:00-03:00",
+            "Wed, 01 May 2024 12:00:00 +0000",
+            "2024-05-01T12:00:00",
+            "2024-05-01 12:00:00.000",
+        ] {
+            assert_eq!(timestamp_ms(value), expected, "{value}");
+        }
+        assert_eq!(timestamp_ms("2024-05-01"), Some(1_714_521_600_000));
+        assert_eq!(timestamp_ms("yesterday"), None);
+    }
+
+    #[test]
+    fn recent_lists_newest_updates_first() {
+        let mut catalog = sample_catalog();
+        let template = catalog.items[0].clone();
+        catalog.items = [
+            ("old", Some("2023-01-01T00:00:00Z")),
+            ("unknown", None),
+            ("new", Some("2024-06-01 08:30:00")),
+            ("garbled", Some("not a date")),
+            ("mid", Some("2024-01-15")),
+        ]
+        .into_iter()
+        .map(|(id, updated_at)| Furniture {
+            id: id.into(),
+            updated_at: updated_at.map(String::from),
+            ..template.clone()
+        })
+        .collect();
+        let search = CatalogSearch::from_catalog(catalog);
+
+        let ids = |results: Vec<ProductResult>| -> Vec<String> {
+            results.into_iter().map(|result| result.id).collect()
+        };
+        assert_eq!(
+            ids(search.recent(10)),
+            ["new", "mid", "old", "unknown", "garbled"]
+        );
+        assert_eq!(ids(search.recent(2)), ["new", "mid"]);
+    }
+
+    #[test]
+    fn from_price_falls_back_to_the_cheapest_variation() {
+        let variations = |prices: &[Option<f64>]| {
+            prices
+                .iter()
+                .map(|&price| Variation {
+                    price,
+                    ..Default::default()
+                })
+                .collect()
+        };
+        let mut furniture = Furniture {
+            price: Some(500.0),
+            variations: variations(&[Some(300.0), Some(200.0)]),
+            ..Default::default()
+        };
+        assert_eq!(from_price(&furniture), Some(500.0));
+
+        furniture.price = None;
+        assert_eq!(from_price(&furniture), Some(200.0));
+
+        furniture.variations = variations(&[None, Some(f64::NAN), Some(350.0)]);
+        assert_eq!(from_price(&furniture), Some(350.0));
+
+        furniture.variations = variations(&[None]);
+        assert_eq!(from_price(&furniture), None);
+    }
+
+    #[test]
+    fn names_sort_with_accents_folded() {
+        let mut names: Vec<Option<String>> = ["Zinco", "Água", "banco", "Abajur", "Ébano"]
+            .into_iter()
+            .map(|name| Some(name.to_string()))
+            .chain([None])
+            .collect();
+        names.sort_by(compare_name);
+
+        let sorted: Vec<&str> = names.iter().flatten().map(String::as_str).collect();
+        assert_eq!(sorted, vec!["Abajur", "Água", "banco", "Ébano", "Zinco"]);
+        assert!(names.last().unwrap().is_none());
+    }
+
+    #[test]
+    fn search_reports_matched_variations() {
+        let search = CatalogSearch::from_catalog(sample_catalog());
+
+        let by_color = search.search_results("brown");
+        assert_eq!(by_color[0].matched_variation_ids, vec!["v1".to_string()]);
+
+        let by_product = search.search_results("reading");
+        assert!(by_product[0].matched_variation_ids.is_empty());
+    }
+
+    #[test]
+    fn slugify_transliterates_accents_and_keeps_ascii_output() {
+        use crate::slug::slugify;
+
+        assert_eq!(slugify("Sofa Bed 3 Seats"), "sofa-bed-3-seats");
+        assert_eq!(slugify("  Oak & Walnut  "), "oak---walnut");
+        assert_eq!(
+            slugify("Cadeira Água Pé-de-Moça"),
+            "cadeira-agua-pe-de-moca"
+        );
+        assert_eq!(slugify("Straße Ærø"), "strasse-aero");
+        assert_eq!(slugify("沙发"), "");
+    }
+
+    #[test]
+    fn empty_and_corrupt_blobs_report_the_likely_cause() {
+        for decode in [decode_catalog, decode_catalog_any, decode_catalog_legacy] {
+            let err = decode(&[]).unwrap_err();
+            assert!(matches!(err, DecodeError::Empty));
+            assert!(err.to_string().contains("catalog data is empty"));
+        }
+
+        let mut truncated = encode_catalog(&sample_catalog()).unwrap();
+        truncated.truncate(truncated.len() / 2);
+        let err = decode_catalog_any(&truncated).unwrap_err();
+        assert!(matches!(err, DecodeError::Bincode(_)));
+        assert!(err.to_string().contains("truncated or corrupt"));
+    }
+
+    #[test]
+    fn empty_engine_answers_queries_before_a_catalog_loads() {
+        let search = CatalogSearch::empty();
+
+        assert!(search.search_results("chair").is_empty());
+        assert!(search.search_results("").is_empty());
+        assert_eq!(search.count(""), 0);
+        assert!(search.meta().is_none());
+    }
+
+    #[test]
+    fn repeated_queries_are_served_from_the_result_cache() {
+        let mut search = CatalogSearch::from_catalog(sample_catalog());
+        let cached_keys = |search: &CatalogSearch| search.cached_queries();
+
+        let first = search.search_results("Comfy  Chair");
+        let again = search.search_results(" comfy chair ");
+        assert_eq!(cached_keys(&search), ["comfy chair"]);
+        assert_eq!(first[0].score, again[0].score);
+
+        search
+            .set_weights(ScoringWeights {
+                term: 3.0,
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(cached_keys(&search).is_empty());
+        assert!(search.search_results("comfy chair")[0].score > first[0].score);
+
+        let uncached = CatalogSearch::with_options(
+            sample_catalog(),
+            SearchOptions {
+                result_cache_size: Some(0),
+                ..Default::default()
+            },
+        );
+        uncached.search_results("chair");
+        assert!(cached_keys(&uncached).is_empty());
+
+        let small = CatalogSearch::with_options(
+            sample_catalog(),
+            SearchOptions {
+                result_cache_size: Some(2),
+                ..Default::default()
+            },
+        );
+        for query in ["chair", "walnut", "chair", "leather"] {
+            small.search_results(query);
+        }
+        assert_eq!(cached_keys(&small), ["chair", "leather"]);
+    }
+
+    #[test]
+    fn promo_boost_lifts_promotional_products() {
+        let mut catalog = sample_catalog();
+        let mut promo = catalog.items[0].clone();
+        promo.id = "2".into();
+        promo.name = Some("Sample Chair Deluxe".into());
+        promo.slug = Some("sample-chair-deluxe".into());
+        promo.is_promotional = Some(true);
+        catalog.items[0].name = Some("Sample Chair Classic".into());
+        catalog.items.push(promo);
+
+        let ids = |weights: ScoringWeights| {
+            let search = CatalogSearch::with_options(
+                catalog.clone(),
+                SearchOptions {
+                    weights,
+                    ..Default::default()
+                },
+            );
+            search
+                .search_results("chair")
+                .into_iter()
+                .map(|result| result.id)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(ids(ScoringWeights::default()), ["1", "2"]);
+        let boosted = ScoringWeights {
+            promo: 0.5,
+            ..Default::default()
+        };
+        assert_eq!(ids(boosted), ["2", "1"]);
+    }
+
+    #[test]
+    fn min_score_drops_weak_matches() {
+        let mut catalog = sample_catalog();
+        let mut weak = catalog.items[0].clone();
+        weak.id = "2".into();
+        weak.name = Some("Reading Lamp".into());
+        weak.slug = Some("reading-lamp".into());
+        catalog.items.push(weak);
+
+        let search = |min_score: Option<f32>| {
+            CatalogSearch::with_options(
+                catalog.clone(),
+                SearchOptions {
+                    min_score,
+                    ..Default::default()
+                },
+            )
+        };
+
+        let all = search(None).search_results("comfy chair");
+        assert_eq!(all.len(), 2);
+        let threshold = (all[0].score + all[1].score) / 2.0;
+
+        let strict = search(Some(threshold));
+        let ids: Vec<_> = strict
+            .search_results("comfy chair")
+            .into_iter()
+            .map(|result| result.id)
+            .collect();
+        assert_eq!(ids, ["1"]);
+        assert_eq!(strict.count("comfy chair"), 1);
+        assert_eq!(strict.search_results("").len(), 2);
+    }
+
+    #[test]
+    fn facet_values_count_products_per_value() {
+        let mut catalog = sample_catalog();
+        catalog.items[0].category = Some("Chairs".into());
+        let mut second = catalog.items[0].clone();
+        second.id = "2".into();
+        second.slug = Some("sample-chair-2".into());
+        second.category = Some(" Chairs ".into());
+        second.variations[0].color = Some("Azul".into());
+        let mut brown = second.variations[0].clone();
+        brown.id = "v3".into();
+        brown.color = Some("Brown".into());
+        second.variations.push(brown.clone());
+        brown.id = "v4".into();
+        second.variations.push(brown);
+        catalog.items.push(second);
+        let search = CatalogSearch::from_catalog(catalog);
+
+        let colors = search.facet_values("color").unwrap();
+        let colors: Vec<_> = colors
+            .iter()
+            .map(|facet| (facet.value.as_str(), facet.count))
+            .collect();
+        assert_eq!(colors, [("Azul", 1), ("Brown", 2)]);
+        assert_eq!(
+            search.facet_values("category").unwrap(),
+            [FacetValue {
+                value: "Chairs".into(),
+                count: 2
+            }]
+        );
+
+        let err = search.facet_values("material").unwrap_err();
+        assert_eq!(err.code, ErrorCode::UnknownFacet);
+        assert!(err.message.contains("color, secondary_color, category"));
+    }
+
+    #[test]
+    fn named_catalogs_are_searched_alongside_the_primary() {
+        let mut search = CatalogSearch::from_catalog(sample_catalog());
+        let mut other = sample_catalog();
+        other.items[0].id = "sofa-1".into();
+        other.items[0].name = Some("Linen Sofa".into());
+        other.items[0].slug = Some("linen-sofa".into());
+        other.items[0].category = Some("Sofas".into());
+        other.items[0].searchable_text.clear();
+        let mut velvet = other.items[0].clone();
+        velvet.id = "sofa-2".into();
+        velvet.name = Some("Velvet Sofa".into());
+        velvet.slug = Some("velvet-sofa".into());
+        other.items.push(velvet);
+        let meta = CatalogMeta {
+            generated_at: "2024-05-01T12:00:00Z".into(),
+            product_count: 2,
+            variation_count: 2,
+            generator_version: "catalog-tools 0.1.0".into(),
+        };
+        other.meta = Some(meta.clone());
+        search.add_catalog("outlet", other);
+
+        let ids = |engine: &SearchEngine, query: &str| {
+            engine
+                .search_results(query)
+                .into_iter()
+                .map(|result| result.id)
+                .collect::<Vec<_>>()
+        };
+        let outlet = search.engine(Some("outlet")).unwrap();
+        let primary = search.engine(None).unwrap();
+        assert_eq!(ids(outlet, "linen sofa")[0], "sofa-1");
+        assert!(ids(primary, "sofa").is_empty());
+
+        // Lookups take the catalog name too, like `search`.
+        assert_eq!((outlet.count("sofa"), primary.count("sofa")), (2, 0));
+        assert_eq!(outlet.search_with_suggestion("sofa").results.len(), 2);
+        assert_eq!(outlet.search_grouped("sofa")["Sofas"].count, 2);
+        assert_eq!(outlet.get_by_slug("linen-sofa").unwrap().id, "sofa-1");
+        assert!(primary.get_by_slug("linen-sofa").is_none());
+        assert_eq!(outlet.get_by_id("sofa-2").unwrap().id, "sofa-2");
+        assert!(primary.get_by_id("sofa-2").is_none());
+        assert_eq!(outlet.related("sofa-1", 5)[0].id, "sofa-2");
+        assert!(primary.related("sofa-1", 5).is_empty());
+        let categories = outlet.facet_values("category").unwrap();
+        assert_eq!(
+            (categories[0].value.as_str(), categories[0].count),
+            ("Sofas", 2)
+        );
+        assert_eq!(outlet.meta(), Some(&meta));
+        assert!(primary.meta().is_none());
+        assert_eq!(search.catalog_names(), ["outlet"]);
+
+        let weights = ScoringWeights {
+            name: 4.0,
+            ..Default::default()
+        };
+        search.set_weights(weights).unwrap();
+        assert_eq!(search.engine(Some("outlet")).unwrap().weights(), weights);
+
+        assert!(search.remove_catalog("outlet"));
+        assert!(!search.remove_catalog("outlet"));
+        let Err(err) = search.engine(Some("outlet")) else {
+            panic!("removed catalog is still registered");
+        };
+        assert_eq!(err.code, ErrorCode::UnknownCatalog);
+        assert!(err.message.contains("no catalog named 'outlet'"));
+    }
+
+    #[test]
+    fn max_results_caps_or_uncaps_queries() {
+        let mut catalog = sample_catalog();
+        for idx in 2..=60 {
+            let mut item = catalog.items[0].clone();
+            item.id = idx.to_string();
+            catalog.items.push(item);
+        }
+        let search = |max_results: Option<usize>| {
+            CatalogSearch::with_options(
+                catalog.clone(),
+                SearchOptions {
+                    max_results,
+                    ..Default::default()
+                },
+            )
+        };
+
+        assert_eq!(search(None).search_results("chair").len(), 50);
+        assert_eq!(search(Some(10)).search_results("chair").len(), 10);
+        assert_eq!(search(Some(0)).search_results("chair").len(), 60);
+    }
+
+    #[test]
+    fn errors_carry_a_stable_code() {
+        let code = |bytes: &[u8]| SearchError::from(decode_catalog_any(bytes).unwrap_err()).code;
+        assert_eq!(code(&[]), ErrorCode::EmptyCatalog);
+        assert_eq!(code(b"garbage"), ErrorCode::InvalidCatalog);
+        let mut future = CATALOG_MAGIC.to_vec();
+        future.extend_from_slice(&99u16.to_le_bytes());
+        assert_eq!(code(&future), ErrorCode::UnsupportedVersion);
+
+        let mut search = CatalogSearch::from_catalog(sample_catalog());
+        let weights = ScoringWeights {
+            term: -1.0,
+            ..ScoringWeights::default()
+        };
+        let err = search.set_weights(weights).unwrap_err();
+        assert_eq!(err.code.as_str(), "invalid_weights");
+        assert_eq!(
+            serde_json::to_value(&err).unwrap()["code"],
+            "invalid_weights"
+        );
+    }
+    #[test]
+    fn search_excluding_drops_ids_before_the_result_cap() {
+        let mut catalog = sample_catalog();
+        for idx in 2..=4 {
+            let mut item = catalog.items[0].clone();
+            item.id = idx.to_string();
+            catalog.items.push(item);
+        }
+        let search = CatalogSearch::with_options(
+            catalog,
+            SearchOptions {
+                max_results: Some(2),
+                ..Default::default()
+            },
+        );
+        let ids = |results: Vec<ProductResult>| -> Vec<String> {
+            let mut ids: Vec<String> = results.into_iter().map(|result| result.id).collect();
+            ids.sort();
+            ids
+        };
+
+        let exclude = vec!["1".to_string(), "3".to_string()];
+        assert_eq!(
+            ids(search.search_excluding("chair", &exclude)),
+            vec!["2", "4"]
+        );
+        assert_eq!(ids(search.search_excluding("", &exclude)), vec!["2", "4"]);
+        assert_eq!(
+            search.search_excluding("chair", &[]).len(),
+            search.search_results("chair").len()
+        );
+        // The unfiltered results aren't affected by an earlier exclusion.
+        assert_eq!(ids(search.search_results("chair")), vec!["1", "2"]);
+    }
+    #[test]
+    fn serialized_results_are_stable() {
+        let mut catalog = sample_catalog();
+        let item = &mut catalog.items[0];
+        item.is_promotional = Some(true);
+        item.promotional_price = Some(149.25);
+        item.priority = Some(3);
+        item.category = Some("Chairs".into());
+        item.variations = [("v2", None), ("v1", Some(1))]
+            .into_iter()
+            .map(|(id, order)| Variation {
+                id: id.into(),
+                name: Some(id.to_uppercase()),
+                order,
+                ..Default::default()
+            })
+            .collect();
+        let search = CatalogSearch::from_catalog(catalog);
+
+        let json = serde_json::to_string(&search.all()).unwrap();
+        assert_eq!(
+            json,
+            concat!(
+                r#"[{"id":"1","name":"Sample Chair","slug":"sample-chair","#,
+                r#""description":"A comfy chair for reading","#,
+                r#""quick_description":"Comfy reading chair","#,
+                r#""quick_specifications":"Leather; Walnut","price":199.0,"#,
+                r#""is_promotional":true,"promotional_price":149.25,"discount_percent":25.0,"#,
+                r#""priority":3,"category":"Chairs","variations":["#,
+                r#"{"id":"v1","name":"V1","price":null,"color":null,"secondary_color":null,"#,
+                r#""quick_description":null,"quick_specifications":null,"is_promotional":null,"#,
+                r#""promotional_price":null,"discount_percent":null},"#,
+                r#"{"id":"v2","name":"V2","price":null,"color":null,"secondary_color":null,"#,
+                r#""quick_description":null,"quick_specifications":null,"is_promotional":null,"#,
+                r#""promotional_price":null,"discount_percent":null}],"#,
+                r#""matched_variation_ids":[],"score":0.0,"from_price":199.0}]"#,
+            )
+        );
+    }
+    #[test]
+    fn project_results_keeps_only_the_requested_fields() {
+        let search = CatalogSearch::from_catalog(sample_catalog());
+        let results = search.search_debug("chair");
+
+        let serialized = serde_json::to_value(&results[0]).unwrap();
+        let keys: Vec<&str> = serialized
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        let mut known = PRODUCT_RESULT_FIELDS.to_vec();
+        known.sort();
+        assert_eq!(keys, known);
+
+        let fields = ["id", "price", "image", "name"].map(String::from);
+        let (projected, unknown) = project_results(&results, &fields);
+        assert_eq!(
+            serde_json::to_string(&projected).unwrap(),
+            r#"[{"id":"1","name":"Sample Chair","price":199.0}]"#
+        );
+        assert_eq!(unknown, vec!["image"]);
+    }
 }
@@ -1,8 +1,22 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Catalog {
     pub items: Vec<Furniture>,
+    /// Build information stamped by `catalog-tools`; absent in older blobs.
+    #[serde(default)]
+    pub meta: Option<CatalogMeta>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CatalogMeta {
+    /// RFC 3339 timestamp of when the catalog was built.
+    pub generated_at: String,
+    pub product_count: u64,
+    pub variation_count: u64,
+    /// Name and version of the tool that built the catalog.
+    pub generator_version: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -54,9 +68,18 @@ pub struct Furniture {
     #[serde(default)]
     pub promotional_price: Option<f64>,
     #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
     pub variations: Vec<Variation>,
     #[serde(default)]
     pub searchable_text: String,
+    /// `updated_at` in milliseconds since the Unix epoch, parsed once by
+    /// `prepare_catalog` for [`crate::CatalogSearch::recent`]. Not serialized.
+    #[serde(skip)]
+    pub updated_at_ms: Option<i64>,
+    /// `created_at` parsed the same way, for [`crate::RecencyBoost`].
+    #[serde(skip)]
+    pub created_at_ms: Option<i64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -105,6 +128,108 @@ pub struct Variation {
 
 impl Catalog {
     pub fn empty() -> Self {
-        Self { items: Vec::new() }
+        Self {
+            items: Vec::new(),
+            meta: None,
+        }
+    }
+
+    /// Data problems that decode fine but break lookups or search, in
+    /// catalog order. An empty list means the catalog is usable as is.
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+        let mut product_ids = HashSet::new();
+        let mut variation_ids = HashSet::new();
+
+        for item in &self.items {
+            if item.id.trim().is_empty() {
+                issues.push(ValidationIssue::new(
+                    ValidationIssueKind::EmptyId,
+                    &item.id,
+                    "product has an empty id",
+                ));
+            } else if !product_ids.insert(item.id.as_str()) {
+                issues.push(ValidationIssue::new(
+                    ValidationIssueKind::DuplicateId,
+                    &item.id,
+                    format!("product id '{}' is used more than once", item.id),
+                ));
+            }
+
+            let has_name = item
+                .name
+                .as_deref()
+                .is_some_and(|name| !name.trim().is_empty());
+            if !has_name && item.searchable_text.trim().is_empty() {
+                issues.push(ValidationIssue::new(
+                    ValidationIssueKind::MissingText,
+                    &item.id,
+                    format!("product '{}' has no name and no searchable text", item.id),
+                ));
+            }
+
+            for variation in &item.variations {
+                if variation.id.trim().is_empty() {
+                    issues.push(ValidationIssue::new(
+                        ValidationIssueKind::EmptyId,
+                        &variation.id,
+                        format!("a variation of product '{}' has an empty id", item.id),
+                    ));
+                } else if !variation_ids.insert(variation.id.as_str()) {
+                    issues.push(ValidationIssue::new(
+                        ValidationIssueKind::DuplicateId,
+                        &variation.id,
+                        format!("variation id '{}' is used more than once", variation.id),
+                    ));
+                }
+                if let Some(parent) = variation.furniture_id.as_deref()
+                    && parent != item.id
+                {
+                    issues.push(ValidationIssue::new(
+                        ValidationIssueKind::OrphanVariation,
+                        &variation.id,
+                        format!(
+                            "variation '{}' is attached to product '{}' but names '{parent}' as its product",
+                            variation.id, item.id
+                        ),
+                    ));
+                }
+            }
+        }
+        issues
     }
 }
+
+/// A problem found by [`Catalog::validate`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ValidationIssue {
+    pub kind: ValidationIssueKind,
+    /// Id of the offending product or variation (possibly empty).
+    pub id: String,
+    pub message: String,
+}
+
+impl ValidationIssue {
+    fn new(kind: ValidationIssueKind, id: &str, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            id: id.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ValidationIssueKind {
+    /// A product or variation id is empty or whitespace.
+    EmptyId,
+    /// Two products, or two variations, share an id.
+    DuplicateId,
+    /// A variation's `furniture_id` names a different product than the one
+    /// it's attached to.
+    OrphanVariation,
+    /// A product has neither a name nor searchable text, so no query can
+    /// find it.
+    MissingText,
+}
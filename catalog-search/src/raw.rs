@@ -0,0 +1,156 @@
+//! Import of the raw furniture/variation JSON exports. Shared by
+//! `catalog-tools from-json` and anything else building catalogs, so every
+//! importer flattens descriptions and attaches variations the same way
+//! before [`crate::prepare_catalog`] builds the searchable text.
+
+use crate::model::{Furniture, Variation};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// One product as exported, with `description` still in its structured form.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RawFurniture {
+    pub id: String,
+    #[serde(default)]
+    pub integration_id: Option<String>,
+    #[serde(default)]
+    pub integration_type: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub slug: Option<String>,
+    #[serde(default)]
+    pub specifications: Option<String>,
+    #[serde(default)]
+    pub price: Option<f64>,
+    #[serde(default)]
+    pub weight: Option<f64>,
+    #[serde(default)]
+    pub sku: Option<String>,
+    #[serde(default)]
+    pub status: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+    #[serde(default)]
+    pub project_id: Option<String>,
+    #[serde(default)]
+    pub depth: Option<f64>,
+    #[serde(default)]
+    pub height: Option<f64>,
+    #[serde(default)]
+    pub width: Option<f64>,
+    #[serde(default)]
+    pub description: Option<Value>,
+    #[serde(default)]
+    pub quick_description: Option<String>,
+    #[serde(default)]
+    pub has_variations: Option<bool>,
+    #[serde(default)]
+    pub quick_specifications: Option<String>,
+    #[serde(default)]
+    pub priority: Option<i64>,
+    #[serde(default)]
+    pub is_promotional: Option<bool>,
+    #[serde(default)]
+    pub promotional_price: Option<f64>,
+    #[serde(default)]
+    pub category: Option<String>,
+}
+
+impl RawFurniture {
+    /// Converts to a catalog item without variations or searchable text;
+    /// [`attach_variations`] and [`crate::prepare_catalog`] fill those in.
+    pub fn into_furniture(self) -> Furniture {
+        let description_text = self.description.and_then(flatten_description);
+        Furniture {
+            id: self.id,
+            integration_id: self.integration_id,
+            integration_type: self.integration_type,
+            name: self.name,
+            slug: self.slug,
+            specifications: self.specifications,
+            price: self.price,
+            weight: self.weight,
+            sku: self.sku,
+            status: self.status,
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+            project_id: self.project_id,
+            depth: self.depth,
+            height: self.height,
+            width: self.width,
+            description_text,
+            quick_description: self.quick_description,
+            has_variations: self.has_variations,
+            quick_specifications: self.quick_specifications,
+            priority: self.priority,
+            is_promotional: self.is_promotional,
+            promotional_price: self.promotional_price,
+            category: self.category,
+            variations: Vec::new(),
+            searchable_text: String::new(),
+            updated_at_ms: None,
+            created_at_ms: None,
+        }
+    }
+}
+
+/// Joins every non-empty string in a structured description (nested arrays
+/// and objects included) with spaces.
+pub fn flatten_description(value: Value) -> Option<String> {
+    let mut parts = Vec::new();
+    collect_text(&value, &mut parts);
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.join(" "))
+    }
+}
+
+fn collect_text(value: &Value, acc: &mut Vec<String>) {
+    match value {
+        Value::String(text) => {
+            let trimmed = text.trim();
+            if !trimmed.is_empty() {
+                acc.push(trimmed.to_string());
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                collect_text(item, acc);
+            }
+        }
+        Value::Object(map) => {
+            for item in map.values() {
+                collect_text(item, acc);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Moves each variation onto the item its `furniture_id` names, setting
+/// `has_variations` on the items that got any. Variations for unknown items
+/// are dropped.
+pub fn attach_variations(items: &mut [Furniture], variations: Vec<Variation>) {
+    let mut by_furniture: HashMap<String, Vec<Variation>> = HashMap::new();
+    for variation in variations {
+        if let Some(furniture_id) = variation.furniture_id.clone() {
+            by_furniture
+                .entry(furniture_id)
+                .or_default()
+                .push(variation);
+        }
+    }
+
+    for furniture in items {
+        if let Some(attached) = by_furniture.remove(&furniture.id) {
+            furniture.has_variations = Some(!attached.is_empty());
+            furniture.variations = attached;
+        }
+    }
+}
@@ -0,0 +1,1259 @@
+//! The scoring engine behind [`crate::CatalogSearch`], free of any
+//! `JsValue` handling so the server and CLI tools can search natively.
+
+use crate::error::{ErrorCode, SearchError};
+use crate::model::{Catalog, CatalogMeta, Furniture, Variation};
+use crate::{
+    DEFAULT_MAX_RESULTS, DEFAULT_RESULT_CACHE_SIZE, EmptyQueryBehavior, FacetField, FacetValue,
+    OUT_OF_STOCK_STATUS, ProductResult, RankingAlgorithm, ResultGroup, ScoreBreakdown,
+    ScoringWeights, SearchField, SearchOptions, SearchResponse, SuggestField, Suggestion,
+    TokenScore, UNCATEGORIZED, VariationResult, build_searchable_text, default_stopwords,
+    edit_distance, normalize, now_ms, prepare_catalog, push_normalized, sort_variations,
+    term_frequencies, timestamp_ms, token_set, tokenize,
+};
+#[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+use rayon::prelude::*;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    ops::{Bound, RangeBounds},
+    sync::Mutex,
+};
+
+/// Index and scorer for one catalog. Built once per catalog; searches only
+/// need `&self`, so one engine can serve concurrent requests.
+pub struct SearchEngine {
+    catalog: Catalog,
+    /// Term frequencies per catalog item, aligned with `catalog.items`.
+    terms: Vec<HashMap<String, u32>>,
+    /// Normalized name and slug per item, for the additive scorer's bonuses.
+    name_keys: Vec<Option<String>>,
+    slug_keys: Vec<Option<String>>,
+    /// Normalized category per item, for the related-products boost.
+    category_keys: Vec<Option<String>>,
+    bm25: Bm25Stats,
+    ranking: RankingAlgorithm,
+    weights: ScoringWeights,
+    stopwords: HashSet<String>,
+    /// Normalized query word -> words it also matches, from
+    /// `SearchOptions::synonyms`.
+    synonyms: HashMap<String, Vec<String>>,
+    /// Distinct indexed tokens, sorted, used for "did you mean" suggestions.
+    vocabulary: Vec<String>,
+    /// Slug -> item index. On duplicate slugs the highest-priority item wins.
+    slugs: HashMap<String, usize>,
+    ids: HashMap<String, usize>,
+    /// Set when `in_stock_only` is on; hides unavailable products.
+    stock: Option<StockFilter>,
+    empty_query: EmptyQueryBehavior,
+    result_cache: ResultCache,
+    min_score: Option<f32>,
+    max_results: usize,
+    /// Options this engine was built with, reused for catalogs added later.
+    options: SearchOptions,
+    /// Milliseconds since the Unix epoch, for recency bonuses.
+    clock: fn() -> i64,
+}
+
+/// Added to the term similarity of products in the same category as the one
+/// passed to [`SearchEngine::related`].
+const RELATED_CATEGORY_BOOST: f32 = 0.25;
+
+/// Score of a [`SearchEngine::suggest`] match in the slug, relative to one in
+/// the name.
+const SUGGEST_SLUG_WEIGHT: f32 = 0.8;
+/// Fraction of that score a match inside a word keeps.
+const SUGGEST_MID_WORD: f32 = 0.4;
+
+/// How long cached results are reused when a recency boost is set. Bonuses
+/// decay continuously, so entries are keyed by the minute they were scored in.
+const RECENCY_CACHE_BUCKET_MS: i64 = 60_000;
+
+/// Least-recently-used results of non-debug searches, keyed by the
+/// normalized query. A mutex rather than a `RefCell` keeps the engine `Sync`
+/// for the server, which shares it between threads.
+struct ResultCache {
+    capacity: usize,
+    /// Most recently used last.
+    entries: Mutex<VecDeque<(String, Vec<ProductResult>)>>,
+}
+
+impl ResultCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<Vec<ProductResult>> {
+        let mut entries = self.entries.lock().unwrap_or_else(|err| err.into_inner());
+        let pos = entries.iter().position(|(cached, _)| cached == key)?;
+        let entry = entries.remove(pos)?;
+        let results = entry.1.clone();
+        entries.push_back(entry);
+        Some(results)
+    }
+
+    fn insert(&self, key: String, results: &[ProductResult]) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap_or_else(|err| err.into_inner());
+        if entries.len() >= self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back((key, results.to_vec()));
+    }
+
+    fn clear(&self) {
+        self.entries
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clear();
+    }
+}
+
+#[derive(Debug, Clone)]
+struct StockFilter {
+    available: Option<HashSet<String>>,
+    missing_available: bool,
+}
+
+impl StockFilter {
+    fn from_options(options: &SearchOptions) -> Option<Self> {
+        options.in_stock_only.then(|| Self {
+            available: options.available_statuses.as_ref().map(|statuses| {
+                statuses
+                    .iter()
+                    .map(|status| status.trim().to_lowercase())
+                    .collect()
+            }),
+            missing_available: !options.hide_missing_status,
+        })
+    }
+
+    fn allows(&self, furniture: &Furniture) -> bool {
+        let Some(status) = furniture.status.as_deref() else {
+            return self.missing_available;
+        };
+        let status = status.trim().to_lowercase();
+        match &self.available {
+            Some(available) => available.contains(&status),
+            None => status != OUT_OF_STOCK_STATUS,
+        }
+    }
+}
+
+impl SearchEngine {
+    /// Builds a search index from an already decoded catalog.
+    pub fn from_catalog(catalog: Catalog) -> Self {
+        Self::with_options(catalog, SearchOptions::default())
+    }
+
+    /// Like [`Self::from_catalog`], but rebuilds every item's searchable text
+    /// from `fields` only, discarding any precomputed text.
+    pub fn with_fields(catalog: Catalog, fields: &[SearchField]) -> Self {
+        let options = SearchOptions {
+            fields: Some(fields.to_vec()),
+            ..Default::default()
+        };
+        Self::with_options(catalog, options)
+    }
+
+    pub fn with_options(mut catalog: Catalog, options: SearchOptions) -> Self {
+        let stopwords = match &options.stopwords {
+            Some(words) => words.iter().map(|word| normalize(word.trim())).collect(),
+            None => default_stopwords(),
+        };
+
+        // Precomputed text was built from every field with the default
+        // stopwords, so rebuild it whenever either differs.
+        if options.fields.is_some() || options.stopwords.is_some() {
+            let fields = options.fields.as_deref().unwrap_or(&SearchField::ALL);
+            for furniture in &mut catalog.items {
+                furniture.searchable_text = build_searchable_text(furniture, fields, &stopwords);
+                furniture.updated_at_ms = furniture.updated_at.as_deref().and_then(timestamp_ms);
+                furniture.created_at_ms = furniture.created_at.as_deref().and_then(timestamp_ms);
+            }
+        } else {
+            prepare_catalog(&mut catalog);
+        }
+
+        let mut search = Self::index(catalog);
+        search.ranking = options.ranking;
+        search.weights = options.weights;
+        search.stopwords = stopwords;
+        search.synonyms = expand_synonyms(&options.synonyms, options.bidirectional_synonyms);
+        search.stock = StockFilter::from_options(&options);
+        search.empty_query = options.empty_query;
+        search.min_score = options.min_score;
+        search.max_results = match options.max_results {
+            Some(0) => usize::MAX,
+            Some(limit) => limit,
+            None => DEFAULT_MAX_RESULTS,
+        };
+        search.result_cache = ResultCache::new(
+            options
+                .result_cache_size
+                .unwrap_or(DEFAULT_RESULT_CACHE_SIZE),
+        );
+        search.options = options;
+        search
+    }
+
+    fn index(mut catalog: Catalog) -> Self {
+        for furniture in &mut catalog.items {
+            sort_variations(furniture);
+        }
+
+        let terms: Vec<HashMap<String, u32>> = catalog
+            .items
+            .iter()
+            .map(|furniture| term_frequencies(&furniture.searchable_text))
+            .collect();
+        let bm25 = Bm25Stats::new(&terms);
+        let name_keys = catalog
+            .items
+            .iter()
+            .map(|furniture| furniture.name.as_deref().map(normalize))
+            .collect();
+        let slug_keys = catalog
+            .items
+            .iter()
+            .map(|furniture| furniture.slug.as_deref().map(normalize))
+            .collect();
+        let category_keys = catalog
+            .items
+            .iter()
+            .map(|furniture| {
+                furniture
+                    .category
+                    .as_deref()
+                    .map(|category| normalize(category.trim()))
+                    .filter(|category| !category.is_empty())
+            })
+            .collect();
+        let mut vocabulary: Vec<String> = bm25.doc_freq.keys().cloned().collect();
+        vocabulary.sort();
+
+        let mut slugs: HashMap<String, usize> = HashMap::new();
+        let mut ids = HashMap::new();
+        for (idx, furniture) in catalog.items.iter().enumerate() {
+            ids.entry(furniture.id.clone()).or_insert(idx);
+
+            let Some(slug) = furniture.slug.as_ref() else {
+                continue;
+            };
+            slugs
+                .entry(slug.clone())
+                .and_modify(|existing| {
+                    let current = catalog.items[*existing].priority;
+                    if compare_priority(furniture.priority, current).is_lt() {
+                        *existing = idx;
+                    }
+                })
+                .or_insert(idx);
+        }
+
+        Self {
+            catalog,
+            terms,
+            name_keys,
+            slug_keys,
+            category_keys,
+            bm25,
+            ranking: RankingAlgorithm::default(),
+            weights: ScoringWeights::default(),
+            stopwords: default_stopwords(),
+            synonyms: HashMap::new(),
+            vocabulary,
+            slugs,
+            ids,
+            stock: None,
+            empty_query: EmptyQueryBehavior::default(),
+            result_cache: ResultCache::new(DEFAULT_RESULT_CACHE_SIZE),
+            min_score: None,
+            max_results: DEFAULT_MAX_RESULTS,
+            options: SearchOptions::default(),
+            clock: now_ms,
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn set_clock(&mut self, clock: fn() -> i64) {
+        self.clock = clock;
+    }
+
+    pub fn weights(&self) -> ScoringWeights {
+        self.weights
+    }
+
+    /// Rejects negative or non-finite weights, leaving the current ones.
+    pub fn set_weights(&mut self, weights: ScoringWeights) -> Result<(), SearchError> {
+        weights
+            .validate()
+            .map_err(|err| SearchError::new(ErrorCode::InvalidWeights, err))?;
+        self.weights = weights;
+        self.options.weights = weights;
+        self.result_cache.clear();
+        Ok(())
+    }
+
+    pub fn catalog(&self) -> &Catalog {
+        &self.catalog
+    }
+
+    /// Options this engine was built with (and its current weights).
+    pub fn options(&self) -> &SearchOptions {
+        &self.options
+    }
+
+    pub fn meta(&self) -> Option<&CatalogMeta> {
+        self.catalog.meta.as_ref()
+    }
+
+    pub fn all(&self) -> Vec<ProductResult> {
+        self.catalog
+            .items
+            .iter()
+            .map(|furniture| build_result(furniture, 0.0, Vec::new()))
+            .collect()
+    }
+
+    pub fn search_results(&self, query: &str) -> Vec<ProductResult> {
+        self.run_search(query, false)
+    }
+
+    /// Same as [`Self::search_results`] with a score breakdown on each result.
+    pub fn search_debug(&self, query: &str) -> Vec<ProductResult> {
+        self.run_search(query, true)
+    }
+
+    /// Same as [`Self::search_results`] without the products in `exclude`,
+    /// which are dropped before ranking so the page stays full.
+    pub fn search_excluding(&self, query: &str, exclude: &[String]) -> Vec<ProductResult> {
+        if exclude.is_empty() {
+            return self.run_search(query, false);
+        }
+        // Exclusions vary per call, so these searches bypass the result cache.
+        let exclude: HashSet<&str> = exclude.iter().map(String::as_str).collect();
+        self.score_query(query, false, &exclude)
+    }
+
+    pub fn search_grouped(&self, query: &str) -> BTreeMap<String, ResultGroup> {
+        let mut groups: BTreeMap<String, ResultGroup> = BTreeMap::new();
+        for result in self.search_results(query) {
+            let category = result
+                .category
+                .clone()
+                .filter(|category| !category.trim().is_empty())
+                .unwrap_or_else(|| UNCATEGORIZED.to_string());
+            let group = groups.entry(category).or_insert_with(|| ResultGroup {
+                count: 0,
+                results: Vec::new(),
+            });
+            group.count += 1;
+            group.results.push(result);
+        }
+        groups
+    }
+
+    pub fn search_with_suggestion(&self, query: &str) -> SearchResponse {
+        let results = self.search_results(query);
+        let suggestion = if results.is_empty() {
+            self.correct(query)
+        } else {
+            None
+        };
+        SearchResponse {
+            results,
+            suggestion,
+        }
+    }
+
+    /// Rewrites the query with every unknown word replaced by the closest
+    /// indexed token, or `None` if no word could be corrected.
+    fn correct(&self, query: &str) -> Option<String> {
+        let normalized = normalize(query.trim());
+        let mut corrected = false;
+        let words: Vec<String> = normalized
+            .split_whitespace()
+            .map(|word| {
+                let known = RangeFilter::parse(word).is_some()
+                    || self.stopwords.contains(word)
+                    || self.bm25.doc_freq.contains_key(word);
+                if known {
+                    return word.to_string();
+                }
+                match self.closest_token(word) {
+                    Some(token) => {
+                        corrected = true;
+                        token.to_string()
+                    }
+                    None => word.to_string(),
+                }
+            })
+            .collect();
+
+        corrected.then(|| words.join(" "))
+    }
+
+    fn closest_token(&self, word: &str) -> Option<&str> {
+        let len = word.chars().count();
+        let max_distance = if len <= 4 { 1 } else { 2 };
+
+        self.vocabulary
+            .iter()
+            .filter(|token| token.chars().count().abs_diff(len) <= max_distance)
+            .map(|token| (edit_distance(word, token), token))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .min_by(|(da, a), (db, b)| {
+                da.cmp(db).then_with(|| {
+                    let fa = self.bm25.doc_freq.get(*a).copied().unwrap_or(0);
+                    let fb = self.bm25.doc_freq.get(*b).copied().unwrap_or(0);
+                    fb.cmp(&fa)
+                })
+            })
+            .map(|(_, token)| token.as_str())
+    }
+
+    /// Products whose name or slug contains `prefix`, for autocomplete.
+    /// Matches at the start of a word rank above ones inside a word (the
+    /// `wood` in `redwood`), and name matches above slug matches; priority
+    /// then name break ties within each kind of match.
+    pub fn suggest(&self, prefix: &str, limit: usize) -> Vec<Suggestion> {
+        let prefix = normalize(prefix.trim());
+        if prefix.is_empty() {
+            return Vec::new();
+        }
+
+        let items = &self.catalog.items;
+        let mut matches: Vec<(usize, SuggestField, bool, f32)> = (0..items.len())
+            .filter(|&idx| self.in_stock(&items[idx]))
+            .filter_map(|idx| {
+                [
+                    (SuggestField::Name, &self.name_keys[idx]),
+                    (SuggestField::Slug, &self.slug_keys[idx]),
+                ]
+                .into_iter()
+                .filter_map(|(field, text)| {
+                    let word_start = prefix_match(text.as_deref()?, &prefix)?;
+                    let field_weight = match field {
+                        SuggestField::Name => 1.0,
+                        SuggestField::Slug => SUGGEST_SLUG_WEIGHT,
+                    };
+                    let score = if word_start {
+                        field_weight
+                    } else {
+                        field_weight * SUGGEST_MID_WORD
+                    };
+                    Some((idx, field, word_start, score))
+                })
+                .max_by(|a, b| a.3.total_cmp(&b.3))
+            })
+            .collect();
+
+        matches.sort_by(|(a_idx, .., a), (b_idx, .., b)| {
+            let (a_item, b_item) = (&items[*a_idx], &items[*b_idx]);
+            b.total_cmp(a)
+                .then_with(|| compare_priority(a_item.priority, b_item.priority))
+                .then_with(|| compare_name(&a_item.name, &b_item.name))
+        });
+        matches.truncate(limit);
+        matches
+            .into_iter()
+            .map(|(idx, field, word_start, score)| Suggestion {
+                id: items[idx].id.clone(),
+                name: items[idx].name.clone(),
+                slug: items[idx].slug.clone(),
+                field,
+                word_start,
+                score,
+            })
+            .collect()
+    }
+
+    /// Number of products matching `query`, without building results. Unlike
+    /// `search`, the count isn't capped at one page. An empty query counts
+    /// every listable product.
+    pub fn count(&self, query: &str) -> usize {
+        let parsed = self.parse_query(query);
+        if parsed.is_empty() {
+            return self
+                .catalog
+                .items
+                .iter()
+                .filter(|item| self.in_stock(item))
+                .count();
+        }
+
+        let is_match = |idx: usize| self.score_item(idx, &parsed, false).is_some();
+
+        #[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+        let count = (0..self.catalog.items.len())
+            .into_par_iter()
+            .filter(|&idx| is_match(idx))
+            .count();
+        #[cfg(not(all(feature = "parallel", not(target_arch = "wasm32"))))]
+        let count = (0..self.catalog.items.len())
+            .filter(|&idx| is_match(idx))
+            .count();
+        count
+    }
+
+    fn parse_query(&self, query: &str) -> ParsedQuery {
+        let trimmed = normalize(query.trim());
+        let (filters, text) = parse_query(&trimmed);
+        let tokens: Vec<String> = tokenize(&text)
+            .filter(|token| !self.stopwords.contains(token))
+            .collect();
+        let synonyms = tokens
+            .iter()
+            .map(|token| self.synonyms.get(token).cloned().unwrap_or_default())
+            .collect();
+        ParsedQuery {
+            filters,
+            tokens,
+            synonyms,
+            now_ms: (self.clock)(),
+        }
+    }
+
+    fn score_item(&self, idx: usize, query: &ParsedQuery, debug: bool) -> Option<Scored> {
+        let furniture = &self.catalog.items[idx];
+        if furniture.searchable_text.is_empty()
+            || !self.in_stock(furniture)
+            || !query.filters.iter().all(|filter| filter.matches(furniture))
+        {
+            return None;
+        }
+        self.compute_score(idx, query, debug).filter(|scored| {
+            query.tokens.is_empty() || self.min_score.is_none_or(|min| scored.score >= min)
+        })
+    }
+
+    pub(crate) fn run_search(&self, query: &str, debug: bool) -> Vec<ProductResult> {
+        if debug {
+            return self.score_query(query, true, &HashSet::new());
+        }
+        // Queries are normalized and split on whitespace before parsing, so
+        // this key can't conflate queries with different results.
+        let mut key = normalize(query.trim())
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        if self.options.recency_boost.is_some() {
+            let bucket = (self.clock)().div_euclid(RECENCY_CACHE_BUCKET_MS);
+            key = format!("{key}\0{bucket}");
+        }
+        if let Some(results) = self.result_cache.get(&key) {
+            return results;
+        }
+        let results = self.score_query(query, false, &HashSet::new());
+        self.result_cache.insert(key, &results);
+        results
+    }
+
+    fn score_query(&self, query: &str, debug: bool, exclude: &HashSet<&str>) -> Vec<ProductResult> {
+        let parsed = self.parse_query(query);
+        if parsed.is_empty() {
+            return self.top_by_priority_excluding(self.empty_query.limit(), exclude);
+        }
+        // A variation matches on any query word or synonym.
+        let tokens: Vec<String> = parsed
+            .tokens
+            .iter()
+            .chain(parsed.synonyms.iter().flatten())
+            .cloned()
+            .collect();
+
+        let score_item = |idx: usize| {
+            if exclude.contains(self.catalog.items[idx].id.as_str()) {
+                return None;
+            }
+            self.score_item(idx, &parsed, debug)
+                .map(|scored| (idx, scored))
+        };
+
+        // Both paths collect in catalog order, so the stable sort below gives
+        // identical results either way.
+        #[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+        let mut matches: Vec<(usize, Scored)> = (0..self.catalog.items.len())
+            .into_par_iter()
+            .filter_map(score_item)
+            .collect();
+        #[cfg(not(all(feature = "parallel", not(target_arch = "wasm32"))))]
+        let mut matches: Vec<(usize, Scored)> = (0..self.catalog.items.len())
+            .filter_map(score_item)
+            .collect();
+
+        let items = &self.catalog.items;
+        matches.sort_by(|(a_idx, a), (b_idx, b)| {
+            let (a_item, b_item) = (&items[*a_idx], &items[*b_idx]);
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| compare_priority(a_item.priority, b_item.priority))
+                .then_with(|| compare_name(&a_item.name, &b_item.name))
+        });
+        matches.truncate(self.max_results);
+
+        // Only the returned page pays for cloning and variation matching.
+        matches
+            .into_iter()
+            .map(|(idx, scored)| {
+                let furniture = &items[idx];
+                let matched_variation_ids = furniture
+                    .variations
+                    .iter()
+                    .filter(|variation| variation_matches(variation, &tokens))
+                    .map(|variation| variation.id.clone())
+                    .collect();
+                let mut result = build_result(furniture, scored.score, matched_variation_ids);
+                result.score_breakdown = scored.breakdown;
+                result
+            })
+            .collect()
+    }
+
+    /// Distinct values of `field` across the whole catalog, with the number
+    /// of products having each (once per product, however many of its
+    /// variations share the value). Values are trimmed and sorted like names.
+    pub fn facet_values(&self, field: &str) -> Result<Vec<FacetValue>, SearchError> {
+        let field = FacetField::from_name(field).ok_or_else(|| {
+            SearchError::new(
+                ErrorCode::UnknownFacet,
+                format!(
+                    "unknown facet field '{field}'; expected one of: {}",
+                    FacetField::NAMES.join(", ")
+                ),
+            )
+        })?;
+
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for furniture in &self.catalog.items {
+            let values: HashSet<&str> = field
+                .values(furniture)
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .collect();
+            for value in values {
+                *counts.entry(value).or_default() += 1;
+            }
+        }
+
+        let mut values: Vec<FacetValue> = counts
+            .into_iter()
+            .map(|(value, count)| FacetValue {
+                value: value.to_string(),
+                count,
+            })
+            .collect();
+        values.sort_by(|a, b| collate(&a.value, &b.value));
+        Ok(values)
+    }
+
+    /// Products sharing the most indexed terms with `product_id` (Jaccard
+    /// similarity), boosted when they share its category.
+    pub fn related(&self, product_id: &str, limit: usize) -> Vec<ProductResult> {
+        let Some(&target) = self.ids.get(product_id) else {
+            return Vec::new();
+        };
+        let target_terms = &self.terms[target];
+        let target_category = self.category_keys[target].as_ref();
+
+        let items = &self.catalog.items;
+        let mut scored: Vec<(usize, f32)> = (0..items.len())
+            .filter(|&idx| idx != target && self.in_stock(&items[idx]))
+            .filter_map(|idx| {
+                let terms = &self.terms[idx];
+                let (small, large) = if terms.len() < target_terms.len() {
+                    (terms, target_terms)
+                } else {
+                    (target_terms, terms)
+                };
+                let shared = small
+                    .keys()
+                    .filter(|term| large.contains_key(*term))
+                    .count();
+                let union = terms.len() + target_terms.len() - shared;
+                let mut score = if union == 0 {
+                    0.0
+                } else {
+                    shared as f32 / union as f32
+                };
+                if target_category.is_some() && self.category_keys[idx].as_ref() == target_category
+                {
+                    score += RELATED_CATEGORY_BOOST;
+                }
+                (score > 0.0).then_some((idx, score))
+            })
+            .collect();
+
+        scored.sort_by(|(a_idx, a), (b_idx, b)| {
+            let (a_item, b_item) = (&items[*a_idx], &items[*b_idx]);
+            b.partial_cmp(a)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| compare_priority(a_item.priority, b_item.priority))
+                .then_with(|| compare_name(&a_item.name, &b_item.name))
+        });
+        scored.truncate(limit);
+        scored
+            .into_iter()
+            .map(|(idx, score)| build_result(&items[idx], score, Vec::new()))
+            .collect()
+    }
+
+    pub fn get_by_slug(&self, slug: &str) -> Option<ProductResult> {
+        self.slugs.get(slug).map(|&idx| self.result_at(idx))
+    }
+
+    pub fn get_by_id(&self, id: &str) -> Option<ProductResult> {
+        self.ids.get(id).map(|&idx| self.result_at(idx))
+    }
+
+    fn result_at(&self, idx: usize) -> ProductResult {
+        build_result(&self.catalog.items[idx], 0.0, Vec::new())
+    }
+
+    /// The `limit` most recently updated products, newest first. Products
+    /// without a parseable `updated_at` come last, in catalog order.
+    pub fn recent(&self, limit: usize) -> Vec<ProductResult> {
+        let mut items: Vec<&Furniture> = self
+            .catalog
+            .items
+            .iter()
+            .filter(|item| self.in_stock(item))
+            .collect();
+        // `None` sorts below every timestamp, so reversed it comes last.
+        items.sort_by_key(|item| std::cmp::Reverse(item.updated_at_ms));
+        items
+            .into_iter()
+            .take(limit)
+            .map(|item| build_result(item, 0.0, Vec::new()))
+            .collect()
+    }
+
+    pub fn top_by_priority(&self, limit: usize) -> Vec<ProductResult> {
+        self.top_by_priority_excluding(limit, &HashSet::new())
+    }
+
+    fn top_by_priority_excluding(
+        &self,
+        limit: usize,
+        exclude: &HashSet<&str>,
+    ) -> Vec<ProductResult> {
+        let mut items: Vec<ProductResult> = self
+            .catalog
+            .items
+            .iter()
+            .filter(|item| self.in_stock(item) && !exclude.contains(item.id.as_str()))
+            .map(|item| build_result(item, priority_score(item.priority), Vec::new()))
+            .collect();
+
+        items.sort_by(|a, b| {
+            compare_priority(a.priority, b.priority).then_with(|| compare_name(&a.name, &b.name))
+        });
+
+        items.truncate(limit);
+        items
+    }
+
+    /// Keys of the cached searches, least recently used first.
+    #[cfg(test)]
+    pub(crate) fn cached_queries(&self) -> Vec<String> {
+        self.result_cache
+            .entries
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+
+    fn in_stock(&self, furniture: &Furniture) -> bool {
+        self.stock
+            .as_ref()
+            .is_none_or(|filter| filter.allows(furniture))
+    }
+
+    fn compute_score(&self, idx: usize, query: &ParsedQuery, debug: bool) -> Option<Scored> {
+        let furniture = &self.catalog.items[idx];
+        let tokens = &query.tokens;
+        let priority = match self.ranking {
+            RankingAlgorithm::Additive => {
+                priority_score(furniture.priority) * self.weights.priority
+            }
+            RankingAlgorithm::Bm25 => 0.0,
+        };
+        let weights = &self.weights;
+        let mut breakdown = debug.then(|| ScoreBreakdown {
+            priority,
+            ..Default::default()
+        });
+
+        if tokens.is_empty() {
+            return Some(Scored {
+                score: priority,
+                breakdown,
+            });
+        }
+
+        let base = furniture.searchable_text.as_str();
+        if base.is_empty() {
+            return None;
+        }
+
+        let mut score = 0.0;
+
+        // Every query word must match, itself or through one of its
+        // synonyms; the best-scoring synonym earns the reduced credit.
+        for (token, synonyms) in tokens.iter().zip(&query.synonyms) {
+            let (token, (text_score, name_score, slug_score)) = match self.term_scores(idx, token) {
+                Some(scores) => (token, scores),
+                None => synonyms
+                    .iter()
+                    .filter_map(|synonym| {
+                        let (text, name, slug) = self.term_scores(idx, synonym)?;
+                        let scale = weights.synonym;
+                        Some((synonym, (text * scale, name * scale, slug * scale)))
+                    })
+                    .max_by(|(_, a), (_, b)| (a.0 + a.1 + a.2).total_cmp(&(b.0 + b.1 + b.2)))?,
+            };
+
+            score += text_score + name_score + slug_score;
+            if let Some(breakdown) = breakdown.as_mut() {
+                breakdown.tokens.push(TokenScore {
+                    token: token.clone(),
+                    text: text_score,
+                    name: name_score,
+                    slug: slug_score,
+                });
+            }
+        }
+
+        let promo = if furniture.is_promotional == Some(true) {
+            weights.promo
+        } else {
+            0.0
+        };
+        let recency = self.options.recency_boost.map_or(0.0, |boost| {
+            boost.bonus(furniture.created_at_ms, query.now_ms)
+        });
+        if let Some(breakdown) = breakdown.as_mut() {
+            breakdown.promo = promo;
+            breakdown.recency = recency;
+        }
+        score += priority + promo + recency;
+
+        Some(Scored { score, breakdown })
+    }
+
+    /// Text, name and slug credit for one word in item `idx`, or `None` if
+    /// the item doesn't contain it.
+    fn term_scores(&self, idx: usize, token: &str) -> Option<(f32, f32, f32)> {
+        let base = self.catalog.items[idx].searchable_text.as_str();
+        let frequency = self.terms[idx].get(token).copied().unwrap_or(0);
+        let substring = frequency == 0 && base.contains(token);
+        if frequency == 0 && !substring {
+            return None;
+        }
+
+        let weights = &self.weights;
+        let scores = match self.ranking {
+            RankingAlgorithm::Additive => {
+                let text_score = if substring {
+                    weights.term * weights.substring
+                } else {
+                    weights.term
+                };
+                let name_score = match &self.name_keys[idx] {
+                    Some(name) if name.contains(token) => weights.name,
+                    _ => 0.0,
+                };
+                let slug_score = match &self.slug_keys[idx] {
+                    Some(slug) if slug.contains(token) => weights.slug,
+                    _ => 0.0,
+                };
+                (text_score, name_score, slug_score)
+            }
+            RankingAlgorithm::Bm25 => {
+                // Substring-only hits count as a single, discounted occurrence.
+                let bm25 = if substring {
+                    weights.substring * self.bm25.term_score(token, 1, idx)
+                } else {
+                    self.bm25.term_score(token, frequency, idx)
+                };
+                (weights.term * bm25, 0.0, 0.0)
+            }
+        };
+        Some(scores)
+    }
+}
+
+/// Normalizes the configured synonym map. With `bidirectional`, every word
+/// of an entry also expands to the key and the entry's other words.
+fn expand_synonyms(
+    synonyms: &HashMap<String, Vec<String>>,
+    bidirectional: bool,
+) -> HashMap<String, Vec<String>> {
+    let mut expanded: HashMap<String, Vec<String>> = HashMap::new();
+    for (word, alternatives) in synonyms {
+        let word = normalize(word.trim());
+        let alternatives: Vec<String> = alternatives
+            .iter()
+            .map(|alternative| normalize(alternative.trim()))
+            .filter(|alternative| !alternative.is_empty())
+            .collect();
+        if word.is_empty() || alternatives.is_empty() {
+            continue;
+        }
+        if bidirectional {
+            for alternative in &alternatives {
+                let entry = expanded.entry(alternative.clone()).or_default();
+                entry.push(word.clone());
+                entry.extend(alternatives.iter().cloned());
+            }
+        }
+        expanded.entry(word).or_default().extend(alternatives);
+    }
+    for (word, alternatives) in &mut expanded {
+        alternatives.sort();
+        alternatives.dedup();
+        alternatives.retain(|alternative| alternative != word);
+    }
+    expanded.retain(|_, alternatives| !alternatives.is_empty());
+    expanded
+}
+
+const BM25_K1: f32 = 1.2;
+const BM25_B: f32 = 0.75;
+
+/// Corpus statistics for BM25, computed once when the index is built.
+#[derive(Debug, Default)]
+struct Bm25Stats {
+    /// Number of items containing each token.
+    doc_freq: HashMap<String, usize>,
+    doc_lengths: Vec<usize>,
+    avg_doc_length: f32,
+}
+
+impl Bm25Stats {
+    fn new(terms: &[HashMap<String, u32>]) -> Self {
+        let mut doc_freq: HashMap<String, usize> = HashMap::new();
+        let mut doc_lengths = Vec::with_capacity(terms.len());
+        for doc in terms {
+            for token in doc.keys() {
+                *doc_freq.entry(token.clone()).or_default() += 1;
+            }
+            doc_lengths.push(doc.values().map(|&count| count as usize).sum());
+        }
+
+        let total: usize = doc_lengths.iter().sum();
+        let avg_doc_length = if doc_lengths.is_empty() {
+            0.0
+        } else {
+            total as f32 / doc_lengths.len() as f32
+        };
+
+        Self {
+            doc_freq,
+            doc_lengths,
+            avg_doc_length,
+        }
+    }
+
+    fn idf(&self, token: &str) -> f32 {
+        let docs = self.doc_lengths.len() as f32;
+        let freq = self.doc_freq.get(token).copied().unwrap_or(0) as f32;
+        (1.0 + (docs - freq + 0.5) / (freq + 0.5)).ln()
+    }
+
+    fn term_score(&self, token: &str, frequency: u32, idx: usize) -> f32 {
+        let frequency = frequency as f32;
+        let length_ratio = if self.avg_doc_length > 0.0 {
+            self.doc_lengths[idx] as f32 / self.avg_doc_length
+        } else {
+            1.0
+        };
+        let norm = BM25_K1 * (1.0 - BM25_B + BM25_B * length_ratio);
+        self.idf(token) * frequency * (BM25_K1 + 1.0) / (frequency + norm)
+    }
+}
+
+struct Scored {
+    score: f32,
+    breakdown: Option<ScoreBreakdown>,
+}
+
+/// A query split into range filters and the remaining text tokens.
+struct ParsedQuery {
+    filters: Vec<RangeFilter>,
+    tokens: Vec<String>,
+    /// Synonyms of each token, aligned with `tokens`.
+    synonyms: Vec<Vec<String>>,
+    /// When the query ran, for the recency boost.
+    now_ms: i64,
+}
+
+impl ParsedQuery {
+    fn is_empty(&self) -> bool {
+        self.filters.is_empty() && self.tokens.is_empty()
+    }
+}
+
+/// Numeric field filter parsed from a `field:<op>` query term.
+#[derive(Debug)]
+struct RangeFilter {
+    field: RangeField,
+    bounds: (Bound<f64>, Bound<f64>),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum RangeField {
+    Price,
+    PromotionalPrice,
+    Weight,
+    Width,
+    Height,
+    Depth,
+}
+
+impl RangeField {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "price" => Some(Self::Price),
+            "promotional_price" | "promo" => Some(Self::PromotionalPrice),
+            "weight" => Some(Self::Weight),
+            "width" => Some(Self::Width),
+            "height" => Some(Self::Height),
+            "depth" => Some(Self::Depth),
+            _ => None,
+        }
+    }
+
+    fn value(self, furniture: &Furniture) -> Option<f64> {
+        match self {
+            Self::Price => furniture.price,
+            Self::PromotionalPrice => furniture.promotional_price,
+            Self::Weight => furniture.weight,
+            Self::Width => furniture.width,
+            Self::Height => furniture.height,
+            Self::Depth => furniture.depth,
+        }
+    }
+}
+
+impl RangeFilter {
+    /// Parses `price:<200`, `weight:>=10`, `width:50..80`, `depth:..40` or
+    /// `height:90`. Returns `None` for unknown fields or malformed values so
+    /// the term is searched as plain text instead.
+    fn parse(term: &str) -> Option<Self> {
+        let (name, spec) = term.split_once(':')?;
+        let field = RangeField::from_name(name)?;
+        let number = |value: &str| value.trim().parse::<f64>().ok();
+
+        let bounds = if let Some(value) = spec.strip_prefix("<=") {
+            (Bound::Unbounded, Bound::Included(number(value)?))
+        } else if let Some(value) = spec.strip_prefix(">=") {
+            (Bound::Included(number(value)?), Bound::Unbounded)
+        } else if let Some(value) = spec.strip_prefix('<') {
+            (Bound::Unbounded, Bound::Excluded(number(value)?))
+        } else if let Some(value) = spec.strip_prefix('>') {
+            (Bound::Excluded(number(value)?), Bound::Unbounded)
+        } else if let Some((min, max)) = spec.split_once("..") {
+            if min.is_empty() && max.is_empty() {
+                return None;
+            }
+            let lower = if min.is_empty() {
+                Bound::Unbounded
+            } else {
+                Bound::Included(number(min)?)
+            };
+            let upper = if max.is_empty() {
+                Bound::Unbounded
+            } else {
+                Bound::Included(number(max)?)
+            };
+            (lower, upper)
+        } else {
+            let value = number(spec)?;
+            (Bound::Included(value), Bound::Included(value))
+        };
+
+        Some(Self { field, bounds })
+    }
+
+    fn matches(&self, furniture: &Furniture) -> bool {
+        self.field
+            .value(furniture)
+            .is_some_and(|value| self.bounds.contains(&value))
+    }
+}
+
+/// Splits range terms out of a normalized query, returning the filters and the
+/// remaining free text.
+fn parse_query(query: &str) -> (Vec<RangeFilter>, String) {
+    let mut filters = Vec::new();
+    let mut words = Vec::new();
+    for word in query.split_whitespace() {
+        match RangeFilter::parse(word) {
+            Some(filter) => filters.push(filter),
+            None => words.push(word),
+        }
+    }
+    (filters, words.join(" "))
+}
+
+/// Whether `prefix` occurs in `text` at the start of a word (`Some(true)`),
+/// only inside words (`Some(false)`), or not at all.
+fn prefix_match(text: &str, prefix: &str) -> Option<bool> {
+    let mut found = false;
+    for (idx, _) in text.match_indices(prefix) {
+        let word_start = text[..idx]
+            .chars()
+            .next_back()
+            .is_none_or(|c| !c.is_alphanumeric());
+        if word_start {
+            return Some(true);
+        }
+        found = true;
+    }
+    found.then_some(false)
+}
+
+fn variation_matches(variation: &Variation, tokens: &[String]) -> bool {
+    let mut parts = Vec::new();
+    push_normalized(&mut parts, variation.name.as_deref());
+    push_normalized(&mut parts, variation.color.as_deref());
+    push_normalized(&mut parts, variation.secondary_color.as_deref());
+    push_normalized(&mut parts, variation.quick_description.as_deref());
+    push_normalized(&mut parts, variation.quick_specifications.as_deref());
+    if parts.is_empty() {
+        return false;
+    }
+
+    let text = parts.join(" ");
+    let variation_tokens = token_set(&text);
+    tokens
+        .iter()
+        .any(|token| variation_tokens.contains(token) || text.contains(token.as_str()))
+}
+
+fn build_result(
+    furniture: &Furniture,
+    score: f32,
+    matched_variation_ids: Vec<String>,
+) -> ProductResult {
+    ProductResult {
+        id: furniture.id.clone(),
+        name: furniture.name.clone(),
+        slug: furniture.slug.clone(),
+        description: furniture.description_text.clone(),
+        quick_description: furniture.quick_description.clone(),
+        quick_specifications: furniture.quick_specifications.clone(),
+        price: furniture.price,
+        is_promotional: furniture.is_promotional,
+        promotional_price: furniture.promotional_price,
+        discount_percent: discount_percent(
+            furniture.is_promotional,
+            furniture.price,
+            furniture.promotional_price,
+        ),
+        priority: furniture.priority,
+        category: furniture.category.clone(),
+        variations: furniture
+            .variations
+            .iter()
+            .map(|variation| VariationResult {
+                id: variation.id.clone(),
+                name: variation.name.clone(),
+                price: variation.price,
+                color: variation.color.clone(),
+                secondary_color: variation.secondary_color.clone(),
+                quick_description: variation.quick_description.clone(),
+                quick_specifications: variation.quick_specifications.clone(),
+                is_promotional: variation.is_promotional,
+                promotional_price: variation.promotional_price,
+                discount_percent: discount_percent(
+                    variation.is_promotional,
+                    variation.price,
+                    variation.promotional_price,
+                ),
+            })
+            .collect(),
+        matched_variation_ids,
+        score,
+        from_price: from_price(furniture),
+        score_breakdown: None,
+    }
+}
+
+pub(crate) fn from_price(furniture: &Furniture) -> Option<f64> {
+    furniture
+        .price
+        .filter(|price| price.is_finite())
+        .or_else(|| {
+            furniture
+                .variations
+                .iter()
+                .filter_map(|variation| variation.price)
+                .filter(|price| price.is_finite())
+                .min_by(f64::total_cmp)
+        })
+}
+
+/// Whole-number discount of an active promotion, or `None` when there is no
+/// promotion or the promotional price isn't below the regular price.
+pub(crate) fn discount_percent(
+    is_promotional: Option<bool>,
+    price: Option<f64>,
+    promotional_price: Option<f64>,
+) -> Option<f64> {
+    if is_promotional != Some(true) {
+        return None;
+    }
+    let (price, promo) = (price?, promotional_price?);
+    if price <= 0.0 || promo >= price {
+        return None;
+    }
+    Some(((price - promo) / price * 100.0).round())
+}
+
+fn priority_score(priority: Option<i64>) -> f32 {
+    priority.map(|value| (-value) as f32).unwrap_or(0.0)
+}
+
+pub(crate) fn compare_priority(a: Option<i64>, b: Option<i64>) -> std::cmp::Ordering {
+    match (a, b) {
+        (Some(x), Some(y)) => x.cmp(&y),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => std::cmp::Ordering::Equal,
+    }
+}
+
+pub(crate) fn compare_name(a: &Option<String>, b: &Option<String>) -> std::cmp::Ordering {
+    match (a, b) {
+        (Some(x), Some(y)) => collate(x, y),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => std::cmp::Ordering::Equal,
+    }
+}
+
+#[cfg(feature = "collation")]
+fn collate(a: &str, b: &str) -> std::cmp::Ordering {
+    thread_local! {
+        static COLLATOR: std::cell::RefCell<feruca::Collator> =
+            std::cell::RefCell::new(feruca::Collator::default());
+    }
+    COLLATOR.with(|collator| collator.borrow_mut().collate(a, b))
+}
+
+/// Without the `collation` feature, compares accent-folded lowercase text so
+/// "Água" sorts among the A's, falling back to the raw text on ties.
+#[cfg(not(feature = "collation"))]
+fn collate(a: &str, b: &str) -> std::cmp::Ordering {
+    normalize(a).cmp(&normalize(b)).then_with(|| a.cmp(b))
+}
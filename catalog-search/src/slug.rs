@@ -0,0 +1,45 @@
+//! URL slugs for product names, shared by `catalog-tools` and the server so
+//! both produce the same URLs.
+
+use unicode_normalization::{UnicodeNormalization, char::is_combining_mark};
+
+/// Lowercases `input` and replaces every character that isn't an ASCII letter
+/// or digit with `-`, trimming dashes at both ends. Accented letters are
+/// transliterated first (`Cadeira Água` -> `cadeira-agua`), as are a few
+/// letters without a decomposition (`ß`, `æ`, `ø`, ...).
+pub fn slugify(input: &str) -> String {
+    input
+        .nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(transliterate)
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect::<String>()
+        .trim_matches('-')
+        .to_string()
+}
+
+fn transliterate(c: char) -> Vec<char> {
+    let replacement = match c {
+        'ß' => "ss",
+        'æ' => "ae",
+        'Æ' => "AE",
+        'œ' => "oe",
+        'Œ' => "OE",
+        'ø' => "o",
+        'Ø' => "O",
+        'đ' | 'ð' => "d",
+        'Đ' | 'Ð' => "D",
+        'ł' => "l",
+        'Ł' => "L",
+        'þ' => "th",
+        'Þ' => "TH",
+        _ => return vec![c],
+    };
+    replacement.chars().collect()
+}
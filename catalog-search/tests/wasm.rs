@@ -0,0 +1,125 @@
+//! Exercises the `#[wasm_bindgen]` surface: the values returned here are the
+//! ones the browser sees. Run with `wasm-pack test --node`.
+#![cfg(target_arch = "wasm32")]
+
+use catalog_search::model::{Catalog, Furniture, Variation};
+use catalog_search::{CatalogSearch, encode_catalog};
+use serde_json::{Value, json};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_test::wasm_bindgen_test;
+
+fn sample_bytes() -> Vec<u8> {
+    let catalog = Catalog {
+        items: vec![
+            Furniture {
+                id: "1".into(),
+                name: Some("Sample Chair".into()),
+                slug: Some("sample-chair".into()),
+                description_text: Some("A comfy chair for reading".into()),
+                price: Some(199.0),
+                priority: Some(2),
+                variations: vec![Variation {
+                    id: "v1".into(),
+                    name: Some("Walnut".into()),
+                    color: Some("Brown".into()),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            Furniture {
+                id: "2".into(),
+                name: Some("Oak Table".into()),
+                slug: Some("oak-table".into()),
+                priority: Some(1),
+                ..Default::default()
+            },
+        ],
+        meta: None,
+    };
+    encode_catalog(&catalog).expect("encode")
+}
+
+fn engine() -> CatalogSearch {
+    CatalogSearch::new(&sample_bytes(), JsValue::UNDEFINED).expect("construct")
+}
+
+fn to_json(value: JsValue) -> Value {
+    serde_wasm_bindgen::from_value(value).expect("plain JSON value")
+}
+
+#[wasm_bindgen_test]
+fn search_returns_plain_product_objects() {
+    let results = to_json(engine().search("chair", None, None).unwrap());
+    let results = results.as_array().expect("array");
+    assert_eq!(results.len(), 1);
+
+    let product = &results[0];
+    assert_eq!(product["id"], "1");
+    assert_eq!(product["name"], "Sample Chair");
+    assert_eq!(product["price"], 199.0);
+    assert_eq!(product["description"], "A comfy chair for reading");
+    assert_eq!(product["category"], Value::Null);
+    assert_eq!(product["matched_variation_ids"], json!([]));
+    assert!(product["score"].as_f64().unwrap() > 0.0);
+    assert!(product.get("score_breakdown").is_none());
+
+    let variation = &product["variations"][0];
+    assert_eq!(variation["id"], "v1");
+    assert_eq!(variation["color"], "Brown");
+}
+
+#[wasm_bindgen_test]
+fn debug_search_attaches_a_score_breakdown() {
+    let results = to_json(engine().search("chair", Some(true), None).unwrap());
+    let breakdown = &results[0]["score_breakdown"];
+    assert_eq!(breakdown["tokens"][0]["token"], "chair");
+    assert!(breakdown["priority"].is_number());
+}
+
+#[wasm_bindgen_test]
+fn all_lists_every_product_in_catalog_order() {
+    let results = to_json(engine().all_js(None).unwrap());
+    let ids: Vec<_> = results
+        .as_array()
+        .expect("array")
+        .iter()
+        .map(|product| product["id"].as_str().unwrap())
+        .collect();
+    assert_eq!(ids, ["1", "2"]);
+}
+
+#[wasm_bindgen_test]
+fn empty_queries_list_products_and_unknown_terms_match_nothing() {
+    let engine = engine();
+    let results = to_json(engine.search("", None, None).unwrap());
+    assert_eq!(results.as_array().unwrap().len(), 2);
+
+    let results = to_json(engine.search("zzzz", None, None).unwrap());
+    assert_eq!(results, json!([]));
+}
+
+#[wasm_bindgen_test]
+fn search_excluding_takes_an_array_of_ids() {
+    let engine = engine();
+    let results = to_json(
+        engine
+            .search_excluding_js("", Some(vec!["1".into()]), None)
+            .unwrap(),
+    );
+    assert_eq!(results[0]["id"], "2");
+    assert_eq!(results.as_array().unwrap().len(), 1);
+
+    let results = to_json(engine.search_excluding_js("", None, None).unwrap());
+    assert_eq!(results.as_array().unwrap().len(), 2);
+}
+
+#[wasm_bindgen_test]
+fn errors_are_js_errors_with_a_code() {
+    let err = match CatalogSearch::new(&[], JsValue::UNDEFINED) {
+        Ok(_) => panic!("an empty blob must be rejected"),
+        Err(err) => JsValue::from(err),
+    };
+    assert!(err.is_instance_of::<js_sys::Error>());
+    let code = js_sys::Reflect::get(&err, &"code".into()).unwrap();
+    assert_eq!(code.as_string().as_deref(), Some("empty_catalog"));
+}
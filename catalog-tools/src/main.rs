@@ -1,23 +1,27 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use catalog_search::{
-    encode_catalog,
-    model::{Catalog, Furniture, Variation},
-    prepare_catalog,
+    PrepareOptions, ScoringWeights, SearchEngine, decode_catalog_any, decode_catalog_legacy,
+    encode_catalog, encode_catalog_json, encode_results,
+    model::{Catalog, CatalogMeta, Furniture, Variation},
+    prepare_catalog, prepare_catalog_with_options,
+    raw::{RawFurniture, attach_variations},
+    slug::slugify,
 };
-use clap::{Args, Parser, Subcommand};
+use chrono::{SecondsFormat, Utc};
+use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum};
 use rand::{
     Rng, SeedableRng,
     distributions::{Alphanumeric, DistString},
     rngs::StdRng,
     seq::SliceRandom,
 };
-use serde::Deserialize;
-use serde_json::Value;
+use serde::Serialize;
 use std::{
-    collections::HashMap,
+    collections::HashSet,
     fs,
     io::BufWriter,
     path::{Path, PathBuf},
+    time::Instant,
 };
 use uuid::Uuid;
 
@@ -38,6 +42,17 @@ enum Command {
     /// Convert JSON furniture/variation exports into a catalog binary
     #[command(name = "from-json")]
     FromJson(FromJsonArgs),
+    /// Export a catalog binary as CSV for spreadsheet review
+    #[command(name = "to-csv")]
+    ToCsv(ToCsvArgs),
+    /// Time search queries against a catalog binary
+    Bench(BenchArgs),
+    /// Show how each result of a query was scored
+    Explain(ExplainArgs),
+    /// Write a smaller catalog with only some of another catalog's products
+    Subset(SubsetArgs),
+    /// Check a catalog for empty or duplicate ids and unsearchable products
+    Validate(ValidateArgs),
 }
 
 #[derive(Args)]
@@ -48,15 +63,23 @@ struct MockArgs {
     /// Number of variations per product
     #[arg(long, default_value_t = 3)]
     variations_per_product: usize,
-    /// Output path for the catalog bincode blob
+    /// Output path for the catalog blob
     #[arg(long)]
     catalog_out: PathBuf,
+    /// Encoding of the catalog blob
+    #[arg(long, value_enum, default_value_t = CatalogFormat::Bincode)]
+    format: CatalogFormat,
     /// Optional path to write the generated catalog as JSON (for inspection)
     #[arg(long)]
     json_out: Option<PathBuf>,
     /// Optional RNG seed to make generation deterministic
     #[arg(long)]
     seed: Option<u64>,
+    /// Fraction (0.0-1.0) of products that reuse an earlier product's name,
+    /// with their own id and slug. Useful for checking that ranking stays
+    /// stable among same-named products.
+    #[arg(long, default_value_t = 0.0, value_parser = parse_ratio)]
+    duplicate_ratio: f64,
 }
 
 #[derive(Args)]
@@ -67,12 +90,121 @@ struct FromJsonArgs {
     /// Variation JSON export (array of variations)
     #[arg(long)]
     variations: PathBuf,
-    /// Output path for the catalog bincode blob
+    /// Output path for the catalog blob
     #[arg(long)]
     catalog_out: PathBuf,
+    /// Encoding of the catalog blob
+    #[arg(long, value_enum, default_value_t = CatalogFormat::Bincode)]
+    format: CatalogFormat,
     /// Optional path to write the derived catalog as JSON (for inspection)
     #[arg(long)]
     json_out: Option<PathBuf>,
+    /// Only include products with this status (repeatable, case-insensitive)
+    #[arg(long = "status")]
+    statuses: Vec<String>,
+    /// Skip products with this status (repeatable, case-insensitive)
+    #[arg(long = "exclude-status")]
+    exclude_statuses: Vec<String>,
+    /// Drop variations that have no name, color or price
+    #[arg(long)]
+    drop_empty_variations: bool,
+    /// Merge products sharing an integration id, keeping the highest priority
+    #[arg(long)]
+    dedupe_integration_id: bool,
+    /// Reduce HTML in descriptions to plain text
+    #[arg(long)]
+    strip_html: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum CatalogFormat {
+    Bincode,
+    Json,
+}
+
+#[derive(Args)]
+struct ToCsvArgs {
+    /// Catalog blob (bincode or JSON) to export
+    #[arg(long)]
+    catalog: PathBuf,
+    /// Output path for the CSV file
+    #[arg(long)]
+    out: PathBuf,
+    /// Write one row per variation instead of one row per product
+    #[arg(long)]
+    per_variation: bool,
+    /// Read a headerless blob written before catalogs were versioned
+    #[arg(long)]
+    legacy: bool,
+}
+
+#[derive(Args)]
+struct BenchArgs {
+    /// Catalog blob (bincode or JSON) to search
+    #[arg(long)]
+    catalog: PathBuf,
+    /// Query to time (repeatable); defaults to a small mixed set
+    #[arg(long = "query")]
+    queries: Vec<String>,
+    /// Runs per query
+    #[arg(long, default_value_t = 20)]
+    iterations: u32,
+    /// Read a headerless blob written before catalogs were versioned
+    #[arg(long)]
+    legacy: bool,
+}
+
+#[derive(Args)]
+struct ExplainArgs {
+    /// Catalog blob (bincode or JSON) to search
+    #[arg(long)]
+    catalog: PathBuf,
+    /// Query to explain
+    #[arg(long)]
+    query: String,
+    /// Scorer weights as JSON (`{"name": 2.0}`) or a path to a JSON file;
+    /// keys left out keep their defaults
+    #[arg(long)]
+    weights: Option<String>,
+    /// Read a headerless blob written before catalogs were versioned
+    #[arg(long)]
+    legacy: bool,
+}
+
+#[derive(Args)]
+#[command(group = ArgGroup::new("selection").required(true).multiple(true))]
+struct SubsetArgs {
+    /// Catalog blob (bincode or JSON) to take products from
+    #[arg(long)]
+    catalog: PathBuf,
+    /// File listing the product ids to keep, one per line
+    #[arg(long, group = "selection")]
+    ids: Option<PathBuf>,
+    /// Keep products in this category (case-insensitive)
+    #[arg(long, group = "selection")]
+    category: Option<String>,
+    /// Keep at most this many products, in catalog order
+    #[arg(long, group = "selection")]
+    limit: Option<usize>,
+    /// Output path for the subset catalog blob
+    #[arg(long)]
+    out: PathBuf,
+    /// Encoding of the subset catalog blob
+    #[arg(long, value_enum, default_value_t = CatalogFormat::Bincode)]
+    format: CatalogFormat,
+    /// Read a headerless blob written before catalogs were versioned
+    #[arg(long)]
+    legacy: bool,
+}
+
+#[derive(Args)]
+struct ValidateArgs {
+    /// Catalog blob (bincode or JSON) to check
+    #[arg(long)]
+    catalog: PathBuf,
+    /// Read a headerless blob written before catalogs were versioned
+    #[arg(long)]
+    legacy: bool,
 }
 
 fn main() -> Result<()> {
@@ -80,6 +212,11 @@ fn main() -> Result<()> {
     match cli.command {
         Command::Mock(args) => run_mock(args),
         Command::FromJson(args) => run_from_json(args),
+        Command::ToCsv(args) => run_to_csv(args),
+        Command::Bench(args) => run_bench(args),
+        Command::Explain(args) => run_explain(args),
+        Command::Subset(args) => run_subset(args),
+        Command::Validate(args) => run_validate(args),
     }
 }
 
@@ -152,7 +289,17 @@ fn run_mock(args: MockArgs) -> Result<()> {
         let adjective = adjectives.choose(&mut rng).unwrap();
         let material = materials.choose(&mut rng).unwrap();
         let product_type = product_types.choose(&mut rng).unwrap();
-        let name = format!("{adjective} {material} {product_type}");
+        let mut name = format!("{adjective} {material} {product_type}");
+        // Only draw from the RNG when duplicates are requested so existing
+        // seeds keep producing the same catalog.
+        let duplicate =
+            args.duplicate_ratio > 0.0 && !items.is_empty() && rng.gen_bool(args.duplicate_ratio);
+        if duplicate {
+            let source: &Furniture = &items[rng.gen_range(0..items.len())];
+            if let Some(source_name) = &source.name {
+                name = source_name.clone();
+            }
+        }
         let slug = slugify(&name);
         let price = round_currency(rng.gen_range(50.0..5000.0));
         let promo = rng.gen_bool(0.2);
@@ -190,7 +337,7 @@ fn run_mock(args: MockArgs) -> Result<()> {
                     rng.gen_range(30..240)
                 )),
                 is_promotional: Some(promo && rng.gen_bool(0.5)),
-                promotional_price: promotional_price,
+                promotional_price,
                 ..Default::default()
             });
         }
@@ -232,7 +379,9 @@ fn run_mock(args: MockArgs) -> Result<()> {
         let mut furniture = Furniture {
             id,
             name: Some(name.clone()),
-            slug: Some(if idx % 2 == 0 {
+            slug: Some(if duplicate {
+                format!("{slug}-{idx}")
+            } else if idx % 2 == 0 {
                 slug.clone()
             } else {
                 format!(
@@ -253,6 +402,7 @@ fn run_mock(args: MockArgs) -> Result<()> {
             depth: Some(rng.gen_range(30.0..120.0)),
             height: Some(rng.gen_range(35.0..210.0)),
             width: Some(rng.gen_range(30.0..240.0)),
+            category: Some(product_type.to_string()),
             ..Default::default()
         };
 
@@ -260,9 +410,15 @@ fn run_mock(args: MockArgs) -> Result<()> {
         items.push(furniture);
     }
 
-    let mut catalog = Catalog { items };
+    let mut catalog = Catalog { items, meta: None };
     prepare_catalog(&mut catalog);
-    write_outputs(&catalog, &args.catalog_out, args.json_out.as_deref())?;
+    stamp_meta(&mut catalog);
+    write_outputs(
+        &catalog,
+        &args.catalog_out,
+        args.format,
+        args.json_out.as_deref(),
+    )?;
 
     println!(
         "Generated mock catalog with {} products -> {}",
@@ -278,34 +434,58 @@ fn run_from_json(args: FromJsonArgs) -> Result<()> {
     let variations_raw = fs::read_to_string(&args.variations)
         .with_context(|| format!("reading {}", args.variations.display()))?;
 
-    let raw_furnitures: Vec<RawFurniture> =
+    let mut raw_furnitures: Vec<RawFurniture> =
         serde_json::from_str(&furniture_raw).context("parsing furniture json")?;
-    let mut variations: Vec<Variation> =
+    let variations: Vec<Variation> =
         serde_json::from_str(&variations_raw).context("parsing variations json")?;
 
-    let mut by_furniture: HashMap<String, Vec<Variation>> = HashMap::new();
-    for variation in variations.drain(..) {
-        if let Some(furniture_id) = variation.furniture_id.clone() {
-            by_furniture
-                .entry(furniture_id)
-                .or_default()
-                .push(variation);
-        }
+    let total = raw_furnitures.len();
+    raw_furnitures.retain(|raw| {
+        status_allowed(
+            raw.status.as_deref(),
+            &args.statuses,
+            &args.exclude_statuses,
+        )
+    });
+    let excluded = total - raw_furnitures.len();
+    if excluded > 0 {
+        println!("Excluded {excluded} of {total} products by status");
     }
 
-    let mut items = Vec::with_capacity(raw_furnitures.len());
-    for raw in raw_furnitures {
-        let mut furniture = raw.into_furniture();
-        if let Some(attached) = by_furniture.remove(&furniture.id) {
-            furniture.has_variations = Some(!attached.is_empty());
-            furniture.variations = attached;
+    let mut items: Vec<Furniture> = raw_furnitures
+        .into_iter()
+        .map(RawFurniture::into_furniture)
+        .collect();
+    for furniture in &mut items {
+        if furniture.slug.as_deref() == Some("") {
+            furniture.slug = Some(slugify_fallback());
         }
-        items.push(furniture);
     }
+    attach_variations(&mut items, variations);
 
-    let mut catalog = Catalog { items };
-    prepare_catalog(&mut catalog);
-    write_outputs(&catalog, &args.catalog_out, args.json_out.as_deref())?;
+    let mut catalog = Catalog { items, meta: None };
+    let options = PrepareOptions {
+        drop_empty_variations: args.drop_empty_variations,
+        dedupe_by_integration_id: args.dedupe_integration_id,
+        strip_html: args.strip_html,
+    };
+    let report = prepare_catalog_with_options(&mut catalog, &options);
+    if report.dropped_variations > 0 {
+        println!("Dropped {} empty variations", report.dropped_variations);
+    }
+    if report.merged_products > 0 {
+        println!(
+            "Merged {} products sharing an integration id",
+            report.merged_products
+        );
+    }
+    stamp_meta(&mut catalog);
+    write_outputs(
+        &catalog,
+        &args.catalog_out,
+        args.format,
+        args.json_out.as_deref(),
+    )?;
 
     println!(
         "Built catalog from JSON ({} products) -> {}",
@@ -315,154 +495,341 @@ fn run_from_json(args: FromJsonArgs) -> Result<()> {
     Ok(())
 }
 
-fn write_outputs(catalog: &Catalog, catalog_path: &Path, json_path: Option<&Path>) -> Result<()> {
-    let bytes = encode_catalog(catalog).context("encoding catalog to bincode")?;
-    fs::write(catalog_path, bytes)
-        .with_context(|| format!("writing {}", catalog_path.display()))?;
+/// With no `--status` flags every product passes; otherwise its status must be
+/// listed (products without a status are dropped). `--exclude-status` applies
+/// on top of that.
+fn status_allowed(status: Option<&str>, include: &[String], exclude: &[String]) -> bool {
+    let matches = |list: &[String]| {
+        status.is_some_and(|status| list.iter().any(|s| s.eq_ignore_ascii_case(status.trim())))
+    };
+    (include.is_empty() || matches(include)) && !matches(exclude)
+}
 
-    if let Some(json_path) = json_path {
-        let file = fs::File::create(json_path)
-            .with_context(|| format!("creating {}", json_path.display()))?;
-        let writer = BufWriter::new(file);
-        serde_json::to_writer_pretty(writer, catalog)
-            .with_context(|| format!("writing {}", json_path.display()))?;
+fn run_to_csv(args: ToCsvArgs) -> Result<()> {
+    let catalog = read_catalog(&args.catalog, args.legacy)?;
+
+    let mut writer = csv::Writer::from_path(&args.out)
+        .with_context(|| format!("creating {}", args.out.display()))?;
+    let mut rows = 0;
+
+    for furniture in &catalog.items {
+        if args.per_variation {
+            for variation in &furniture.variations {
+                writer.serialize(VariationRow::new(furniture, variation))?;
+                rows += 1;
+            }
+        } else {
+            writer.serialize(ProductRow::new(furniture))?;
+            rows += 1;
+        }
     }
+    writer
+        .flush()
+        .with_context(|| format!("writing {}", args.out.display()))?;
 
+    println!(
+        "Exported {rows} rows from {} products -> {}",
+        catalog.items.len(),
+        args.out.display()
+    );
     Ok(())
 }
 
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct RawFurniture {
-    id: String,
-    #[serde(default)]
-    integration_id: Option<String>,
-    #[serde(default)]
-    integration_type: Option<String>,
-    #[serde(default)]
-    name: Option<String>,
-    #[serde(default)]
-    slug: Option<String>,
-    #[serde(default)]
-    specifications: Option<String>,
-    #[serde(default)]
-    price: Option<f64>,
-    #[serde(default)]
-    weight: Option<f64>,
-    #[serde(default)]
-    sku: Option<String>,
-    #[serde(default)]
-    status: Option<String>,
-    #[serde(default)]
-    created_at: Option<String>,
-    #[serde(default)]
-    updated_at: Option<String>,
-    #[serde(default)]
-    project_id: Option<String>,
-    #[serde(default)]
-    depth: Option<f64>,
-    #[serde(default)]
-    height: Option<f64>,
-    #[serde(default)]
-    width: Option<f64>,
-    #[serde(default)]
-    description: Option<Value>,
-    #[serde(default)]
-    quick_description: Option<String>,
-    #[serde(default)]
-    has_variations: Option<bool>,
-    #[serde(default)]
-    quick_specifications: Option<String>,
-    #[serde(default)]
-    priority: Option<i64>,
-    #[serde(default)]
-    is_promotional: Option<bool>,
-    #[serde(default)]
-    promotional_price: Option<f64>,
-}
+fn run_bench(args: BenchArgs) -> Result<()> {
+    let catalog = read_catalog(&args.catalog, args.legacy)?;
 
-impl RawFurniture {
-    fn into_furniture(self) -> Furniture {
-        let description_text = self.description.and_then(flatten_description);
-        Furniture {
-            id: self.id,
-            integration_id: self.integration_id,
-            integration_type: self.integration_type,
-            name: self.name,
-            slug: self
-                .slug
-                .map(|s| if s.is_empty() { slugify_fallback() } else { s }),
-            specifications: self.specifications,
-            price: self.price,
-            weight: self.weight,
-            sku: self.sku,
-            status: self.status,
-            created_at: self.created_at,
-            updated_at: self.updated_at,
-            project_id: self.project_id,
-            depth: self.depth,
-            height: self.height,
-            width: self.width,
-            description_text,
-            quick_description: self.quick_description,
-            has_variations: self.has_variations,
-            quick_specifications: self.quick_specifications,
-            priority: self.priority,
-            is_promotional: self.is_promotional,
-            promotional_price: self.promotional_price,
-            variations: Vec::new(),
-            searchable_text: String::new(),
+    let started = Instant::now();
+    let search = SearchEngine::from_catalog(catalog);
+    println!(
+        "Indexed {} products in {:?} (parallel scoring: {})",
+        search.catalog().items.len(),
+        started.elapsed(),
+        if cfg!(feature = "parallel") {
+            "on"
+        } else {
+            "off"
+        }
+    );
+
+    let queries = if args.queries.is_empty() {
+        [
+            "sofa",
+            "oak table",
+            "modern velvet armchair",
+            "price:<500 desk",
+        ]
+        .map(String::from)
+        .to_vec()
+    } else {
+        args.queries
+    };
+    let iterations = args.iterations.max(1);
+
+    for query in &queries {
+        let started = Instant::now();
+        let mut results = Vec::new();
+        for _ in 0..iterations {
+            results = search.search_results(query);
+        }
+        println!(
+            "{query:>28}: {:?}/query ({} results)",
+            started.elapsed() / iterations,
+            results.len()
+        );
+
+        // What `search` and `search_bin` hand to JS for the same page.
+        let started = Instant::now();
+        let mut json_len = 0;
+        for _ in 0..iterations {
+            json_len = serde_json::to_vec(&results)?.len();
+        }
+        let json_time = started.elapsed() / iterations;
+        let started = Instant::now();
+        let mut bin_len = 0;
+        for _ in 0..iterations {
+            bin_len = encode_results(&results)?.len();
         }
+        println!(
+            "{:>28}  json {json_len} bytes in {json_time:?}, binary {bin_len} bytes in {:?}",
+            "",
+            started.elapsed() / iterations
+        );
     }
+
+    Ok(())
 }
 
-fn flatten_description(value: Value) -> Option<String> {
-    let mut parts = Vec::new();
-    collect_text(&value, &mut parts);
-    if parts.is_empty() {
-        None
-    } else {
-        Some(parts.join(" "))
+fn run_explain(args: ExplainArgs) -> Result<()> {
+    let catalog = read_catalog(&args.catalog, args.legacy)?;
+    let mut search = SearchEngine::from_catalog(catalog);
+    if let Some(weights) = &args.weights {
+        search.set_weights(read_weights(weights)?)?;
+    }
+
+    let results = search.search_debug(&args.query);
+    println!("{} results for {:?}", results.len(), args.query);
+    for (rank, result) in results.iter().enumerate() {
+        println!(
+            "\n{:>3}. {} (id {}) score {:.3}",
+            rank + 1,
+            result.name.as_deref().unwrap_or("<unnamed>"),
+            result.id,
+            result.score
+        );
+        let Some(breakdown) = &result.score_breakdown else {
+            continue;
+        };
+        for token in &breakdown.tokens {
+            println!(
+                "       {:<20} text {:.3}  name {:.3}  slug {:.3}",
+                format!("{:?}", token.token),
+                token.text,
+                token.name,
+                token.slug
+            );
+        }
+        println!(
+            "       priority {:.3}  promo {:.3}  recency {:.3}",
+            breakdown.priority, breakdown.promo, breakdown.recency
+        );
+        if !result.matched_variation_ids.is_empty() {
+            println!(
+                "       matched variations: {}",
+                result.matched_variation_ids.join(", ")
+            );
+        }
     }
+
+    Ok(())
 }
 
-fn collect_text(value: &Value, acc: &mut Vec<String>) {
-    match value {
-        Value::String(text) => {
-            let trimmed = text.trim();
-            if !trimmed.is_empty() {
-                acc.push(trimmed.to_string());
-            }
+fn run_subset(args: SubsetArgs) -> Result<()> {
+    let mut catalog = read_catalog(&args.catalog, args.legacy)?;
+    let total = catalog.items.len();
+
+    if let Some(path) = &args.ids {
+        let text =
+            fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        let ids: HashSet<&str> = text
+            .lines()
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .collect();
+        catalog.items.retain(|item| ids.contains(item.id.as_str()));
+        let found: HashSet<&str> = catalog.items.iter().map(|item| item.id.as_str()).collect();
+        let missing = ids.difference(&found).count();
+        if missing > 0 {
+            println!("{missing} listed ids were not in the catalog");
         }
-        Value::Array(items) => {
-            for item in items {
-                collect_text(item, acc);
-            }
+    }
+    if let Some(category) = &args.category {
+        let category = category.trim();
+        catalog.items.retain(|item| {
+            item.category
+                .as_deref()
+                .is_some_and(|value| value.trim().eq_ignore_ascii_case(category))
+        });
+    }
+    if let Some(limit) = args.limit {
+        catalog.items.truncate(limit);
+    }
+
+    // Variations live inside their products, so they come along as is.
+    prepare_catalog(&mut catalog);
+    stamp_meta(&mut catalog);
+    write_outputs(&catalog, &args.out, args.format, None)?;
+
+    println!(
+        "Kept {} of {total} products -> {}",
+        catalog.items.len(),
+        args.out.display()
+    );
+    Ok(())
+}
+
+fn run_validate(args: ValidateArgs) -> Result<()> {
+    let catalog = read_catalog(&args.catalog, args.legacy)?;
+    let issues = catalog.validate();
+    for issue in &issues {
+        println!("{:?}: {}", issue.kind, issue.message);
+    }
+    if !issues.is_empty() {
+        bail!(
+            "{} issue(s) in {} products of {}",
+            issues.len(),
+            catalog.items.len(),
+            args.catalog.display()
+        );
+    }
+    println!(
+        "{} products in {} look fine",
+        catalog.items.len(),
+        args.catalog.display()
+    );
+    Ok(())
+}
+
+/// `value` is inline JSON when it looks like an object, otherwise a path.
+fn read_weights(value: &str) -> Result<ScoringWeights> {
+    if value.trim_start().starts_with('{') {
+        return serde_json::from_str(value).context("parsing --weights");
+    }
+    let text = fs::read_to_string(value).with_context(|| format!("reading {value}"))?;
+    serde_json::from_str(&text).with_context(|| format!("parsing weights from {value}"))
+}
+
+/// One CSV row per product. Field order is the column order.
+#[derive(Serialize)]
+struct ProductRow<'a> {
+    id: &'a str,
+    name: Option<&'a str>,
+    slug: Option<&'a str>,
+    price: Option<f64>,
+    promotional_price: Option<f64>,
+    priority: Option<i64>,
+    category: Option<&'a str>,
+    variation_count: usize,
+    description: Option<&'a str>,
+}
+
+impl<'a> ProductRow<'a> {
+    fn new(furniture: &'a Furniture) -> Self {
+        Self {
+            id: &furniture.id,
+            name: furniture.name.as_deref(),
+            slug: furniture.slug.as_deref(),
+            price: furniture.price,
+            promotional_price: furniture.promotional_price,
+            priority: furniture.priority,
+            category: furniture.category.as_deref(),
+            variation_count: furniture.variations.len(),
+            description: furniture.description_text.as_deref(),
         }
-        Value::Object(map) => {
-            for item in map.values() {
-                collect_text(item, acc);
-            }
+    }
+}
+
+/// One CSV row per variation, repeating the parent product's key fields.
+#[derive(Serialize)]
+struct VariationRow<'a> {
+    id: &'a str,
+    name: Option<&'a str>,
+    slug: Option<&'a str>,
+    category: Option<&'a str>,
+    variation_id: &'a str,
+    variation_name: Option<&'a str>,
+    color: Option<&'a str>,
+    secondary_color: Option<&'a str>,
+    size: Option<&'a str>,
+    price: Option<f64>,
+    promotional_price: Option<f64>,
+}
+
+impl<'a> VariationRow<'a> {
+    fn new(furniture: &'a Furniture, variation: &'a Variation) -> Self {
+        Self {
+            id: &furniture.id,
+            name: furniture.name.as_deref(),
+            slug: furniture.slug.as_deref(),
+            category: furniture.category.as_deref(),
+            variation_id: &variation.id,
+            variation_name: variation.name.as_deref(),
+            color: variation.color.as_deref(),
+            secondary_color: variation.secondary_color.as_deref(),
+            size: variation.size.as_deref(),
+            price: variation.price.or(furniture.price),
+            promotional_price: variation.promotional_price.or(furniture.promotional_price),
         }
-        _ => {}
     }
 }
 
-fn slugify(input: &str) -> String {
-    input
-        .chars()
-        .map(|c| {
-            if c.is_ascii_alphanumeric() {
-                c.to_ascii_lowercase()
-            } else if c.is_whitespace() || c == '-' || c == '_' {
-                '-'
-            } else {
-                '-'
-            }
-        })
-        .collect::<String>()
-        .trim_matches('-')
-        .to_string()
+fn read_catalog(path: &Path, legacy: bool) -> Result<Catalog> {
+    let bytes = fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+    let catalog = if legacy {
+        decode_catalog_legacy(&bytes)
+    } else {
+        decode_catalog_any(&bytes)
+    };
+    catalog.with_context(|| format!("decoding {}", path.display()))
+}
+
+/// Records when and by what the catalog was built, counting what ends up in
+/// the output after preparation.
+fn stamp_meta(catalog: &mut Catalog) {
+    catalog.meta = Some(CatalogMeta {
+        generated_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        product_count: catalog.items.len() as u64,
+        variation_count: catalog
+            .items
+            .iter()
+            .map(|item| item.variations.len() as u64)
+            .sum(),
+        generator_version: concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION"))
+            .to_string(),
+    });
+}
+
+fn write_outputs(
+    catalog: &Catalog,
+    catalog_path: &Path,
+    format: CatalogFormat,
+    json_path: Option<&Path>,
+) -> Result<()> {
+    let bytes = match format {
+        CatalogFormat::Bincode => encode_catalog(catalog).context("encoding catalog to bincode")?,
+        CatalogFormat::Json => encode_catalog_json(catalog).context("encoding catalog to JSON")?,
+    };
+    fs::write(catalog_path, bytes)
+        .with_context(|| format!("writing {}", catalog_path.display()))?;
+
+    if let Some(json_path) = json_path {
+        let file = fs::File::create(json_path)
+            .with_context(|| format!("creating {}", json_path.display()))?;
+        let writer = BufWriter::new(file);
+        serde_json::to_writer_pretty(writer, catalog)
+            .with_context(|| format!("writing {}", json_path.display()))?;
+    }
+
+    Ok(())
 }
 
 fn slugify_fallback() -> String {
@@ -495,6 +862,17 @@ fn random_specs(rng: &mut StdRng) -> String {
         .join(" • ")
 }
 
+fn parse_ratio(value: &str) -> Result<f64, String> {
+    let ratio: f64 = value
+        .parse()
+        .map_err(|_| format!("invalid ratio '{value}'"))?;
+    if (0.0..=1.0).contains(&ratio) {
+        Ok(ratio)
+    } else {
+        Err(format!("ratio must be between 0.0 and 1.0, got {ratio}"))
+    }
+}
+
 fn round_currency(value: f64) -> f64 {
     (value * 100.0).round() / 100.0
 }
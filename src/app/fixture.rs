@@ -0,0 +1,110 @@
+//! Test fixture: a temporary directory holding a routes config and template
+//! files, and the [`AppState`] serving them.
+
+use anyhow::Result;
+use axum::{
+    extract::{Path as PathParams, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::Response,
+};
+use catalog_search::model::{Catalog, Furniture};
+use serde_json::Value;
+use std::{
+    future::Future,
+    path::{Path, PathBuf},
+};
+use tempfile::TempDir;
+
+use super::AppState;
+use crate::{
+    db::Repo,
+    http::{QueryParams, TemplateOverride, TenantPath, render_dynamic},
+    templates::TemplateService,
+    tenancy::TenantResolver,
+};
+
+pub(crate) struct Fixture {
+    /// Removed, with everything in it, when the fixture is dropped.
+    dir: TempDir,
+    pub state: AppState,
+}
+
+impl Fixture {
+    /// Writes `routes` to `routes.json` and each `(name, source)` template
+    /// under `templates/` (names include the tenant, e.g. `shop/about.html`),
+    /// then loads them the way the app does at startup.
+    pub async fn new(routes: &str, templates: &[(&str, &str)]) -> Result<Self> {
+        let dir = tempfile::tempdir()?;
+        let template_dir = dir.path().join("templates");
+        std::fs::create_dir_all(&template_dir)?;
+        for (name, source) in templates {
+            let path = template_dir.join(name);
+            std::fs::create_dir_all(path.parent().unwrap_or(&template_dir))?;
+            std::fs::write(path, source)?;
+        }
+        let config = dir.path().join("routes.json");
+        std::fs::write(&config, routes)?;
+
+        let repo = Repo::new(&config.to_string_lossy()).await?;
+        let state = AppState {
+            tenants: TenantResolver::new(repo.clone()),
+            tmpl: TemplateService::new(template_dir, Default::default()),
+            repo,
+            catalog: Default::default(),
+            admin_token: None,
+        };
+        Ok(Self { dir, state })
+    }
+
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    pub fn template_dir(&self) -> PathBuf {
+        self.path().join("templates")
+    }
+}
+
+/// Writes a prepared, encoded catalog blob to `path` holding one product per
+/// `(id, name, slug)`.
+pub(crate) fn write_catalog(path: &Path, items: &[(&str, &str, &str)]) -> Result<()> {
+    let mut catalog = Catalog {
+        items: items
+            .iter()
+            .map(|(id, name, slug)| Furniture {
+                id: id.to_string(),
+                name: Some(name.to_string()),
+                slug: Some(slug.to_string()),
+                ..Default::default()
+            })
+            .collect(),
+        meta: None,
+    };
+    catalog_search::prepare_catalog(&mut catalog);
+    std::fs::write(path, catalog_search::encode_catalog(&catalog)?)?;
+    Ok(())
+}
+
+/// Calls the page handler the way `GET /@<tenant>/<path>` would, with `params`
+/// (a JSON object) as the query string. An empty `path` is the tenant's home
+/// page.
+pub(crate) fn render(
+    state: &AppState,
+    tenant: &str,
+    path: &str,
+    headers: HeaderMap,
+    params: Value,
+) -> impl Future<Output = Result<Response, (StatusCode, String)>> + use<> {
+    render_dynamic(
+        headers,
+        Query(TemplateOverride::default()),
+        Query(QueryParams {
+            params: params.as_object().cloned().unwrap_or_default(),
+        }),
+        PathParams(TenantPath {
+            tenant: Some(tenant.to_string()),
+            path: Some(path.to_string()).filter(|path| !path.is_empty()),
+        }),
+        State(state.clone()),
+    )
+}
@@ -1,29 +1,59 @@
+#[cfg(test)]
+pub(crate) mod fixture;
+
 use anyhow::Result;
 use axum::{Router, serve};
-use std::net::SocketAddr;
+use std::{net::SocketAddr, time::Instant};
 use tokio::net::TcpListener;
 
-use crate::{db::Repo, http::build_router, templates::TemplateService, tenancy::TenantResolver};
+use crate::{
+    catalog::CatalogStore,
+    db::Repo,
+    http::{RequestLimits, build_router},
+    templates::{TemplateService, TemplateSettings},
+    tenancy::TenantResolver,
+};
 
 #[derive(Clone)]
 pub struct AppState {
     pub tenants: TenantResolver,
     pub tmpl: TemplateService,
     pub repo: Repo,
+    pub catalog: CatalogStore,
+    /// Token admin endpoints require (`ADMIN_TOKEN`); they're disabled
+    /// without one.
+    pub admin_token: Option<String>,
 }
 
 pub async fn run() -> Result<()> {
     let routes_file = std::env::var("ROUTES_FILE").unwrap_or_else(|_| "config/routes.json".into());
     let repo = Repo::new(&routes_file).await?;
     let template_dir = std::env::var("TEMPLATE_DIR").unwrap_or_else(|_| "templates".into());
+    let mut template_settings = TemplateSettings::from_env();
+    for (tenant, settings) in repo.all_tenant_settings() {
+        if !settings.autoescape.is_empty() {
+            template_settings
+                .autoescape
+                .insert(tenant.to_string(), settings.autoescape.clone());
+        }
+    }
+    let catalog = CatalogStore::from_env().await?;
 
     let state = AppState {
-        tenants: TenantResolver::new(repo.clone()),
-        tmpl: TemplateService::new(template_dir),
+        tenants: TenantResolver::from_env(repo.clone()),
+        tmpl: TemplateService::new(template_dir, template_settings),
         repo: repo.clone(),
+        catalog,
+        admin_token: std::env::var("ADMIN_TOKEN")
+            .ok()
+            .filter(|token| !token.is_empty()),
     };
 
-    let app: Router = build_router(state);
+    if env_flag("PRELOAD_TEMPLATES") {
+        preload_templates(&state).await;
+    }
+
+    let app: Router = build_router(state, RequestLimits::from_env());
 
     let addr: SocketAddr = "0.0.0.0:8080".parse()?;
     let listener = TcpListener::bind(addr).await?;
@@ -32,3 +62,34 @@ pub async fn run() -> Result<()> {
     serve(listener, app).await?;
     Ok(())
 }
+
+async fn preload_templates(state: &AppState) {
+    let started = Instant::now();
+    let mut warmed = 0;
+
+    for tenant in state.repo.tenants() {
+        match state.tmpl.env_for(tenant).await {
+            Ok(_) => warmed += 1,
+            Err(err) => tracing::warn!("failed to preload templates for {tenant}: {err:#}"),
+        }
+    }
+
+    state.tmpl.sync_cache().await;
+    tracing::info!(
+        "preloaded templates for {warmed}/{} tenants in {:?} ({} environments cached)",
+        state.repo.tenants().len(),
+        started.elapsed(),
+        state.tmpl.cached_environments()
+    );
+}
+
+pub(crate) fn env_flag(name: &str) -> bool {
+    std::env::var(name)
+        .map(|value| {
+            matches!(
+                value.trim().to_ascii_lowercase().as_str(),
+                "1" | "true" | "yes"
+            )
+        })
+        .unwrap_or(false)
+}
@@ -0,0 +1,188 @@
+use crate::{app::env_flag, db::TenantCfg};
+use anyhow::{Context, Result};
+use catalog_search::{SearchEngine, decode_catalog_any, decode_catalog_legacy};
+use moka::{future::Cache, ops::compute::Op};
+use serde_json::Value as Json;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+use tokio::{fs, task};
+
+#[derive(Clone)]
+pub struct CatalogStore {
+    /// `CATALOG_PATH`, served to tenants without their own `catalog_path`.
+    default_path: Option<PathBuf>,
+    /// Catalogs loaded by path, reloaded when their file changes.
+    files: Cache<PathBuf, Arc<LoadedCatalog>>,
+}
+
+/// A decoded catalog file, with the modification time and size it had when
+/// read; a change to either reloads it.
+struct LoadedCatalog {
+    search: Arc<SearchEngine>,
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+impl Default for CatalogStore {
+    fn default() -> Self {
+        Self {
+            default_path: None,
+            files: Cache::new(64),
+        }
+    }
+}
+
+impl CatalogStore {
+    pub async fn from_env() -> Result<Self> {
+        let Ok(path) = std::env::var("CATALOG_PATH") else {
+            return Ok(Self::default());
+        };
+
+        Self::from_path(PathBuf::from(path)).await
+    }
+
+    /// A store serving the catalog at `path` to tenants without their own.
+    /// It's loaded now so a broken file fails at startup.
+    pub async fn from_path(path: PathBuf) -> Result<Self> {
+        let store = Self {
+            default_path: Some(path),
+            ..Self::default()
+        };
+        store.default_catalog().await?;
+        Ok(store)
+    }
+
+    /// The catalog in the file at `path`, decoded on first use and again
+    /// whenever the file changes. Loads are serialized per path, so requests
+    /// arriving together (or right after a change) decode the file once.
+    pub async fn for_path(&self, path: &Path) -> Result<Arc<SearchEngine>> {
+        let metadata = fs::metadata(path)
+            .await
+            .with_context(|| format!("reading catalog from {}", path.display()))?;
+        let modified = metadata.modified().ok();
+        let len = metadata.len();
+        let fresh = |loaded: &LoadedCatalog| loaded.modified == modified && loaded.len == len;
+
+        if let Some(loaded) = self.files.get(path).await
+            && fresh(&loaded)
+        {
+            return Ok(loaded.search.clone());
+        }
+
+        let result = self
+            .files
+            .entry_by_ref(path)
+            .and_try_compute_with(|current| async move {
+                // Another request may have loaded it while this one waited.
+                if current.is_some_and(|current| fresh(current.value())) {
+                    return Ok(Op::Nop);
+                }
+                let search = Arc::new(load_catalog(path).await?);
+                Ok::<_, anyhow::Error>(Op::Put(Arc::new(LoadedCatalog {
+                    search,
+                    modified,
+                    len,
+                })))
+            })
+            .await?;
+        let loaded = result
+            .into_entry()
+            .context("catalog cache entry missing after load")?
+            .into_value();
+        Ok(loaded.search.clone())
+    }
+
+    /// The catalog in `CATALOG_PATH`, if set; reloaded like [`Self::for_path`].
+    pub async fn default_catalog(&self) -> Result<Option<Arc<SearchEngine>>> {
+        match &self.default_path {
+            Some(path) => self.for_path(path).await.map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// The catalog a tenant serves: its own `catalog_path`, else the
+    /// `CATALOG_PATH` one. `None` when neither is configured.
+    pub async fn for_tenant(
+        &self,
+        settings: Option<&TenantCfg>,
+    ) -> Result<Option<Arc<SearchEngine>>> {
+        match settings.and_then(|settings| settings.catalog_path.as_deref()) {
+            Some(path) => self.for_path(path).await.map(Some),
+            None => self.default_catalog().await,
+        }
+    }
+
+    /// Slug and `updated_at` of every product with a slug in the tenant's
+    /// catalog; empty without one.
+    pub async fn product_slugs(
+        &self,
+        settings: Option<&TenantCfg>,
+    ) -> Result<Vec<(String, Option<String>)>> {
+        let Some(search) = self.for_tenant(settings).await? else {
+            return Ok(Vec::new());
+        };
+        Ok(search
+            .catalog()
+            .items
+            .iter()
+            .filter_map(|item| {
+                let slug = item.slug.clone().filter(|slug| !slug.is_empty())?;
+                Some((slug, item.updated_at.clone()))
+            })
+            .collect())
+    }
+
+    pub async fn search(
+        &self,
+        settings: Option<&TenantCfg>,
+        query: &str,
+        limit: Option<usize>,
+    ) -> Result<Json> {
+        let search = self
+            .for_tenant(settings)
+            .await?
+            .context("no catalog loaded (set CATALOG_PATH or the tenant's catalog_path)")?;
+
+        let mut results = search.search_results(query);
+        if let Some(limit) = limit {
+            results.truncate(limit);
+        }
+
+        serde_json::to_value(results).context("serializing catalog results")
+    }
+}
+
+async fn load_catalog(path: &Path) -> Result<SearchEngine> {
+    let bytes = fs::read(path)
+        .await
+        .with_context(|| format!("reading catalog from {}", path.display()))?;
+    // Decoding, validation and indexing are CPU-bound; keep them off the
+    // async workers.
+    let path = path.to_path_buf();
+    task::spawn_blocking(move || {
+        // CATALOG_LEGACY_FORMAT reads headerless blobs from before versioning.
+        let catalog = if env_flag("CATALOG_LEGACY_FORMAT") {
+            decode_catalog_legacy(&bytes)
+        } else {
+            decode_catalog_any(&bytes)
+        }
+        .with_context(|| format!("decoding catalog from {}", path.display()))?;
+
+        tracing::info!(
+            "loaded catalog with {} products from {}",
+            catalog.items.len(),
+            path.display()
+        );
+        let issues = catalog.validate();
+        for issue in &issues {
+            tracing::warn!("catalog {}: {}", path.display(), issue.message);
+        }
+
+        Ok(SearchEngine::from_catalog(catalog))
+    })
+    .await
+    .context("catalog loading task failed")?
+}
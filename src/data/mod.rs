@@ -1,9 +1,19 @@
-use crate::db::Repo;
+use crate::{
+    catalog::CatalogStore,
+    db::{Repo, TenantCfg},
+};
 use anyhow::{Context, Result};
+use minijinja::value::{Enumerator, Object, ObjectRepr};
+use num_format::{Locale, ToFormattedString};
+use reqwest::header::HeaderMap;
 use serde::{Deserialize, Serialize};
 use serde_json::{Value as Json, json};
-use std::path::PathBuf;
-use tokio::fs;
+use std::{
+    fmt,
+    path::PathBuf,
+    sync::{Arc, OnceLock},
+};
+use tokio::{fs, runtime::Handle};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "provider", rename_all = "snake_case")]
@@ -19,22 +29,101 @@ pub enum DataSourceCfg {
         url: String,
         method: Option<String>,
         headers: Option<Json>,
+        /// Defaults to true: non-2xx responses fail the page. When false,
+        /// every response is returned as
+        /// `{ "status": 404, "headers": { "name": "value" }, "body": ... }`,
+        /// where `body` is the parsed JSON, the raw text if it isn't JSON, or
+        /// null when empty, so templates can branch on `status`.
+        fail_on_error: Option<bool>,
+        /// JSON Pointer (e.g. `/data/furniture`) to the part of the response
+        /// to use. With `fail_on_error: false` it applies to `body`, and only
+        /// for 2xx responses.
+        select: Option<String>,
+        /// Inbound request headers (e.g. `accept-language`) copied onto the
+        /// upstream request, matched case-insensitively. Only listed headers
+        /// are sent, so cookies and credentials stay put unless named here.
+        #[serde(default)]
+        forward_headers: Vec<String>,
     },
     MockFile {
         path: String,
+        /// JSON Pointer to the part of the file to use.
+        select: Option<String>,
+    },
+    Catalog {
+        query: Option<String>,
+        limit: Option<usize>,
     },
 }
 
 pub struct ContextBuilder;
 
+/// The parts of the page request a data source can read: query and route
+/// params for `{{placeholders}}`, and headers for `forward_headers`.
+#[derive(Debug, Clone, Default)]
+pub struct SourceRequest {
+    pub params: serde_json::Map<String, Json>,
+    pub headers: HeaderMap,
+}
+
+impl SourceRequest {
+    /// A copy keeping only the headers named in `names`.
+    fn forwarding(&self, names: &[String]) -> Self {
+        let mut headers = HeaderMap::new();
+        for name in names {
+            let name = name.trim().to_ascii_lowercase();
+            for value in self.headers.get_all(name.as_str()) {
+                if let Ok(name) = reqwest::header::HeaderName::from_bytes(name.as_bytes()) {
+                    headers.append(name, value.clone());
+                }
+            }
+        }
+        Self {
+            params: self.params.clone(),
+            headers,
+        }
+    }
+}
+
 impl ContextBuilder {
+    /// Resolves `source` into the template context. `route` is exposed as
+    /// `page.route`.
     pub async fn from_source(
         repo: &Repo,
+        catalog: &CatalogStore,
         tenant: &str,
         source: &Json,
-        query_params: &serde_json::Map<String, Json>,
+        request: &SourceRequest,
+        route: Json,
     ) -> Result<minijinja::Value> {
-        let mut v = Self::process_source(repo, tenant, source, query_params).await?;
+        Self::build(repo, catalog, tenant, source, request, route, false).await
+    }
+
+    /// Like [`Self::from_source`], but nested sources are only fetched when
+    /// a template first uses their key. The fetch blocks, so render on a
+    /// blocking thread (or a multi-threaded runtime worker).
+    pub async fn from_source_lazy(
+        repo: &Repo,
+        catalog: &CatalogStore,
+        tenant: &str,
+        source: &Json,
+        request: &SourceRequest,
+        route: Json,
+    ) -> Result<minijinja::Value> {
+        Self::build(repo, catalog, tenant, source, request, route, true).await
+    }
+
+    async fn build(
+        repo: &Repo,
+        catalog: &CatalogStore,
+        tenant: &str,
+        source: &Json,
+        request: &SourceRequest,
+        route: Json,
+        lazy: bool,
+    ) -> Result<minijinja::Value> {
+        let query_params = &request.params;
+        let mut v = Self::process_source(repo, catalog, tenant, source, request).await?;
 
         if let Some(site) = v.get_mut("site").and_then(|value| value.as_object_mut()) {
             site.entry("slug".to_string())
@@ -48,7 +137,16 @@ impl ContextBuilder {
             });
         }
 
+        match v.get_mut("page") {
+            Some(Json::Object(page)) => {
+                page.insert("route".to_string(), route);
+            }
+            Some(_) => {}
+            None => v["page"] = json!({ "route": route }),
+        }
+
         // Process nested data sources in the response (e.g., results, products)
+        let mut deferred = Vec::new();
         if let Some(obj) = v.as_object_mut() {
             let keys_to_process: Vec<String> = obj
                 .iter()
@@ -62,139 +160,695 @@ impl ContextBuilder {
                 .collect();
 
             for key in keys_to_process {
-                if let Some(nested_source) = obj.get(&key).cloned() {
-                    if let Ok(mut nested_value) =
-                        Self::process_source(repo, tenant, &nested_source, query_params).await
-                    {
-                        if let Some(data_obj) = nested_value.as_object_mut() {
-                            if let Some(data_value) = data_obj.remove("data") {
-                                nested_value = data_value;
-                            }
-                        }
-                        obj.insert(key, nested_value);
+                if lazy {
+                    if let Some(nested_source) = obj.remove(&key) {
+                        deferred.push((key, nested_source));
                     }
+                    continue;
+                }
+                if let Some(nested_source) = obj.get(&key).cloned()
+                    && let Ok(nested_value) =
+                        Self::process_nested(repo, catalog, tenant, &nested_source, request).await
+                {
+                    obj.insert(key, nested_value);
                 }
             }
         }
 
+        if let Some(settings) = repo.tenant_settings(tenant)
+            && let Some(format) = PriceFormat::from_settings(settings)
+        {
+            format.annotate(&mut v);
+        }
+
         if let Some(obj) = v.as_object_mut() {
             for (key, value) in query_params.iter() {
                 obj.entry(key.clone()).or_insert(value.clone());
             }
 
-            if let Some(q_value) = query_params.get("q") {
-                if let Some(page) = obj.get_mut("page").and_then(|p| p.as_object_mut()) {
-                    page.insert("query".to_string(), q_value.clone());
-                }
+            if let Some(q_value) = query_params.get("q")
+                && let Some(page) = obj.get_mut("page").and_then(|p| p.as_object_mut())
+            {
+                page.insert("query".to_string(), q_value.clone());
             }
         }
 
-        Ok(minijinja::Value::from_serialize(&v))
+        if deferred.is_empty() {
+            return Ok(minijinja::Value::from_serialize(&v));
+        }
+        let mut ctx: std::collections::BTreeMap<String, minijinja::Value> = match v {
+            Json::Object(obj) => obj
+                .into_iter()
+                .map(|(key, value)| (key, minijinja::Value::from_serialize(&value)))
+                .collect(),
+            _ => Default::default(),
+        };
+        for (key, source) in deferred {
+            let lazy = LazySource {
+                repo: repo.clone(),
+                catalog: catalog.clone(),
+                tenant: tenant.to_string(),
+                source,
+                request: request.clone(),
+                runtime: Handle::current(),
+                resolved: OnceLock::new(),
+            };
+            ctx.insert(key, minijinja::Value::from_object(lazy));
+        }
+        Ok(minijinja::Value::from(ctx))
+    }
+
+    /// Resolves a nested source the way page data expects it: a `data` key
+    /// is unwrapped and arrays are paginated. `?page=` / `?per_page=` apply
+    /// to every nested source that yields an array, so one whose list must
+    /// stay whole (e.g. a `categories` sidebar next to `products`) should
+    /// yield an object instead.
+    async fn process_nested(
+        repo: &Repo,
+        catalog: &CatalogStore,
+        tenant: &str,
+        source: &Json,
+        request: &SourceRequest,
+    ) -> Result<Json> {
+        let mut value = Self::process_source(repo, catalog, tenant, source, request).await?;
+        if let Some(data_obj) = value.as_object_mut()
+            && let Some(data_value) = data_obj.remove("data")
+        {
+            value = data_value;
+        }
+        if let (Some(pagination), Json::Array(items)) =
+            (Pagination::from_params(&request.params), &mut value)
+        {
+            value = pagination.apply(std::mem::take(items));
+        }
+        Ok(value)
     }
 
     async fn process_source(
         repo: &Repo,
+        catalog: &CatalogStore,
         tenant: &str,
         source: &Json,
-        query_params: &serde_json::Map<String, Json>,
+        request: &SourceRequest,
     ) -> Result<Json> {
-        let data_source_cfg =
-            if let Ok(cfg) = serde_json::from_value::<DataSourceCfg>(source.clone()) {
-                cfg
-            } else {
-                DataSourceCfg::Static {
-                    payload: source.clone(),
-                }
-            };
+        let query_params = &request.params;
+        let data_source_cfg = if source.get("provider").is_some() {
+            serde_json::from_value::<DataSourceCfg>(source.clone())
+                .context("invalid data source")?
+        } else {
+            DataSourceCfg::Static {
+                payload: source.clone(),
+            }
+        };
 
         match data_source_cfg {
             DataSourceCfg::Static { payload } => Ok(payload),
-            DataSourceCfg::DbQuery { sql, params } => repo.json_query(tenant, &sql, params).await,
+            DataSourceCfg::DbQuery { sql, params } => {
+                let params =
+                    params.map(|params| render_placeholder_value(params, tenant, query_params));
+                repo.json_query(tenant, &sql, params).await
+            }
             DataSourceCfg::Http {
                 url,
                 method,
                 headers,
+                fail_on_error,
+                select,
+                forward_headers,
             } => {
-                let final_url = render_placeholder_string(&url, query_params);
-
-                let client = reqwest::Client::new();
-                let method = method.unwrap_or_else(|| "GET".to_string()).to_uppercase();
-
-                let mut req = match method.as_str() {
-                    "POST" => client.post(&final_url),
-                    "PUT" => client.put(&final_url),
-                    "PATCH" => client.patch(&final_url),
-                    "DELETE" => client.delete(&final_url),
-                    _ => client.get(&final_url),
-                };
+                let method = method.unwrap_or_else(|| "GET".to_string());
+                let fail_on_error = fail_on_error.unwrap_or(true);
+                let mut response = fetch_http(
+                    &url,
+                    &method,
+                    headers.as_ref(),
+                    tenant,
+                    &request.forwarding(&forward_headers),
+                    None,
+                    fail_on_error,
+                )
+                .await?;
 
-                if let Some(headers_obj) = headers {
-                    if let Some(headers_map) = headers_obj.as_object() {
-                        for (key, value) in headers_map {
-                            if let Some(val_str) = value.as_str() {
-                                let rendered = render_placeholder_string(val_str, query_params);
-                                req = req.header(key.clone(), rendered);
-                            }
+                if fail_on_error {
+                    return select_pointer(response, select.as_deref());
+                }
+                let success = response["status"]
+                    .as_u64()
+                    .is_some_and(|status| (200..300).contains(&status));
+                if success {
+                    response["body"] = select_pointer(response["body"].take(), select.as_deref())?;
+                }
+                Ok(response)
+            }
+            DataSourceCfg::MockFile { path, select } => {
+                let dirs = std::env::var("MOCK_DATA_DIR").unwrap_or_else(|_| "mock-data".into());
+                let candidates = mock_file_candidates(&dirs, tenant, &path);
+                let mut found = None;
+                for candidate in &candidates {
+                    match fs::read_to_string(candidate).await {
+                        Ok(raw) => {
+                            found = Some((candidate.clone(), raw));
+                            break;
+                        }
+                        Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+                        Err(err) => {
+                            return Err(err).with_context(|| {
+                                format!("reading mock data file {:?}", candidate)
+                            });
                         }
                     }
                 }
-
-                let response = req
-                    .send()
+                let Some((resolved, raw)) = found else {
+                    anyhow::bail!("mock data file {path:?} not found; tried {candidates:?}");
+                };
+                let parsed = serde_json::from_str(&raw)
+                    .with_context(|| format!("parsing JSON from {:?}", resolved))?;
+                select_pointer(parsed, select.as_deref())
+                    .with_context(|| format!("selecting from {:?}", resolved))
+            }
+            DataSourceCfg::Catalog { query, limit } => {
+                let query = match query {
+                    Some(template) => render_placeholder_string(&template, tenant, query_params),
+                    None => param_str(query_params, "q").unwrap_or_default().to_string(),
+                };
+                catalog
+                    .search(repo.tenant_settings(tenant), &query, limit)
                     .await
-                    .with_context(|| format!("failed to fetch from {}", final_url))?;
+            }
+        }
+    }
+}
 
-                let status = response.status();
+/// A nested data source that is fetched the first time a template touches
+/// it, then behaves like the resolved value. A failed fetch is logged and
+/// resolves to `none`.
+struct LazySource {
+    repo: Repo,
+    catalog: CatalogStore,
+    tenant: String,
+    source: Json,
+    request: SourceRequest,
+    runtime: Handle,
+    resolved: OnceLock<minijinja::Value>,
+}
 
-                if !status.is_success() {
-                    let error_body = response.text().await.unwrap_or_default();
-                    anyhow::bail!("HTTP error {}: {}", status, error_body);
+impl LazySource {
+    fn value(&self) -> &minijinja::Value {
+        self.resolved.get_or_init(|| {
+            let fetch = ContextBuilder::process_nested(
+                &self.repo,
+                &self.catalog,
+                &self.tenant,
+                &self.source,
+                &self.request,
+            );
+            // Templates render synchronously; on a runtime worker the thread
+            // has to be handed over before blocking on the fetch.
+            let fetched = match Handle::try_current() {
+                Ok(_) => tokio::task::block_in_place(|| self.runtime.block_on(fetch)),
+                Err(_) => self.runtime.block_on(fetch),
+            };
+            match fetched {
+                Ok(mut value) => {
+                    if let Some(settings) = self.repo.tenant_settings(&self.tenant)
+                        && let Some(format) = PriceFormat::from_settings(settings)
+                    {
+                        format.annotate(&mut value);
+                    }
+                    minijinja::Value::from_serialize(&value)
+                }
+                Err(err) => {
+                    tracing::warn!("lazy data source for {} failed: {err:#}", self.tenant);
+                    minijinja::Value::from(())
                 }
+            }
+        })
+    }
+}
 
-                let body = response
-                    .text()
-                    .await
-                    .with_context(|| "failed to read response")?;
+impl fmt::Debug for LazySource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LazySource")
+            .field("source", &self.source)
+            .field("resolved", &self.resolved.get().is_some())
+            .finish()
+    }
+}
+
+impl Object for LazySource {
+    fn repr(self: &Arc<Self>) -> ObjectRepr {
+        match self.value().kind() {
+            minijinja::value::ValueKind::Seq => ObjectRepr::Seq,
+            minijinja::value::ValueKind::Map => ObjectRepr::Map,
+            _ => ObjectRepr::Plain,
+        }
+    }
 
-                let parsed =
-                    serde_json::from_str::<Json>(&body).with_context(|| "failed to parse JSON")?;
+    fn get_value(self: &Arc<Self>, key: &minijinja::Value) -> Option<minijinja::Value> {
+        self.value()
+            .get_item(key)
+            .ok()
+            .filter(|value| !value.is_undefined())
+    }
 
-                Ok(parsed)
+    fn enumerate(self: &Arc<Self>) -> Enumerator {
+        let value = self.value();
+        match value.kind() {
+            minijinja::value::ValueKind::Seq => Enumerator::Seq(value.len().unwrap_or(0)),
+            minijinja::value::ValueKind::Map => {
+                Enumerator::Values(value.try_iter().into_iter().flatten().collect())
             }
-            DataSourceCfg::MockFile { path } => {
-                let base = std::env::var("MOCK_DATA_DIR").unwrap_or_else(|_| "mock-data".into());
-                let resolved = PathBuf::from(base).join(path);
-                let raw = fs::read_to_string(&resolved)
-                    .await
-                    .with_context(|| format!("reading mock data file {:?}", resolved))?;
-                serde_json::from_str(&raw)
-                    .with_context(|| format!("parsing JSON from {:?}", resolved))
+            _ => Enumerator::NonEnumerable,
+        }
+    }
+
+    fn is_true(self: &Arc<Self>) -> bool {
+        self.value().is_true()
+    }
+
+    fn render(self: &Arc<Self>, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.value(), f)
+    }
+}
+
+/// Config problems in `source` that would otherwise only surface when a page
+/// renders, each prefixed with `at` (plus the key for nested sources).
+/// Objects with a `provider` must match [`DataSourceCfg`]; anything else is a
+/// free-form static payload.
+pub fn source_errors(source: &Json, at: &str) -> Vec<String> {
+    let payload = if source.get("provider").is_some() {
+        match serde_json::from_value::<DataSourceCfg>(source.clone()) {
+            Ok(DataSourceCfg::Static { payload }) => payload,
+            Ok(_) => return Vec::new(),
+            Err(err) => return vec![format!("{at}: {err}")],
+        }
+    } else {
+        source.clone()
+    };
+
+    let Json::Object(obj) = payload else {
+        return Vec::new();
+    };
+    obj.iter()
+        .filter(|(_, nested)| nested.is_object() && nested.get("provider").is_some())
+        .filter_map(|(key, nested)| {
+            serde_json::from_value::<DataSourceCfg>(nested.clone())
+                .err()
+                .map(|err| format!("{at}.{key}: {err}"))
+        })
+        .collect()
+}
+
+/// Whether `source` resolves to the same context every time for the same
+/// request: true when it (and every nested source) is `static` or
+/// `mock_file`.
+pub fn is_deterministic(source: &Json) -> bool {
+    match source {
+        Json::Object(obj) => {
+            let provider_ok = match obj.get("provider").and_then(|p| p.as_str()) {
+                Some(provider) => matches!(provider, "static" | "mock_file"),
+                None => true,
+            };
+            provider_ok && obj.values().all(is_deterministic)
+        }
+        Json::Array(items) => items.iter().all(is_deterministic),
+        _ => true,
+    }
+}
+
+/// Sends submitted form fields as a JSON body to an `http` data source
+/// (`POST` unless the source sets a method) and returns its JSON response.
+/// Only `{{tenant}}` and `{{env.NAME}}` are substituted into the URL and
+/// headers, so submitted values never end up in them.
+pub async fn forward_form(
+    tenant: &str,
+    source: &Json,
+    fields: &serde_json::Map<String, Json>,
+) -> Result<Json> {
+    let cfg = serde_json::from_value::<DataSourceCfg>(source.clone())
+        .context("parsing form forward data source")?;
+    let DataSourceCfg::Http {
+        url,
+        method,
+        headers,
+        ..
+    } = cfg
+    else {
+        anyhow::bail!("form submissions can only be forwarded to an http data source");
+    };
+
+    let method = method.unwrap_or_else(|| "POST".to_string());
+    let body = Json::Object(fields.clone());
+    fetch_http(
+        &url,
+        &method,
+        headers.as_ref(),
+        tenant,
+        &SourceRequest::default(),
+        Some(&body),
+        true,
+    )
+    .await
+}
+
+/// Calls `url`, substituting `request.params` into it and the configured
+/// `headers`. Every header in `request.headers` is sent as is, so pass only
+/// the ones a source forwards.
+async fn fetch_http(
+    url: &str,
+    method: &str,
+    headers: Option<&Json>,
+    tenant: &str,
+    request: &SourceRequest,
+    body: Option<&Json>,
+    fail_on_error: bool,
+) -> Result<Json> {
+    let query_params = &request.params;
+    let final_url = render_placeholder_string(url, tenant, query_params);
+
+    let client = reqwest::Client::new();
+    let mut req = match method.to_uppercase().as_str() {
+        "POST" => client.post(&final_url),
+        "PUT" => client.put(&final_url),
+        "PATCH" => client.patch(&final_url),
+        "DELETE" => client.delete(&final_url),
+        _ => client.get(&final_url),
+    };
+
+    // A configured header wins over a forwarded one of the same name.
+    let configured = headers.and_then(|headers| headers.as_object());
+    let mut forwarded = request.headers.clone();
+    for key in configured.into_iter().flat_map(|map| map.keys()) {
+        forwarded.remove(key.as_str());
+    }
+    req = req.headers(forwarded);
+    if let Some(headers_map) = configured {
+        for (key, value) in headers_map {
+            if let Some(val_str) = value.as_str() {
+                let rendered = render_placeholder_string(val_str, tenant, query_params);
+                req = req.header(key.clone(), rendered);
             }
         }
     }
+
+    if let Some(body) = body {
+        req = req
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body.to_string());
+    }
+
+    let response = req
+        .send()
+        .await
+        .with_context(|| format!("failed to fetch from {}", final_url))?;
+
+    let status = response.status();
+
+    if !fail_on_error {
+        let headers: serde_json::Map<String, Json> = response
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), json!(value.to_str().ok()?))))
+            .collect();
+        let text = response
+            .text()
+            .await
+            .with_context(|| "failed to read response")?;
+        let body = if text.trim().is_empty() {
+            Json::Null
+        } else {
+            serde_json::from_str(&text).unwrap_or(Json::String(text))
+        };
+        return Ok(json!({
+            "status": status.as_u16(),
+            "headers": headers,
+            "body": body,
+        }));
+    }
+
+    if !status.is_success() {
+        let error_body = response.text().await.unwrap_or_default();
+        anyhow::bail!("HTTP error {}: {}", status, error_body);
+    }
+    if status == reqwest::StatusCode::NO_CONTENT {
+        return Ok(Json::Null);
+    }
+
+    let body = response
+        .text()
+        .await
+        .with_context(|| "failed to read response")?;
+
+    serde_json::from_str::<Json>(&body).with_context(|| "failed to parse JSON")
+}
+
+/// Paths tried for a mock file, in order: for each directory in the
+/// colon-separated `dirs`, the tenant-specific `<dir>/<tenant>/<path>` and
+/// then the shared `<dir>/<path>`.
+fn mock_file_candidates(dirs: &str, tenant: &str, path: &str) -> Vec<PathBuf> {
+    dirs.split(':')
+        .map(str::trim)
+        .filter(|dir| !dir.is_empty())
+        .flat_map(|dir| {
+            let dir = PathBuf::from(dir);
+            [dir.join(tenant).join(path), dir.join(path)]
+        })
+        .collect()
+}
+
+/// Extracts the subtree at a JSON Pointer (RFC 6901), or the whole value
+/// when no pointer is configured.
+fn select_pointer(mut value: Json, pointer: Option<&str>) -> Result<Json> {
+    let Some(pointer) = pointer else {
+        return Ok(value);
+    };
+    if !pointer.is_empty() && !pointer.starts_with('/') {
+        anyhow::bail!("invalid select pointer {pointer:?}: JSON pointers must start with '/'");
+    }
+    value
+        .pointer_mut(pointer)
+        .map(Json::take)
+        .with_context(|| format!("select pointer {pointer:?} matched nothing in the response"))
+}
+
+const DEFAULT_PER_PAGE: usize = 20;
+
+/// One page of `items` as `{ items, page, per_page, total, total_pages }`,
+/// honouring `?page=` / `?per_page=` and defaulting to the first page.
+pub fn paginate(query_params: &serde_json::Map<String, Json>, items: Vec<Json>) -> Json {
+    Pagination::from_params(query_params)
+        .unwrap_or(Pagination {
+            page: 1,
+            per_page: DEFAULT_PER_PAGE,
+        })
+        .apply(items)
+}
+
+/// Pagination requested through `?page=` / `?per_page=`. Only present when at
+/// least one of the two params was supplied, so list sources stay plain arrays
+/// for templates that don't paginate.
+struct Pagination {
+    page: usize,
+    per_page: usize,
+}
+
+impl Pagination {
+    fn from_params(query_params: &serde_json::Map<String, Json>) -> Option<Self> {
+        let page = query_params.get("page").and_then(parse_usize);
+        let per_page = query_params.get("per_page").and_then(parse_usize);
+        if page.is_none() && per_page.is_none() {
+            return None;
+        }
+
+        Some(Self {
+            page: page.unwrap_or(1).max(1),
+            per_page: per_page.unwrap_or(DEFAULT_PER_PAGE).max(1),
+        })
+    }
+
+    fn apply(&self, items: Vec<Json>) -> Json {
+        let total = items.len();
+        let total_pages = total.div_ceil(self.per_page).max(1);
+        let page = self.page.min(total_pages);
+        let items: Vec<Json> = items
+            .into_iter()
+            .skip((page - 1) * self.per_page)
+            .take(self.per_page)
+            .collect();
+
+        json!({
+            "items": items,
+            "page": page,
+            "per_page": self.per_page,
+            "total": total,
+            "total_pages": total_pages,
+        })
+    }
+}
+
+const PRICE_FIELDS: [&str; 2] = ["price", "promotional_price"];
+
+/// Localized price strings for a tenant with a `locale` setting.
+struct PriceFormat {
+    locale: Locale,
+    symbol: Option<String>,
+}
+
+impl PriceFormat {
+    fn from_settings(settings: &TenantCfg) -> Option<Self> {
+        Some(Self {
+            locale: settings.number_locale?,
+            symbol: settings.currency_symbol.clone(),
+        })
+    }
+
+    /// Adds `<field>_formatted` next to every numeric price field, at any
+    /// depth. The numeric fields are left as they are.
+    fn annotate(&self, value: &mut Json) {
+        match value {
+            Json::Object(obj) => {
+                for field in PRICE_FIELDS {
+                    if let Some(amount) = obj.get(field).and_then(Json::as_f64) {
+                        obj.insert(format!("{field}_formatted"), json!(self.format(amount)));
+                    }
+                }
+                obj.values_mut().for_each(|value| self.annotate(value));
+            }
+            Json::Array(items) => items.iter_mut().for_each(|item| self.annotate(item)),
+            _ => {}
+        }
+    }
+
+    fn format(&self, amount: f64) -> String {
+        let cents = (amount * 100.0).round() as i64;
+        let units = (cents.unsigned_abs() / 100).to_formatted_string(&self.locale);
+        let number = format!(
+            "{}{units}{}{:02}",
+            if cents < 0 {
+                self.locale.minus_sign()
+            } else {
+                ""
+            },
+            self.locale.decimal(),
+            cents.unsigned_abs() % 100,
+        );
+        match &self.symbol {
+            Some(symbol) => format!("{symbol} {number}"),
+            None => number,
+        }
+    }
 }
 
+/// Accepts `pt-BR`, `pt_BR` or `pt`, falling back to the language when the
+/// region has no data of its own.
+pub fn parse_locale(name: &str) -> Result<Locale> {
+    let name = name.trim().replace('_', "-");
+    Locale::from_name(&name)
+        .or_else(|_| Locale::from_name(name.split('-').next().unwrap_or_default()))
+        .with_context(|| format!("unknown locale {name:?}"))
+}
+
+fn parse_usize(value: &Json) -> Option<usize> {
+    match value {
+        Json::Number(n) => n.as_u64().map(|n| n as usize),
+        Json::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// Replaces `{{tenant}}`, `{{<query param>}}` and `{{env.NAME}}`. The tenant
+/// is substituted first so a `?tenant=` query param can't override it.
 fn render_placeholder_string(
     template: &str,
+    tenant: &str,
     query_params: &serde_json::Map<String, Json>,
 ) -> String {
-    let mut out = template.to_string();
+    let mut out = template.replace("{{tenant}}", tenant);
     for (key, value) in query_params.iter() {
-        if let Some(val_str) = value.as_str() {
-            let placeholder = format!("{{{{{}}}}}", key);
-            out = out.replace(&placeholder, val_str);
+        let rendered = match value {
+            Json::String(val_str) => val_str.clone(),
+            // Repeated params render comma-separated.
+            Json::Array(values) => values
+                .iter()
+                .filter_map(Json::as_str)
+                .collect::<Vec<_>>()
+                .join(","),
+            _ => continue,
+        };
+        let placeholder = format!("{{{{{}}}}}", key);
+        out = out.replace(&placeholder, &rendered);
+    }
+
+    render_env_placeholders(&out)
+}
+
+/// A single-valued query param as text. A repeated one (an array) yields its
+/// last occurrence, the way a plain form lookup would.
+pub fn param_str<'a>(
+    query_params: &'a serde_json::Map<String, Json>,
+    name: &str,
+) -> Option<&'a str> {
+    match query_params.get(name)? {
+        Json::Array(values) => values.iter().rev().find_map(Json::as_str),
+        value => value.as_str(),
+    }
+}
+
+/// Fills query params into `db_query` params. A string that is exactly one
+/// `{{name}}` placeholder becomes that param's value as is, so a repeated
+/// param binds as an array (e.g. for `= ANY($1)` / `IN (...)`); other strings
+/// are rendered like URLs.
+fn render_placeholder_value(
+    value: Json,
+    tenant: &str,
+    query_params: &serde_json::Map<String, Json>,
+) -> Json {
+    match value {
+        Json::String(text) => {
+            let name = text
+                .strip_prefix("{{")
+                .and_then(|rest| rest.strip_suffix("}}"))
+                .map(str::trim)
+                .filter(|name| *name != "tenant");
+            match name.and_then(|name| query_params.get(name)) {
+                Some(param) => param.clone(),
+                None => Json::String(render_placeholder_string(&text, tenant, query_params)),
+            }
         }
+        Json::Array(items) => Json::Array(
+            items
+                .into_iter()
+                .map(|item| render_placeholder_value(item, tenant, query_params))
+                .collect(),
+        ),
+        Json::Object(map) => Json::Object(
+            map.into_iter()
+                .map(|(key, item)| (key, render_placeholder_value(item, tenant, query_params)))
+                .collect(),
+        ),
+        other => other,
     }
+}
 
+pub fn render_env_placeholders(template: &str) -> String {
+    render_env_placeholders_with(template, |key| std::env::var(key).ok())
+}
+
+/// Like [`render_env_placeholders`], resolving `{{env.KEY}}` through `env`
+/// instead of the process environment.
+pub fn render_env_placeholders_with(
+    template: &str,
+    env: impl Fn(&str) -> Option<String>,
+) -> String {
     let mut rendered = String::new();
-    let mut rest = out.as_str();
+    let mut rest = template;
 
     while let Some(start) = rest.find("{{env.") {
         rendered.push_str(&rest[..start]);
         let after = &rest[start + 6..];
         if let Some(end) = after.find("}}") {
             let key = &after[..end];
-            let value = std::env::var(key).unwrap_or_default();
+            let value = env(key).unwrap_or_default();
             rendered.push_str(&value);
             rest = &after[end + 2..];
         } else {
@@ -207,3 +861,264 @@ fn render_placeholder_string(
     rendered.push_str(rest);
     rendered
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::fixture::Fixture;
+
+    #[test]
+    fn prices_are_formatted_for_the_tenant_locale() {
+        let settings = TenantCfg {
+            number_locale: Some(parse_locale("pt-BR").unwrap()),
+            currency_symbol: Some("R$".into()),
+            ..Default::default()
+        };
+        let format = PriceFormat::from_settings(&settings).unwrap();
+        let mut page = json!({
+            "product": { "price": 1999.5, "promotional_price": 199 },
+            "results": [{ "price": 0.1 }, { "price": null }]
+        });
+        format.annotate(&mut page);
+
+        assert_eq!(page["product"]["price"], 1999.5);
+        assert_eq!(page["product"]["price_formatted"], "R$ 1.999,50");
+        assert_eq!(page["product"]["promotional_price_formatted"], "R$ 199,00");
+        assert_eq!(page["results"][0]["price_formatted"], "R$ 0,10");
+        assert!(page["results"][1].get("price_formatted").is_none());
+
+        let en = TenantCfg {
+            number_locale: Some(parse_locale("en").unwrap()),
+            ..Default::default()
+        };
+        let format = PriceFormat::from_settings(&en).unwrap();
+        assert_eq!(format.format(1234567.891), "1,234,567.89");
+
+        assert_eq!(
+            parse_locale("pt_BR").unwrap(),
+            parse_locale("pt-BR").unwrap()
+        );
+        assert!(parse_locale("xx-YY").is_err());
+        assert!(PriceFormat::from_settings(&TenantCfg::default()).is_none());
+    }
+
+    #[test]
+    fn mock_files_are_looked_up_per_tenant_then_shared() {
+        let candidates = mock_file_candidates("fixtures/local: mock-data", "shop", "home.json");
+
+        assert_eq!(
+            candidates,
+            vec![
+                PathBuf::from("fixtures/local/shop/home.json"),
+                PathBuf::from("fixtures/local/home.json"),
+                PathBuf::from("mock-data/shop/home.json"),
+                PathBuf::from("mock-data/home.json"),
+            ]
+        );
+    }
+
+    #[test]
+    fn select_extracts_pointer_subtrees() {
+        let response = json!({ "data": { "furniture": { "name": "Sofa" }, "tags": ["a", "b"] } });
+
+        assert_eq!(
+            select_pointer(response.clone(), Some("/data/furniture")).unwrap(),
+            json!({ "name": "Sofa" })
+        );
+        assert_eq!(
+            select_pointer(response.clone(), Some("/data/tags/1")).unwrap(),
+            "b"
+        );
+        assert_eq!(select_pointer(response.clone(), None).unwrap(), response);
+
+        let missing = select_pointer(response.clone(), Some("/data/missing")).unwrap_err();
+        assert!(missing.to_string().contains("matched nothing"));
+        let invalid = select_pointer(response, Some("data.furniture")).unwrap_err();
+        assert!(invalid.to_string().contains("must start with '/'"));
+    }
+
+    #[test]
+    fn pagination_wraps_items_and_clamps_to_the_last_page() {
+        let params = |value: Json| value.as_object().unwrap().clone();
+        let items: Vec<Json> = (1..=5).map(|id| json!({ "id": id })).collect();
+
+        assert!(Pagination::from_params(&params(json!({ "q": "sofa" }))).is_none());
+
+        let second = Pagination::from_params(&params(json!({ "page": "2", "per_page": 2 })))
+            .unwrap()
+            .apply(items.clone());
+        assert_eq!(
+            second,
+            json!({
+                "items": [{ "id": 3 }, { "id": 4 }],
+                "page": 2,
+                "per_page": 2,
+                "total": 5,
+                "total_pages": 3,
+            })
+        );
+
+        let past_the_end =
+            Pagination::from_params(&params(json!({ "page": "9", "per_page": "2" })))
+                .unwrap()
+                .apply(items.clone());
+        assert_eq!(past_the_end["page"], 3);
+        assert_eq!(past_the_end["items"], json!([{ "id": 5 }]));
+
+        let zero = Pagination::from_params(&params(json!({ "page": "0" })))
+            .unwrap()
+            .apply(items);
+        assert_eq!(zero["page"], 1);
+        assert_eq!(zero["per_page"], DEFAULT_PER_PAGE);
+        assert_eq!(zero["items"].as_array().unwrap().len(), 5);
+
+        let empty = paginate(&params(json!({ "page": "4" })), Vec::new());
+        assert_eq!(empty["page"], 1);
+        assert_eq!(empty["total_pages"], 1);
+        assert_eq!(empty["items"], json!([]));
+    }
+
+    #[tokio::test]
+    async fn http_sources_can_return_error_statuses() {
+        let app = axum::Router::new().route(
+            "/missing",
+            axum::routing::get(|| async {
+                (
+                    axum::http::StatusCode::NOT_FOUND,
+                    axum::Json(json!({ "error": "not found" })),
+                )
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/missing", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let request = SourceRequest::default();
+        let lenient = fetch_http(&url, "GET", None, "shop", &request, None, false)
+            .await
+            .unwrap();
+        assert_eq!(lenient["status"], 404);
+        assert_eq!(lenient["body"], json!({ "error": "not found" }));
+        assert_eq!(lenient["headers"]["content-type"], "application/json");
+
+        let strict = fetch_http(&url, "GET", None, "shop", &request, None, true).await;
+        assert!(strict.unwrap_err().to_string().contains("404"));
+    }
+
+    #[tokio::test]
+    async fn http_sources_forward_only_listed_request_headers() {
+        let app = axum::Router::new().route(
+            "/echo",
+            axum::routing::get(|headers: axum::http::HeaderMap| async move {
+                let received: serde_json::Map<String, Json> = headers
+                    .iter()
+                    .map(|(name, value)| (name.to_string(), json!(value.to_str().unwrap())))
+                    .collect();
+                axum::Json(received)
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/echo", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let fixture = Fixture::new(r#"{ "tenants": ["shop"] }"#, &[])
+            .await
+            .unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert("accept-language", "pt-BR".parse().unwrap());
+        headers.insert("x-api-key", "from-the-browser".parse().unwrap());
+        headers.insert("cookie", "session=secret".parse().unwrap());
+        headers.insert("authorization", "Bearer user".parse().unwrap());
+        let request = SourceRequest {
+            params: serde_json::Map::new(),
+            headers,
+        };
+        let source = json!({
+            "provider": "http",
+            "url": url,
+            "headers": { "X-Api-Key": "configured" },
+            "forward_headers": ["Accept-Language", "X-Api-Key"]
+        });
+
+        let received = ContextBuilder::process_source(
+            &fixture.state.repo,
+            &CatalogStore::default(),
+            "shop",
+            &source,
+            &request,
+        )
+        .await
+        .unwrap();
+        assert_eq!(received["accept-language"], "pt-BR");
+        assert_eq!(received["x-api-key"], "configured");
+        assert!(received.get("cookie").is_none());
+        assert!(received.get("authorization").is_none());
+    }
+
+    #[test]
+    fn tenant_placeholder_is_substituted_into_urls() {
+        let mut query_params = serde_json::Map::new();
+        query_params.insert("tenant".into(), json!("other"));
+        query_params.insert("q".into(), json!("sofa"));
+
+        let url = render_placeholder_string(
+            "https://api.example.com/{{tenant}}/products?q={{q}}",
+            "shop",
+            &query_params,
+        );
+
+        assert_eq!(url, "https://api.example.com/shop/products?q=sofa");
+    }
+
+    #[test]
+    fn repeated_params_render_joined_or_bind_as_arrays() {
+        let mut query_params = serde_json::Map::new();
+        query_params.insert("color".into(), json!(["red", "blue"]));
+        query_params.insert("q".into(), json!("sofa"));
+
+        let url = render_placeholder_string(
+            "https://api.example.com/products?color={{color}}&q={{q}}",
+            "shop",
+            &query_params,
+        );
+        assert_eq!(
+            url,
+            "https://api.example.com/products?color=red,blue&q=sofa"
+        );
+
+        let params = render_placeholder_value(
+            json!({ "colors": "{{color}}", "q": "{{q}}", "label": "{{tenant}}:{{q}}" }),
+            "shop",
+            &query_params,
+        );
+        assert_eq!(
+            params,
+            json!({ "colors": ["red", "blue"], "q": "sofa", "label": "shop:sofa" })
+        );
+    }
+
+    #[test]
+    fn malformed_sources_are_reported_with_their_location() {
+        let errors = source_errors(
+            &json!({
+                "provider": "static",
+                "payload": {
+                    "title": "Home",
+                    "meta": { "provider": "acme" },
+                    "featured": { "provider": "http", "method": "GET" },
+                    "sale": { "provider": "htp", "url": "https://example.com" },
+                    "ok": { "provider": "mock_file", "path": "home.json" }
+                }
+            }),
+            "data_source",
+        );
+        assert_eq!(errors.len(), 3);
+        assert!(errors[0].starts_with("data_source.featured: missing field `url`"));
+        assert!(errors[1].starts_with("data_source.meta: unknown variant `acme`"));
+        assert!(errors[2].starts_with("data_source.sale: unknown variant `htp`"));
+
+        // Objects without a provider are free-form payloads.
+        assert!(source_errors(&json!({ "title": "Home", "tags": ["a"] }), "x").is_empty());
+        assert!(source_errors(&Json::Null, "x").is_empty());
+    }
+}
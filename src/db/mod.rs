@@ -1,22 +1,141 @@
-use anyhow::{Context, Result};
+use crate::{data, templates::EscapeMode};
+use anyhow::{Context, Result, bail};
+use num_format::Locale;
 use serde::{Deserialize, Serialize};
-use serde_json::{Value as Json, json};
-use std::{collections::HashMap, fs, path::Path, path::PathBuf, sync::Arc};
+use serde_json::{Map, Value as Json, json};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    path::Path,
+    path::PathBuf,
+    sync::Arc,
+};
+
+const SHARED_TENANT: &str = "_shared";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct RouteCfg {
     path: String,
+    /// Exposed to templates as `page.route.name`; defaults to `path`.
+    #[serde(default)]
+    name: Option<String>,
+    /// Arbitrary data exposed to templates as `page.route.meta`.
+    #[serde(default)]
+    meta: Option<Json>,
     template_name: String,
     #[serde(default)]
     data_source: Json,
+    #[serde(default)]
+    status: Option<u16>,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    #[serde(default)]
+    form: Option<FormCfg>,
+    /// Query param that picks an entry of `variants`; defaults to `view`.
+    #[serde(default)]
+    variant_param: Option<String>,
+    #[serde(default)]
+    variants: BTreeMap<String, RouteVariant>,
+    /// Conditional `ETag`/`304` responses. On by default when every data
+    /// source is `static` or `mock_file`; `true` also enables it for other
+    /// providers (e.g. `http`), `false` disables it. A `304` skips
+    /// rendering, so pages that call `set_cookie()` should disable it.
+    #[serde(default)]
+    etag: Option<bool>,
+    /// Sends the page while it renders instead of after, for large listing
+    /// pages. Render errors then abort the response instead of producing a
+    /// `500`, and cookies queued with `set_cookie()` are dropped with a
+    /// warning.
+    #[serde(default)]
+    stream: bool,
+    /// Fetches nested data sources only when the template uses their key,
+    /// for pages whose templates branch on which data they show. Disables
+    /// `etag`, since hashing the context would fetch every source.
+    #[serde(default)]
+    lazy_sources: bool,
 }
 
+/// Alternative template and/or data source for a route, selected by the
+/// value of the route's variant query param (e.g. `?view=list`). Missing
+/// fields fall back to the route's own.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteVariant {
+    #[serde(default)]
+    pub template_name: Option<String>,
+    #[serde(default)]
+    pub data_source: Option<Json>,
+}
+
+/// `POST` handling for a route, e.g. a contact or newsletter form. Routes
+/// without it reject submissions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FormCfg {
+    /// Template rendered after a submission; defaults to the route's template.
+    #[serde(default)]
+    pub success_template: Option<String>,
+    /// An `http` data source the submitted fields are sent to as a JSON body.
+    #[serde(default)]
+    pub forward_to: Option<Json>,
+    /// Largest accepted request body. Defaults to 64 KiB.
+    #[serde(default)]
+    pub max_body_bytes: Option<usize>,
+}
+
+/// Per-tenant settings that aren't tied to a single route.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TenantCfg {
+    /// Template extension (without the dot) -> autoescape mode. Extends the
+    /// default of escaping `.html` as HTML and leaving everything else raw.
+    #[serde(default)]
+    pub autoescape: BTreeMap<String, EscapeMode>,
+    /// Body of the tenant's `robots.txt`. Takes precedence over a
+    /// `robots.txt` template.
+    #[serde(default)]
+    pub robots: Option<String>,
+    /// Names of request cookies exposed to templates as `cookies`; `"*"`
+    /// exposes all of them. Empty by default, so no cookie reaches a
+    /// template unless listed.
+    #[serde(default)]
+    pub cookies: Vec<String>,
+    /// Locale (e.g. `pt-BR`) for the `<field>_formatted` strings added next
+    /// to `price` and `promotional_price` in page data. Unset skips them.
+    #[serde(default)]
+    pub locale: Option<String>,
+    /// `locale` parsed when the config loads.
+    #[serde(skip)]
+    pub number_locale: Option<Locale>,
+    /// Prefixed to formatted prices, e.g. `R$`.
+    #[serde(default)]
+    pub currency_symbol: Option<String>,
+    /// Catalog blob the tenant serves: searched by `/@tenant/api/search`,
+    /// server-rendered search pages and `catalog` data sources, and listed
+    /// in its sitemap. Defaults to the `CATALOG_PATH` catalog.
+    #[serde(default)]
+    pub catalog_path: Option<PathBuf>,
+    /// Tenant whose routes this one falls back to before `_shared`. Chains
+    /// (`franchise -> base -> _shared`) are followed; cycles and unknown
+    /// parents are rejected at load.
+    #[serde(default)]
+    pub inherits: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 struct Config {
     #[serde(default)]
     tenants: Vec<String>,
     #[serde(default)]
     routes: HashMap<String, Vec<RouteCfg>>, // tenant_slug -> routes
+    #[serde(default)]
+    settings: HashMap<String, TenantCfg>, // tenant_slug -> settings
+}
+
+/// One `<tenant>.json` file when the routes config is a directory.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TenantFile {
+    #[serde(default)]
+    routes: Vec<RouteCfg>,
+    #[serde(default)]
+    settings: TenantCfg,
 }
 
 #[derive(Clone)]
@@ -26,34 +145,165 @@ pub struct Repo {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Route {
+    pub name: String,
+    /// The configured path pattern that matched.
+    pub path: String,
+    pub meta: Json,
     pub template_name: String,
     pub data_source: Json,
+    /// Values captured while matching: `:name` segments of a param route, or
+    /// `path` (the part of the requested path after the wildcard's prefix,
+    /// e.g. `a/b` for `/docs/a/b` on `/docs/*`) for a wildcard route.
+    #[serde(default)]
+    pub params: Map<String, Json>,
+    #[serde(default)]
+    pub status: Option<u16>,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default)]
+    pub form: Option<FormCfg>,
+    #[serde(default)]
+    pub variant_param: Option<String>,
+    #[serde(default)]
+    pub variants: BTreeMap<String, RouteVariant>,
+    #[serde(default)]
+    pub etag: Option<bool>,
+    #[serde(default)]
+    pub stream: bool,
+    #[serde(default)]
+    pub lazy_sources: bool,
+}
+
+/// Summary of a configured route for admin tools. Only the data source's
+/// provider is included, since sources can carry credentials (e.g. `http`
+/// headers).
+#[derive(Debug, Clone, Serialize)]
+pub struct RouteSummary {
+    pub path: String,
+    pub name: String,
+    pub template_name: String,
+    pub provider: Option<String>,
+    /// Inherited from a parent tenant or `_shared` rather than configured
+    /// for the tenant.
+    pub shared: bool,
+}
+
+impl Route {
+    /// What templates see as `page.route`.
+    pub fn context(&self) -> Json {
+        json!({
+            "name": self.name,
+            "path": self.path,
+            "template": self.template_name,
+            "meta": self.meta,
+        })
+    }
+
+    /// Whether this is a root wildcard (`/*`) that matches every path.
+    pub fn is_catch_all(&self) -> bool {
+        wildcard_prefix(&self.path) == Some("/")
+    }
+
+    /// Applies the variant named by the request's variant query param, if
+    /// any matches; otherwise the route is returned unchanged.
+    pub fn with_variant(mut self, query_params: &Map<String, Json>) -> Self {
+        let param = self.variant_param.as_deref().unwrap_or("view");
+        let variant = query_params
+            .get(param)
+            .and_then(|value| value.as_str())
+            .and_then(|value| self.variants.get(value))
+            .cloned();
+        if let Some(variant) = variant {
+            if let Some(template_name) = variant.template_name {
+                self.template_name = template_name;
+            }
+            if let Some(data_source) = variant.data_source {
+                self.data_source = data_source;
+            }
+        }
+        self
+    }
+}
+
+impl RouteCfg {
+    fn to_route(&self, params: Map<String, Json>) -> Route {
+        Route {
+            name: self.name.clone().unwrap_or_else(|| self.path.clone()),
+            path: self.path.clone(),
+            meta: self.meta.clone().unwrap_or_else(|| json!({})),
+            template_name: self.template_name.clone(),
+            data_source: self.data_source.clone(),
+            params,
+            status: self.status,
+            headers: self.headers.clone(),
+            form: self.form.clone(),
+            variant_param: self.variant_param.clone(),
+            variants: self.variants.clone(),
+            etag: self.etag,
+            stream: self.stream,
+            lazy_sources: self.lazy_sources,
+        }
+    }
 }
 
 impl Repo {
     pub async fn new(config_path: &str) -> Result<Self> {
         let path = PathBuf::from(config_path);
-        let cfg = load_config(&path)?;
+        let mut cfg = load_config(&path)?;
+        resolve_locales(&mut cfg)
+            .with_context(|| format!("validating routes config from {}", path.display()))?;
+        validate_inheritance(&cfg)
+            .with_context(|| format!("validating routes config from {}", path.display()))?;
+        validate_data_sources(&cfg)
+            .with_context(|| format!("validating routes config from {}", path.display()))?;
         Ok(Self {
             config: Arc::new(cfg),
         })
     }
 
-    pub async fn find_route(&self, tenant: &str, path: &str) -> Result<Option<Route>> {
-        let routes = self
+    /// The tenant, its `inherits` ancestors nearest first, then `_shared`.
+    fn route_chain<'a>(&'a self, tenant: &'a str) -> Vec<&'a str> {
+        let mut chain = vec![tenant];
+        let mut current = tenant;
+        // Cycles are rejected at load; the length check only guards
+        // against looping forever if that ever regresses.
+        while let Some(parent) = self
             .config
-            .routes
-            .get(tenant)
-            .or_else(|| self.config.routes.get("_shared"));
-        if let Some(list) = routes {
-            if let Some(rc) = list.iter().find(|r| r.path == path) {
-                return Ok(Some(Route {
-                    template_name: rc.template_name.clone(),
-                    data_source: rc.data_source.clone(),
-                }));
-            }
+            .settings
+            .get(current)
+            .and_then(|settings| settings.inherits.as_deref())
+            .filter(|parent| *parent != SHARED_TENANT)
+            && chain.len() <= self.config.settings.len()
+        {
+            chain.push(parent);
+            current = parent;
+        }
+        if tenant != SHARED_TENANT {
+            chain.push(SHARED_TENANT);
         }
-        Ok(None)
+        chain
+    }
+
+    /// Routes visible to `tenant`, in lookup order along its chain.
+    fn chain_routes(&self, tenant: &str) -> Vec<&RouteCfg> {
+        self.route_chain(tenant)
+            .into_iter()
+            .filter_map(|tenant| self.config.routes.get(tenant))
+            .flatten()
+            .collect()
+    }
+
+    /// Resolves a route for `path` along the tenant's [`Self::route_chain`],
+    /// one level at a time: any route of the tenant beats its parent's, and
+    /// any of the parent's beats `_shared`'s. Within a level, precedence is
+    /// exact match, then param routes (`/products/:slug`), then wildcards
+    /// (`/*`, `/docs/*`), where the longest wildcard prefix wins.
+    pub async fn find_route(&self, tenant: &str, path: &str) -> Result<Option<Route>> {
+        Ok(self
+            .route_chain(tenant)
+            .into_iter()
+            .filter_map(|level| self.config.routes.get(level))
+            .find_map(|routes| match_route(routes, path)))
     }
 
     pub async fn json_query(
@@ -66,6 +316,67 @@ impl Repo {
         Ok(json!([]))
     }
 
+    /// Static page paths a tenant serves (its own, inherited and `_shared`
+    /// routes), sorted and deduplicated. Param and wildcard routes are
+    /// skipped, as are routes configured with a non-2xx status.
+    pub fn page_paths(&self, tenant: &str) -> Vec<String> {
+        let paths: HashSet<&str> = self
+            .chain_routes(tenant)
+            .into_iter()
+            .filter(|rc| !rc.path.contains(':') && !rc.path.contains('*'))
+            .filter(|rc| rc.status.is_none_or(|status| (200..300).contains(&status)))
+            .map(|rc| rc.path.as_str())
+            .collect();
+
+        let mut paths: Vec<String> = paths.into_iter().map(String::from).collect();
+        paths.sort();
+        paths
+    }
+
+    /// Routes a tenant serves, its own first and then the inherited and
+    /// `_shared` ones it doesn't override, each in config order.
+    pub fn route_summaries(&self, tenant: &str) -> Vec<RouteSummary> {
+        let mut seen = HashSet::new();
+        self.route_chain(tenant)
+            .into_iter()
+            .filter_map(|owner| Some((owner, self.config.routes.get(owner)?)))
+            .flat_map(|(owner, routes)| routes.iter().map(move |rc| (rc, owner != tenant)))
+            .filter(|(rc, _)| seen.insert(rc.path.as_str()))
+            .map(|(rc, shared)| RouteSummary {
+                path: rc.path.clone(),
+                name: rc.name.clone().unwrap_or_else(|| rc.path.clone()),
+                template_name: rc.template_name.clone(),
+                provider: rc
+                    .data_source
+                    .get("provider")
+                    .and_then(|provider| provider.as_str())
+                    .map(String::from),
+                shared,
+            })
+            .collect()
+    }
+
+    /// Whether the loaded config defines any tenant, either listed or with
+    /// routes.
+    pub fn has_tenants(&self) -> bool {
+        !self.config.tenants.is_empty() || !self.config.routes.is_empty()
+    }
+
+    pub fn tenants(&self) -> &[String] {
+        &self.config.tenants
+    }
+
+    pub fn tenant_settings(&self, tenant: &str) -> Option<&TenantCfg> {
+        self.config.settings.get(tenant)
+    }
+
+    pub fn all_tenant_settings(&self) -> impl Iterator<Item = (&str, &TenantCfg)> {
+        self.config
+            .settings
+            .iter()
+            .map(|(tenant, settings)| (tenant.as_str(), settings))
+    }
+
     pub async fn tenant_exists(&self, slug: &str) -> Result<bool> {
         let in_list = self.config.tenants.iter().any(|s| s == slug);
         let in_routes = self.config.routes.contains_key(slug);
@@ -73,10 +384,495 @@ impl Repo {
     }
 }
 
+fn match_params(pattern: &str, path: &str) -> Option<Map<String, Json>> {
+    if !pattern.contains(':') || pattern.contains('*') {
+        return None;
+    }
+
+    let pattern_segments: Vec<&str> = pattern.trim_matches('/').split('/').collect();
+    let path_segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    if pattern_segments.len() != path_segments.len() {
+        return None;
+    }
+
+    let mut params = Map::new();
+    for (expected, actual) in pattern_segments.iter().zip(&path_segments) {
+        if let Some(name) = expected.strip_prefix(':') {
+            if actual.is_empty() {
+                return None;
+            }
+            params.insert(name.to_string(), json!(actual));
+        } else if expected != actual {
+            return None;
+        }
+    }
+    Some(params)
+}
+
+fn match_route(routes: &[RouteCfg], path: &str) -> Option<Route> {
+    if let Some(rc) = routes.iter().find(|r| r.path == path) {
+        return Some(rc.to_route(Map::new()));
+    }
+
+    for rc in routes {
+        if let Some(params) = match_params(&rc.path, path) {
+            return Some(rc.to_route(params));
+        }
+    }
+
+    let mut wildcard: Option<(&str, &RouteCfg)> = None;
+    for rc in routes {
+        if let Some(prefix) = wildcard_prefix(&rc.path)
+            && (path.starts_with(prefix) || path == prefix.trim_end_matches('/'))
+            && wildcard.is_none_or(|(longest, _)| prefix.len() > longest.len())
+        {
+            wildcard = Some((prefix, rc));
+        }
+    }
+    let (prefix, rc) = wildcard?;
+    let mut params = Map::new();
+    let rest = path.strip_prefix(prefix).unwrap_or_default();
+    params.insert("path".to_string(), json!(rest));
+    Some(rc.to_route(params))
+}
+
+fn wildcard_prefix(pattern: &str) -> Option<&str> {
+    pattern
+        .strip_suffix('*')
+        .filter(|prefix| prefix.ends_with('/'))
+}
+
 fn load_config(path: &Path) -> Result<Config> {
+    if path.is_dir() {
+        return load_config_dir(path);
+    }
+
     let text = fs::read_to_string(path)
         .with_context(|| format!("reading routes config from {}", path.display()))?;
     let cfg: Config = serde_json::from_str(&text)
         .with_context(|| format!("parsing routes config from {}", path.display()))?;
     Ok(cfg)
 }
+
+fn load_config_dir(dir: &Path) -> Result<Config> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .with_context(|| format!("reading routes directory {}", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file() && path.extension().and_then(|e| e.to_str()) == Some("json"))
+        .collect();
+    files.sort();
+
+    let mut cfg = Config::default();
+
+    for path in files {
+        let Some(tenant) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("reading routes config from {}", path.display()))?;
+        let file: TenantFile = serde_json::from_str(&text)
+            .with_context(|| format!("parsing routes config from {}", path.display()))?;
+        ensure_unique_paths(&file.routes)
+            .with_context(|| format!("validating routes config from {}", path.display()))?;
+
+        if tenant != SHARED_TENANT {
+            cfg.tenants.push(tenant.to_string());
+        }
+        cfg.routes.insert(tenant.to_string(), file.routes);
+        cfg.settings.insert(tenant.to_string(), file.settings);
+    }
+
+    Ok(cfg)
+}
+
+/// Rejects `inherits` pointing at an unknown tenant or forming a cycle.
+fn validate_inheritance(cfg: &Config) -> Result<()> {
+    let mut tenants: Vec<&String> = cfg.settings.keys().collect();
+    tenants.sort();
+    for tenant in tenants {
+        let mut chain = vec![tenant.as_str()];
+        let mut current = tenant.as_str();
+        while let Some(parent) = cfg
+            .settings
+            .get(current)
+            .and_then(|settings| settings.inherits.as_deref())
+        {
+            if parent == SHARED_TENANT {
+                break;
+            }
+            let known = cfg.routes.contains_key(parent)
+                || cfg.settings.contains_key(parent)
+                || cfg.tenants.iter().any(|t| t == parent);
+            if !known {
+                bail!("tenant '{current}' inherits from unknown tenant '{parent}'");
+            }
+            if chain.contains(&parent) {
+                chain.push(parent);
+                bail!("tenant inheritance cycle: {}", chain.join(" -> "));
+            }
+            chain.push(parent);
+            current = parent;
+        }
+    }
+    Ok(())
+}
+
+/// Parses each tenant's `locale` into `number_locale`, rejecting unknown
+/// ones so a typo fails on boot rather than on every page.
+fn resolve_locales(cfg: &mut Config) -> Result<()> {
+    let mut errors = Vec::new();
+    for (tenant, settings) in &mut cfg.settings {
+        let Some(name) = settings.locale.as_deref() else {
+            continue;
+        };
+        match data::parse_locale(name) {
+            Ok(locale) => settings.number_locale = Some(locale),
+            Err(err) => errors.push(format!("{tenant}: {err:#}")),
+        }
+    }
+    if !errors.is_empty() {
+        errors.sort();
+        bail!(
+            "{} invalid locale(s):\n  {}",
+            errors.len(),
+            errors.join("\n  ")
+        );
+    }
+    Ok(())
+}
+
+/// Checks every route's (and variant's) data source up front, reporting all
+/// of them at once so a bad config fails on boot rather than per request.
+fn validate_data_sources(cfg: &Config) -> Result<()> {
+    let mut errors = Vec::new();
+    for (tenant, routes) in &cfg.routes {
+        for route in routes {
+            let at = format!("{tenant} {}", route.path);
+            errors.extend(data::source_errors(
+                &route.data_source,
+                &format!("{at}: data_source"),
+            ));
+            for (name, variant) in &route.variants {
+                if let Some(source) = &variant.data_source {
+                    errors.extend(data::source_errors(
+                        source,
+                        &format!("{at}: variants.{name}.data_source"),
+                    ));
+                }
+            }
+        }
+    }
+    if !errors.is_empty() {
+        bail!(
+            "{} invalid data source(s):\n  {}",
+            errors.len(),
+            errors.join("\n  ")
+        );
+    }
+    Ok(())
+}
+
+fn ensure_unique_paths(routes: &[RouteCfg]) -> Result<()> {
+    let mut seen = HashSet::new();
+    for route in routes {
+        if !seen.insert(route.path.as_str()) {
+            bail!("duplicate route path '{}'", route.path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repo(config: Json) -> Repo {
+        Repo {
+            config: Arc::new(serde_json::from_value(config).unwrap()),
+        }
+    }
+
+    /// Loads `config` through [`Repo::new`], validation included.
+    async fn load(config: &str) -> Result<Repo> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("routes.json");
+        fs::write(&path, config)?;
+        Repo::new(&path.to_string_lossy()).await
+    }
+
+    #[tokio::test]
+    async fn routes_match_exact_then_params_then_longest_wildcard() {
+        let repo = repo(json!({
+            "routes": {
+                "shop": [
+                    { "path": "/*", "template_name": "catch-all.html" },
+                    { "path": "/docs/*", "template_name": "docs.html" },
+                    { "path": "/products/:slug", "template_name": "product.html" },
+                    { "path": "/products/featured", "template_name": "featured.html" }
+                ]
+            }
+        }));
+        let find = async |path| repo.find_route("shop", path).await.unwrap().unwrap();
+
+        let featured = find("/products/featured").await;
+        assert_eq!(featured.template_name, "featured.html");
+        assert!(featured.params.is_empty());
+
+        let product = find("/products/oak-table").await;
+        assert_eq!(product.template_name, "product.html");
+        assert_eq!(
+            product.params,
+            *json!({ "slug": "oak-table" }).as_object().unwrap()
+        );
+
+        for (path, template, rest) in [
+            ("/docs/setup/linux", "docs.html", "setup/linux"),
+            ("/docs/", "docs.html", ""),
+            ("/docs", "docs.html", ""),
+            (
+                "/products/oak-table/reviews",
+                "catch-all.html",
+                "products/oak-table/reviews",
+            ),
+            ("/about", "catch-all.html", "about"),
+        ] {
+            let route = find(path).await;
+            assert_eq!(route.template_name, template, "{path}");
+            assert_eq!(route.params, *json!({ "path": rest }).as_object().unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn routes_load_from_a_file_or_a_directory_of_tenant_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let routes_dir = dir.path().join("routes");
+        fs::create_dir(&routes_dir).unwrap();
+        fs::write(
+            routes_dir.join("shop.json"),
+            r#"{ "routes": [{ "path": "/", "template_name": "shop-home.html" }] }"#,
+        )
+        .unwrap();
+        fs::write(
+            routes_dir.join("blog.json"),
+            r#"{ "routes": [], "settings": { "locale": "pt-BR" } }"#,
+        )
+        .unwrap();
+        fs::write(
+            routes_dir.join("_shared.json"),
+            r#"{ "routes": [
+                { "path": "/", "template_name": "home.html" },
+                { "path": "/about", "template_name": "about.html" }
+            ] }"#,
+        )
+        .unwrap();
+        fs::write(routes_dir.join("README.txt"), "not a tenant").unwrap();
+
+        let file = dir.path().join("routes.json");
+        fs::write(
+            &file,
+            json!({
+                "tenants": ["blog", "shop"],
+                "routes": {
+                    "shop": [{ "path": "/", "template_name": "shop-home.html" }],
+                    "_shared": [
+                        { "path": "/", "template_name": "home.html" },
+                        { "path": "/about", "template_name": "about.html" }
+                    ]
+                },
+                "settings": { "blog": { "locale": "pt-BR" } }
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        for path in [&routes_dir, &file] {
+            let repo = Repo::new(path.to_str().unwrap()).await.unwrap();
+            assert_eq!(repo.tenants(), ["blog", "shop"]);
+            assert_eq!(
+                repo.tenant_settings("blog").unwrap().locale.as_deref(),
+                Some("pt-BR")
+            );
+            for (tenant, path, template) in [
+                ("shop", "/", "shop-home.html"),
+                ("shop", "/about", "about.html"),
+                ("blog", "/", "home.html"),
+                ("blog", "/about", "about.html"),
+            ] {
+                let route = repo.find_route(tenant, path).await.unwrap().unwrap();
+                assert_eq!(route.template_name, template, "{tenant} {path}");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn duplicate_paths_in_a_tenant_file_fail_to_load() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("shop.json"),
+            r#"{ "routes": [
+                { "path": "/about", "template_name": "about.html" },
+                { "path": "/about", "template_name": "about-v2.html" }
+            ] }"#,
+        )
+        .unwrap();
+
+        let Err(err) = Repo::new(dir.path().to_str().unwrap()).await else {
+            panic!("tenant file with a duplicate path loaded");
+        };
+        let err = format!("{err:#}");
+        assert!(err.contains("shop.json"), "{err}");
+        assert!(err.contains("duplicate route path '/about'"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn routes_with_malformed_sources_fail_to_load() {
+        let loaded = load(
+            r#"{
+                "tenants": ["shop"],
+                "routes": {
+                    "shop": [
+                        { "path": "/", "template_name": "home.html",
+                          "data_source": { "provider": "http", "method": "GET" } },
+                        { "path": "/list", "template_name": "list.html",
+                          "variants": { "grid": { "data_source": { "provider": "mock_fil", "path": "a.json" } } } }
+                    ]
+                }
+            }"#,
+        )
+        .await;
+
+        let Err(err) = loaded else {
+            panic!("config with malformed sources loaded");
+        };
+        let err = format!("{err:#}");
+        assert!(err.contains("2 invalid data source(s)"), "{err}");
+        assert!(
+            err.contains("shop /: data_source: missing field `url`"),
+            "{err}"
+        );
+        assert!(
+            err.contains("shop /list: variants.grid.data_source: unknown variant `mock_fil`"),
+            "{err}"
+        );
+    }
+
+    #[tokio::test]
+    async fn tenants_inherit_routes_from_their_parents() {
+        let repo = load(
+            r#"{
+                "tenants": ["base", "franchise", "outlet"],
+                "settings": {
+                    "franchise": { "inherits": "base" },
+                    "outlet": { "inherits": "_shared" }
+                },
+                "routes": {
+                    "_shared": [
+                        { "path": "/", "template_name": "shared-home.html" },
+                        { "path": "/legal", "template_name": "legal.html" }
+                    ],
+                    "base": [
+                        { "path": "/", "template_name": "base-home.html" },
+                        { "path": "/about", "template_name": "about.html" },
+                        { "path": "/products/featured", "template_name": "featured.html" }
+                    ],
+                    "franchise": [
+                        { "path": "/promo", "template_name": "promo.html" },
+                        { "path": "/products/:slug", "template_name": "product.html" }
+                    ],
+                    "outlet": [
+                        { "path": "/sale", "template_name": "sale.html" }
+                    ]
+                }
+            }"#,
+        )
+        .await
+        .unwrap();
+
+        let template = async |path| {
+            let route = repo.find_route("franchise", path).await.unwrap();
+            route.map(|route| route.template_name)
+        };
+        assert_eq!(template("/promo").await.as_deref(), Some("promo.html"));
+        assert_eq!(template("/").await.as_deref(), Some("base-home.html"));
+        assert_eq!(template("/about").await.as_deref(), Some("about.html"));
+        assert_eq!(template("/legal").await.as_deref(), Some("legal.html"));
+        assert!(repo.find_route("base", "/promo").await.unwrap().is_none());
+        // Any route of the tenant's own beats an exact match further up.
+        assert_eq!(
+            template("/products/featured").await.as_deref(),
+            Some("product.html")
+        );
+        // `_shared` stays the last level for tenants that inherit nothing.
+        let legal = repo.find_route("base", "/legal").await.unwrap();
+        assert_eq!(legal.unwrap().template_name, "legal.html");
+        let legal = repo.find_route("outlet", "/legal").await.unwrap();
+        assert_eq!(legal.unwrap().template_name, "legal.html");
+
+        let summaries: Vec<(String, bool)> = repo
+            .route_summaries("franchise")
+            .into_iter()
+            .map(|summary| (summary.template_name, summary.shared))
+            .collect();
+        assert_eq!(
+            summaries,
+            [
+                ("promo.html".to_string(), false),
+                ("product.html".to_string(), false),
+                ("base-home.html".to_string(), true),
+                ("about.html".to_string(), true),
+                ("featured.html".to_string(), true),
+                ("legal.html".to_string(), true),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn inheritance_cycles_and_unknown_parents_are_rejected_at_load() {
+        let cycle = load(
+            r#"{
+                "settings": {
+                    "a": { "inherits": "b" },
+                    "b": { "inherits": "c" },
+                    "c": { "inherits": "a" }
+                }
+            }"#,
+        )
+        .await;
+        let Err(err) = cycle else {
+            panic!("config with an inheritance cycle loaded");
+        };
+        assert!(
+            format!("{err:#}").contains("tenant inheritance cycle: a -> b -> c -> a"),
+            "{err:#}"
+        );
+
+        let unknown = load(r#"{ "settings": { "a": { "inherits": "ghost" } } }"#).await;
+        let Err(err) = unknown else {
+            panic!("config inheriting from an unknown tenant loaded");
+        };
+        assert!(
+            format!("{err:#}").contains("tenant 'a' inherits from unknown tenant 'ghost'"),
+            "{err:#}"
+        );
+    }
+
+    #[tokio::test]
+    async fn tenant_locales_are_parsed_at_load() {
+        let repo = load(r#"{ "settings": { "shop": { "locale": "pt_BR" } } }"#)
+            .await
+            .unwrap();
+        let settings = repo.tenant_settings("shop").unwrap();
+        assert_eq!(settings.number_locale, Some(Locale::pt));
+
+        let unknown = load(
+            r#"{ "settings": { "shop": { "locale": "pt-BR" }, "blog": { "locale": "xx-YY" } } }"#,
+        )
+        .await;
+        let Err(err) = unknown else {
+            panic!("config with an unknown locale loaded");
+        };
+        let err = format!("{err:#}");
+        assert!(err.contains("1 invalid locale(s)"), "{err}");
+        assert!(err.contains("blog: unknown locale \"xx-YY\""), "{err}");
+    }
+}
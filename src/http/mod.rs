@@ -1,55 +1,375 @@
-use crate::{app::AppState, data::ContextBuilder};
+use crate::{
+    app::{AppState, env_flag},
+    data::{self, ContextBuilder, SourceRequest, forward_form, render_env_placeholders_with},
+    db::Route,
+    templates::{self, env_parse},
+};
 use axum::{
-    Router,
-    body::Body,
-    extract::{Path, Query, State},
-    http::{HeaderMap, HeaderValue, StatusCode, header::CONTENT_TYPE},
-    response::{Html, Response},
-    routing::get,
+    Json, Router,
+    body::{Body, Bytes},
+    extract::{FromRequest, Multipart, Path, Query, Request, State},
+    http::{
+        HeaderMap, HeaderName, HeaderValue, StatusCode,
+        header::{
+            ACCEPT, CACHE_CONTROL, CONTENT_LENGTH, CONTENT_TYPE, COOKIE, ETAG, IF_MODIFIED_SINCE,
+            IF_NONE_MATCH, LAST_MODIFIED, SET_COOKIE,
+        },
+    },
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
 };
-use minijinja::ErrorKind as TemplateErrorKind;
-use serde::Deserialize;
+use catalog_search::SearchEngine;
+use chrono::{DateTime, Utc};
+use http_body_util::Limited;
+use minijinja::{Environment, ErrorKind as TemplateErrorKind};
+use serde::{Deserialize, Serialize};
 use serde_json::json;
-use std::path::{Component, Path as StdPath, PathBuf};
-use tokio::fs;
+use serde_json::{Map, Value};
+use std::{
+    collections::BTreeMap,
+    hash::Hasher,
+    io::Write,
+    path::{Component, Path as StdPath, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+use subtle::ConstantTimeEq;
+use tokio::{fs, sync::mpsc};
+use tracing::Instrument;
 
-pub fn build_router(state: AppState) -> Router {
+pub fn build_router(state: AppState, limits: RequestLimits) -> Router {
     Router::new()
-        .route("/health", get(|| async { "ok" }))
+        .route("/livez", get(|| async { "ok" }))
+        .route("/readyz", get(readiness))
+        .route("/health", get(readiness))
         .route("/static/*path", get(serve_static))
         .route("/favicon.ico", get(serve_favicon))
-        .route("/@:tenant", get(render_dynamic))
-        .route("/@:tenant/", get(render_dynamic))
-        .route("/@:tenant/*path", get(render_dynamic))
-        .route("/*path", get(render_dynamic))
+        .route("/robots.txt", get(robots_index))
+        .route("/@:tenant/api/lint", get(lint_templates))
+        .route("/@:tenant/api/preview", post(preview_template))
+        .route("/@:tenant/api/routes", get(list_routes))
+        .route("/@:tenant/api/search", get(api_search))
+        .route("/@:tenant/static/*path", get(serve_tenant_static))
+        .route("/@:tenant/sitemap.xml", get(sitemap))
+        .route("/@:tenant/robots.txt", get(robots))
+        .route("/@:tenant", get(render_dynamic).post(submit_form))
+        .route("/@:tenant/", get(render_dynamic).post(submit_form))
+        .route("/@:tenant/*path", get(render_dynamic).post(submit_form))
+        .route("/*path", get(render_dynamic).post(submit_form))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            render_error_pages,
+        ))
+        .layer(middleware::from_fn_with_state(
+            limits,
+            enforce_request_limits,
+        ))
+        .layer(middleware::from_fn(propagate_request_id))
         .with_state(state)
 }
 
+const REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Tags every request with an id: the client's `X-Request-Id` when it looks
+/// like one, otherwise a new UUID. It's set on the request for handlers
+/// (templates see it as `request.id`), recorded on the tracing span and
+/// echoed in the response.
+async fn propagate_request_id(mut request: Request, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(&REQUEST_ID)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| valid_request_id(id))
+        .map(String::from)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let Ok(value) = HeaderValue::from_str(&id) else {
+        return next.run(request).await;
+    };
+    request.headers_mut().insert(REQUEST_ID, value.clone());
+
+    let span = tracing::info_span!(
+        "request",
+        id = %id,
+        method = %request.method(),
+        path = %request.uri().path(),
+    );
+    let mut response = next.run(request).instrument(span).await;
+    response.headers_mut().insert(REQUEST_ID, value);
+    response
+}
+
+/// Inbound ids are echoed into headers, logs and pages, so only short
+/// token-like values are trusted.
+fn valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 128
+        && id
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || b"-_.:".contains(&byte))
+}
+
+/// What templates see as `request`.
+fn request_context(headers: &HeaderMap) -> Value {
+    let id = headers
+        .get(&REQUEST_ID)
+        .and_then(|value| value.to_str().ok());
+    json!({ "id": id })
+}
+
+/// Size limits checked before a request is routed. The defaults are generous
+/// for pages and forms but keep crafted requests from exhausting memory.
+#[derive(Debug, Clone, Copy)]
+pub struct RequestLimits {
+    /// Path plus query string, in bytes.
+    pub max_url_bytes: usize,
+    pub max_query_params: usize,
+    /// Applies to every body; form routes can set a lower `max_body_bytes`.
+    pub max_body_bytes: usize,
+}
+
+impl Default for RequestLimits {
+    fn default() -> Self {
+        Self {
+            max_url_bytes: 8 * 1024,
+            max_query_params: 256,
+            max_body_bytes: 4 * 1024 * 1024,
+        }
+    }
+}
+
+impl RequestLimits {
+    /// Reads `MAX_URL_BYTES`, `MAX_QUERY_PARAMS` and `MAX_BODY_BYTES`,
+    /// falling back to the defaults for anything unset.
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            max_url_bytes: env_parse("MAX_URL_BYTES").unwrap_or(defaults.max_url_bytes),
+            max_query_params: env_parse("MAX_QUERY_PARAMS").unwrap_or(defaults.max_query_params),
+            max_body_bytes: env_parse("MAX_BODY_BYTES").unwrap_or(defaults.max_body_bytes),
+        }
+    }
+
+    fn check(&self, request: &Request) -> Result<(), (StatusCode, String)> {
+        let uri = request.uri();
+        let url_bytes = uri.path_and_query().map_or(0, |url| url.as_str().len());
+        if url_bytes > self.max_url_bytes {
+            return Err((
+                StatusCode::URI_TOO_LONG,
+                format!("URL exceeds {} bytes", self.max_url_bytes),
+            ));
+        }
+
+        let params = uri.query().map_or(0, |query| {
+            query.split('&').filter(|p| !p.is_empty()).count()
+        });
+        if params > self.max_query_params {
+            return Err((
+                StatusCode::URI_TOO_LONG,
+                format!(
+                    "URL has more than {} query parameters",
+                    self.max_query_params
+                ),
+            ));
+        }
+
+        let declared = request
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok());
+        if declared.is_some_and(|len| len > self.max_body_bytes as u64) {
+            return Err((
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!("request body exceeds {} bytes", self.max_body_bytes),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Rejects oversized URLs and declared bodies up front, and caps bodies
+/// without a `Content-Length` while they are read.
+async fn enforce_request_limits(
+    State(limits): State<RequestLimits>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if let Err(rejection) = limits.check(&request) {
+        return rejection.into_response();
+    }
+    let request = request.map(|body| Body::new(Limited::new(body, limits.max_body_bytes)));
+    next.run(request).await
+}
+
+/// Largest plain-text error body read back for logging and error pages.
+const ERROR_BODY_LIMIT: usize = 64 * 1024;
+
+/// Turns the plain-text `(StatusCode, String)` errors of tenant requests into
+/// the tenant's `<status>.html` page (or `<class>00.html`, e.g. `500.html`;
+/// `_shared` provides defaults) for clients that accept HTML. The message is
+/// always logged, but templates only see it when `SHOW_ERROR_DETAILS` is set.
+async fn render_error_pages(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let path = request.uri().path().to_string();
+    let headers = request.headers().clone();
+    let response = next.run(request).await;
+
+    let status = response.status();
+    let is_plain_text = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/plain"));
+    if !(status.is_client_error() || status.is_server_error()) || !is_plain_text {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let Ok(body) = axum::body::to_bytes(body, ERROR_BODY_LIMIT).await else {
+        return Response::from_parts(parts, Body::empty());
+    };
+    let message = String::from_utf8_lossy(&body);
+    if status.is_server_error() {
+        tracing::error!("{status} for {path}: {message}");
+    } else {
+        tracing::debug!("{status} for {path}: {message}");
+    }
+
+    let wants_html = headers
+        .get(ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.contains("text/html"));
+    let tenant = match path.strip_prefix("/@") {
+        Some(rest) => rest
+            .split('/')
+            .next()
+            .filter(|tenant| !tenant.is_empty())
+            .map(String::from),
+        None if wants_html => state.tenants.resolve_bare(&headers).await.ok().flatten(),
+        None => None,
+    };
+    let page = match tenant {
+        Some(tenant) if wants_html => {
+            render_error_page(&state, &headers, &tenant, status, &message).await
+        }
+        _ => None,
+    };
+
+    match page {
+        Some(html) => {
+            parts.headers.remove(CONTENT_LENGTH);
+            parts.headers.insert(
+                CONTENT_TYPE,
+                HeaderValue::from_static("text/html; charset=utf-8"),
+            );
+            Response::from_parts(parts, Body::from(html))
+        }
+        None => Response::from_parts(parts, Body::from(body)),
+    }
+}
+
+async fn render_error_page(
+    state: &AppState,
+    headers: &HeaderMap,
+    tenant: &str,
+    status: StatusCode,
+    message: &str,
+) -> Option<String> {
+    let tenant = state.tenants.resolve(headers, tenant).await.ok()?;
+    let env = state.tmpl.env_for(&tenant).await.ok()?;
+    let template = [
+        format!("{}.html", status.as_u16()),
+        format!("{}00.html", status.as_u16() / 100),
+    ]
+    .iter()
+    .find_map(|name| env.get_template(name).ok())?;
+
+    let ctx = minijinja::context! {
+        status => status.as_u16(),
+        reason => status.canonical_reason(),
+        message => env_flag("SHOW_ERROR_DETAILS").then_some(message),
+        request => request_context(headers),
+    };
+    template
+        .render(ctx)
+        .inspect_err(|err| {
+            tracing::warn!("failed to render error page {}: {err:#}", template.name())
+        })
+        .ok()
+}
+
+/// `tenant` is absent on the bare `/*path` route; see [`page_tenant`].
 #[derive(Deserialize)]
-struct TenantPath {
-    tenant: String,
-    path: Option<String>,
+pub(crate) struct TenantPath {
+    pub(crate) tenant: Option<String>,
+    pub(crate) path: Option<String>,
+}
+
+/// The tenant a page request is for: the `@tenant` in the path, or for bare
+/// paths the one the `Host` header names, else `DEFAULT_TENANT`. Bare paths
+/// with neither are 404s.
+async fn page_tenant(
+    state: &AppState,
+    headers: &HeaderMap,
+    tenant: Option<&str>,
+) -> Result<String, (StatusCode, String)> {
+    match tenant {
+        Some(tenant) => state
+            .tenants
+            .resolve(headers, tenant)
+            .await
+            .map_err(internal),
+        None => state
+            .tenants
+            .resolve_bare(headers)
+            .await
+            .map_err(internal)?
+            .ok_or_else(|| {
+                (
+                    StatusCode::NOT_FOUND,
+                    "no tenant for this host; use an /@tenant/ path or set DEFAULT_TENANT"
+                        .to_string(),
+                )
+            }),
+    }
 }
 
 #[derive(Default, Deserialize)]
-struct TemplateOverride {
+pub(crate) struct TemplateOverride {
     #[serde(default)]
     template: Option<String>,
 }
 
+/// Query string params; a repeated key (`?color=red&color=blue`) becomes an
+/// array, a single one stays a string.
 #[derive(Default, Deserialize)]
-struct QueryParams {
-    #[serde(flatten)]
-    params: serde_json::Map<String, serde_json::Value>,
+#[serde(from = "Vec<(String, String)>")]
+pub(crate) struct QueryParams {
+    pub(crate) params: serde_json::Map<String, serde_json::Value>,
+}
+
+impl From<Vec<(String, String)>> for QueryParams {
+    fn from(pairs: Vec<(String, String)>) -> Self {
+        let mut params = Map::new();
+        for (name, value) in pairs {
+            insert_form_field(&mut params, name, value);
+        }
+        Self { params }
+    }
 }
 
-async fn render_dynamic(
+pub(crate) async fn render_dynamic(
     headers: HeaderMap,
     Query(template_override): Query<TemplateOverride>,
     Query(query_params): Query<QueryParams>,
     Path(TenantPath { tenant, path }): Path<TenantPath>,
     State(state): State<AppState>,
-) -> Result<Html<String>, (StatusCode, String)> {
+) -> Result<Response, (StatusCode, String)> {
     let clean_path = path.unwrap_or_else(|| "/".to_string());
     let db_path = if clean_path.starts_with('/') {
         clean_path.clone()
@@ -68,29 +388,27 @@ async fn render_dynamic(
         params_map.insert("product_id".to_string(), json!(slug));
     }
 
-    let tenant = state
-        .tenants
-        .resolve(&headers, &tenant)
-        .await
-        .map_err(internal)?;
+    let tenant = page_tenant(&state, &headers, tenant.as_deref()).await?;
     let mut route = state
         .repo
         .find_route(&tenant, &db_path)
         .await
         .map_err(internal)?;
-    if route.is_none() {
-        if normalized_path == "product" {
-            route = state
-                .repo
-                .find_route(&tenant, "/product")
-                .await
-                .map_err(internal)?;
-        } else if product_slug.is_some() {
-            route = state
-                .repo
-                .find_route(&tenant, "/product")
-                .await
-                .map_err(internal)?;
+    // Product pages prefer the `/product` route (or the built-in product
+    // fallback) over a catch-all wildcard.
+    if (normalized_path == "product" || product_slug.is_some())
+        && route.as_ref().is_none_or(Route::is_catch_all)
+    {
+        route = state
+            .repo
+            .find_route(&tenant, "/product")
+            .await
+            .map_err(internal)?;
+    }
+    let route = route.map(|route| route.with_variant(&query_params.params));
+    if let Some(route) = &route {
+        for (key, value) in &route.params {
+            params_map.insert(key.clone(), value.clone());
         }
     }
 
@@ -101,6 +419,8 @@ async fn render_dynamic(
         .or_else(|| {
             if product_slug.is_some() {
                 Some("pages/product.html".to_string())
+            } else if normalized_path == "search" {
+                Some(SEARCH_TEMPLATE.to_string())
             } else {
                 None
             }
@@ -131,10 +451,94 @@ async fn render_dynamic(
             }
         });
 
-    let env = state.tmpl.env_for(&tenant).await.map_err(internal)?;
-    let ctx = ContextBuilder::from_source(&state.repo, &tenant, &data_source, &params_map)
+    let route_context = match &route {
+        Some(route) => route.context(),
+        None => json!({
+            "name": template_name
+                .rsplit_once('.')
+                .map_or(template_name.as_str(), |(stem, _)| stem),
+            "path": db_path,
+            "template": template_name,
+            "meta": {},
+        }),
+    };
+
+    let lazy = route.as_ref().is_some_and(|route| route.lazy_sources);
+    let use_etag = !lazy
+        && route
+            .as_ref()
+            .and_then(|r| r.etag)
+            .unwrap_or_else(|| data::is_deterministic(&data_source));
+
+    let (env, fingerprint) = state
+        .tmpl
+        .env_with_fingerprint(&tenant)
         .await
         .map_err(internal)?;
+    let source_request = SourceRequest {
+        params: params_map,
+        headers: headers.clone(),
+    };
+    let ctx = if lazy {
+        ContextBuilder::from_source_lazy(
+            &state.repo,
+            &state.catalog,
+            &tenant,
+            &data_source,
+            &source_request,
+            route_context,
+        )
+        .await
+    } else {
+        ContextBuilder::from_source(
+            &state.repo,
+            &state.catalog,
+            &tenant,
+            &data_source,
+            &source_request,
+            route_context,
+        )
+        .await
+    }
+    .map_err(internal)?;
+    let allowed_cookies = state
+        .repo
+        .tenant_settings(&tenant)
+        .map(|settings| settings.cookies.as_slice())
+        .unwrap_or_default();
+    let cookies = request_cookies(&headers, allowed_cookies);
+    let ctx = minijinja::context! { cookies => cookies, ..ctx };
+    let ctx = if template_name == SEARCH_TEMPLATE
+        && let Some(results) = search_results(&state, &tenant, &source_request.params)
+            .await
+            .map_err(internal)?
+    {
+        minijinja::context! { results => results, ..ctx }
+    } else {
+        ctx
+    };
+
+    let etag = if use_etag {
+        Some(context_etag(&ctx, &template_name, fingerprint).map_err(internal)?)
+    } else {
+        None
+    };
+    if let Some(etag) = &etag
+        && if_none_match(&headers, etag)
+    {
+        let mut response = StatusCode::NOT_MODIFIED.into_response();
+        if let Some(route) = &route {
+            apply_route_response(&mut response, route)?;
+            *response.status_mut() = StatusCode::NOT_MODIFIED;
+        }
+        response
+            .headers_mut()
+            .insert(ETAG, HeaderValue::from_str(etag).map_err(internal)?);
+        return Ok(response);
+    }
+
+    // Added after the ETag is computed, since the id differs per request.
+    let ctx = minijinja::context! { request => request_context(&headers), ..ctx };
 
     let tpl = env
         .get_template(&template_name)
@@ -142,84 +546,962 @@ async fn render_dynamic(
             TemplateErrorKind::TemplateNotFound => (StatusCode::NOT_FOUND, err.to_string()),
             _ => internal(err),
         })?;
+    let (mut response, set_cookies) = if route.as_ref().is_some_and(|route| route.stream) {
+        let body = render_streaming(env.clone(), template_name.clone(), ctx);
+        (rendered(&template_name, body), Vec::new())
+    } else if lazy {
+        // Lazy sources block while fetching, so render off the runtime.
+        let env = env.clone();
+        let name = template_name.clone();
+        let (html, set_cookies) = tokio::task::spawn_blocking(move || {
+            let tpl = env.get_template(&name)?;
+            let (html, render_state) = tpl.render_and_return_state(ctx)?;
+            Ok::<_, minijinja::Error>((html, templates::response_cookies(&render_state)))
+        })
+        .await
+        .map_err(internal)?
+        .map_err(internal)?;
+        (rendered(&template_name, html), set_cookies)
+    } else {
+        let (html, render_state) = tpl.render_and_return_state(ctx).map_err(internal)?;
+        (
+            rendered(&template_name, html),
+            templates::response_cookies(&render_state),
+        )
+    };
+    if let Some(route) = &route {
+        apply_route_response(&mut response, route)?;
+    }
+    for cookie in set_cookies {
+        response.headers_mut().append(
+            SET_COOKIE,
+            HeaderValue::from_str(&cookie).map_err(internal)?,
+        );
+    }
+    if let Some(etag) = &etag
+        && response.status().is_success()
+    {
+        response
+            .headers_mut()
+            .insert(ETAG, HeaderValue::from_str(etag).map_err(internal)?);
+    }
+    Ok(response)
+}
+
+/// Output buffered before a streamed page sends its next chunk.
+const STREAM_CHUNK_BYTES: usize = 16 * 1024;
+
+/// Renders on a blocking thread, handing the output to the response body in
+/// chunks as it's produced. The status is sent before rendering finishes, so
+/// a render error can only be logged and the body cut short.
+fn render_streaming(
+    env: Arc<Environment<'static>>,
+    template_name: String,
+    ctx: minijinja::Value,
+) -> Body {
+    let (tx, rx) = mpsc::channel(4);
+    tokio::task::spawn_blocking(move || {
+        let mut writer = ChunkWriter {
+            buf: Vec::with_capacity(STREAM_CHUNK_BYTES),
+            tx: tx.clone(),
+        };
+        let rendered = env
+            .get_template(&template_name)
+            .and_then(|tpl| {
+                let state = tpl.render_to_write(ctx, &mut writer)?;
+                // Headers went out with the first chunk, so queued cookies
+                // can't be sent.
+                let dropped = templates::response_cookies(&state);
+                if !dropped.is_empty() {
+                    tracing::warn!(
+                        "{template_name} is streamed; dropped {} cookie(s) set with set_cookie()",
+                        dropped.len()
+                    );
+                }
+                Ok(())
+            })
+            .map_err(|err| std::io::Error::other(format!("{err:#}")))
+            .and_then(|()| writer.flush());
+        if let Err(err) = rendered {
+            tracing::error!("failed to stream {template_name}: {err}");
+            let _ = tx.blocking_send(Err(err));
+        }
+    });
+    Body::from_stream(futures_util::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|chunk| (chunk, rx))
+    }))
+}
+
+struct ChunkWriter {
+    buf: Vec<u8>,
+    tx: mpsc::Sender<std::io::Result<Bytes>>,
+}
+
+impl std::io::Write for ChunkWriter {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        self.buf.extend_from_slice(data);
+        if self.buf.len() >= STREAM_CHUNK_BYTES {
+            self.flush()?;
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        let chunk = Bytes::from(std::mem::take(&mut self.buf));
+        self.tx
+            .blocking_send(Ok(chunk))
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::BrokenPipe, "client disconnected"))
+    }
+}
+
+/// Cookies from the request's `Cookie` headers whose names are in `allowed`
+/// (`"*"` allows all). Surrounding double quotes are stripped from values.
+fn request_cookies(headers: &HeaderMap, allowed: &[String]) -> Map<String, Value> {
+    let allow_all = allowed.iter().any(|name| name == "*");
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(name, value)| (name.trim(), value.trim()))
+        .filter(|(name, _)| allow_all || allowed.iter().any(|allowed| allowed == name))
+        .map(|(name, value)| {
+            let value = value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .unwrap_or(value);
+            (name.to_string(), json!(value))
+        })
+        .collect()
+}
+
+/// Weak ETag for a page: the resolved context, the template name and the
+/// tenant's template fingerprint fully determine the rendered output.
+fn context_etag(
+    ctx: &minijinja::Value,
+    template_name: &str,
+    fingerprint: u64,
+) -> Result<String, serde_json::Error> {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    hasher.write(&serde_json::to_vec(ctx)?);
+    hasher.write(template_name.as_bytes());
+    hasher.write_u64(fingerprint);
+    Ok(format!("W/\"{:016x}\"", hasher.finish()))
+}
+
+/// Weak comparison of `If-None-Match` against `etag`, as conditional `GET`s
+/// require.
+fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    headers
+        .get_all(IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|tag| tag.trim() == "*" || opaque(tag) == opaque(etag))
+}
+
+const DEFAULT_FORM_BODY_LIMIT: usize = 64 * 1024;
+
+/// Handles a form `POST` to a route with a `form` config: the fields are
+/// exposed to the template as `form`, optionally forwarded to an http data
+/// source (its response is `forwarded`), and the success template rendered.
+async fn submit_form(
+    headers: HeaderMap,
+    Query(query_params): Query<QueryParams>,
+    Path(TenantPath { tenant, path }): Path<TenantPath>,
+    State(state): State<AppState>,
+    request: Request,
+) -> Result<Response, (StatusCode, String)> {
+    let clean_path = path.unwrap_or_else(|| "/".to_string());
+    let db_path = if clean_path.starts_with('/') {
+        clean_path
+    } else {
+        format!("/{}", clean_path)
+    };
+
+    let tenant = page_tenant(&state, &headers, tenant.as_deref()).await?;
+    let route = state
+        .repo
+        .find_route(&tenant, &db_path)
+        .await
+        .map_err(internal)?;
+    let Some((route, form)) = route.and_then(|route| {
+        let form = route.form.clone()?;
+        Some((route, form))
+    }) else {
+        return Err((
+            StatusCode::METHOD_NOT_ALLOWED,
+            "this page does not accept form submissions".to_string(),
+        ));
+    };
+
+    let limit = form.max_body_bytes.unwrap_or(DEFAULT_FORM_BODY_LIMIT);
+    let fields = read_form(request, limit).await?;
+
+    let forwarded = match &form.forward_to {
+        Some(source) => forward_form(&tenant, source, &fields)
+            .await
+            .map_err(|err| (StatusCode::BAD_GATEWAY, format!("{err:#}")))?,
+        None => Value::Null,
+    };
+
+    let mut source_request = SourceRequest {
+        params: query_params.params,
+        headers: headers.clone(),
+    };
+    for (key, value) in &route.params {
+        source_request.params.insert(key.clone(), value.clone());
+    }
+
+    let env = state.tmpl.env_for(&tenant).await.map_err(internal)?;
+    let ctx = ContextBuilder::from_source(
+        &state.repo,
+        &state.catalog,
+        &tenant,
+        &route.data_source,
+        &source_request,
+        route.context(),
+    )
+    .await
+    .map_err(internal)?;
+    let ctx = minijinja::context! {
+        form => fields,
+        forwarded => forwarded,
+        request => request_context(&headers),
+        ..ctx
+    };
+
+    let template_name = form
+        .success_template
+        .as_deref()
+        .unwrap_or(&route.template_name);
+    let tpl = env
+        .get_template(template_name)
+        .map_err(|err| match err.kind() {
+            TemplateErrorKind::TemplateNotFound => (StatusCode::NOT_FOUND, err.to_string()),
+            _ => internal(err),
+        })?;
     let html = tpl.render(ctx).map_err(internal)?;
-    Ok(Html(html))
+
+    let mut response = rendered(template_name, html);
+    apply_route_response(&mut response, &route)?;
+    Ok(response)
 }
 
-async fn serve_static(Path(path): Path<String>) -> Result<Response, (StatusCode, String)> {
-    let clean_path = sanitize_path(&path).ok_or_else(|| {
+/// Reads a url-encoded or multipart body of at most `limit` bytes. Repeated
+/// fields become arrays; uploaded files are ignored.
+async fn read_form(
+    request: Request,
+    limit: usize,
+) -> Result<Map<String, Value>, (StatusCode, String)> {
+    let (parts, body) = request.into_parts();
+    let bytes = axum::body::to_bytes(body, limit).await.map_err(|_| {
         (
-            StatusCode::BAD_REQUEST,
-            "invalid path segment requested".to_string(),
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("form body exceeds {limit} bytes"),
         )
     })?;
+    let content_type = parts
+        .headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
 
-    let base = PathBuf::from("static");
-    let full_path = base.join(clean_path);
+    let mut fields = Map::new();
+    if content_type.starts_with("multipart/form-data") {
+        let request = Request::from_parts(parts, Body::from(bytes));
+        let mut multipart = Multipart::from_request(request, &())
+            .await
+            .map_err(bad_request)?;
+        while let Some(field) = multipart.next_field().await.map_err(bad_request)? {
+            let Some(name) = field.name().map(str::to_string) else {
+                continue;
+            };
+            if field.file_name().is_some() {
+                continue;
+            }
+            let value = field.text().await.map_err(bad_request)?;
+            insert_form_field(&mut fields, name, value);
+        }
+    } else if content_type.starts_with("application/x-www-form-urlencoded") {
+        let pairs: Vec<(String, String)> =
+            serde_urlencoded::from_bytes(&bytes).map_err(bad_request)?;
+        for (name, value) in pairs {
+            insert_form_field(&mut fields, name, value);
+        }
+    } else {
+        return Err((
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "expected a url-encoded or multipart form body".to_string(),
+        ));
+    }
 
-    let data = fs::read(&full_path).await.map_err(|err| match err.kind() {
-        std::io::ErrorKind::NotFound => (
-            StatusCode::NOT_FOUND,
-            format!("static asset not found: {}", full_path.display()),
-        ),
-        _ => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("failed to read static asset: {err}"),
-        ),
-    })?;
+    Ok(fields)
+}
 
-    let mime = mime_for(&full_path);
-    let mut response = Response::new(Body::from(data));
-    response.headers_mut().insert(
-        CONTENT_TYPE,
-        HeaderValue::from_str(mime)
-            .unwrap_or_else(|_| HeaderValue::from_static("application/octet-stream")),
-    );
+fn insert_form_field(fields: &mut Map<String, Value>, name: String, value: String) {
+    match fields.get_mut(&name) {
+        Some(Value::Array(values)) => values.push(json!(value)),
+        Some(existing) => *existing = json!([existing.take(), value]),
+        None => {
+            fields.insert(name, json!(value));
+        }
+    }
+}
 
-    Ok(response)
+/// A rendered template as a response typed by the template's extension.
+fn rendered(template_name: &str, body: impl Into<Body>) -> Response {
+    (
+        [(CONTENT_TYPE, templates::content_type(template_name))],
+        body.into(),
+    )
+        .into_response()
 }
 
-async fn serve_favicon() -> Result<Response, (StatusCode, String)> {
-    let base = PathBuf::from("static");
-    let full_path = base.join("favicon.ico");
+/// Applies the route's configured status and headers to a successfully
+/// rendered page. Header values may reference `{{env.NAME}}`.
+fn apply_route_response(
+    response: &mut Response,
+    route: &Route,
+) -> Result<(), (StatusCode, String)> {
+    apply_route_response_with(response, route, |key| std::env::var(key).ok())
+}
 
-    let data = fs::read(&full_path).await.map_err(|err| match err.kind() {
-        std::io::ErrorKind::NotFound => (
-            StatusCode::NOT_FOUND,
-            format!("favicon not found: {}", full_path.display()),
-        ),
-        _ => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("failed to read favicon: {err}"),
-        ),
-    })?;
+/// Like [`apply_route_response`], resolving `{{env.KEY}}` in header values
+/// through `env`.
+fn apply_route_response_with(
+    response: &mut Response,
+    route: &Route,
+    env: impl Fn(&str) -> Option<String>,
+) -> Result<(), (StatusCode, String)> {
+    if let Some(status) = route.status {
+        *response.status_mut() = StatusCode::from_u16(status).map_err(internal)?;
+    }
 
-    let mut response = Response::new(Body::from(data));
+    for (name, value) in &route.headers {
+        let name = HeaderName::from_bytes(name.as_bytes()).map_err(internal)?;
+        let value =
+            HeaderValue::from_str(&render_env_placeholders_with(value, &env)).map_err(internal)?;
+        response.headers_mut().insert(name, value);
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct SitemapUrl {
+    loc: String,
+    lastmod: Option<String>,
+}
+
+/// Lists the tenant's static routes and every catalog product. A tenant
+/// `sitemap.xml` template, if present, renders `urls` (each with `loc` and
+/// `lastmod`), `base_url` and `tenant` instead of the built-in document;
+/// `.xml` templates aren't autoescaped unless the tenant configures it, so
+/// such templates should pipe values through `|e`.
+async fn sitemap(
+    headers: HeaderMap,
+    Path(tenant): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Response, (StatusCode, String)> {
+    let tenant = state
+        .tenants
+        .resolve(&headers, &tenant)
+        .await
+        .map_err(internal)?;
+    let base_url = format!("{}/@{tenant}", public_origin(&headers));
+
+    let mut urls: Vec<SitemapUrl> = state
+        .repo
+        .page_paths(&tenant)
+        .into_iter()
+        .map(|path| SitemapUrl {
+            loc: format!("{base_url}{path}"),
+            lastmod: None,
+        })
+        .collect();
+    urls.extend(
+        state
+            .catalog
+            .product_slugs(state.repo.tenant_settings(&tenant))
+            .await
+            .map_err(internal)?
+            .into_iter()
+            .map(|(slug, updated_at)| SitemapUrl {
+                loc: format!("{base_url}/products/{slug}"),
+                lastmod: updated_at,
+            }),
+    );
+
+    let env = state.tmpl.env_for(&tenant).await.map_err(internal)?;
+    let xml = match env.get_template("sitemap.xml") {
+        Ok(tpl) => tpl
+            .render(minijinja::context! { urls, base_url, tenant })
+            .map_err(internal)?,
+        Err(err) if err.kind() == TemplateErrorKind::TemplateNotFound => render_sitemap(&urls),
+        Err(err) => return Err(internal(err)),
+    };
+
+    let mut response = xml.into_response();
     response
         .headers_mut()
-        .insert(CONTENT_TYPE, HeaderValue::from_static("image/x-icon"));
-
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/xml"));
     Ok(response)
 }
 
-fn internal<E: std::fmt::Display>(e: E) -> (StatusCode, String) {
-    (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+/// Serves the tenant's `robots` setting, else its `robots.txt` template
+/// (rendered with `sitemap_url`, `base_url` and `tenant`), else a policy
+/// allowing everything that points at the tenant sitemap.
+async fn robots(
+    headers: HeaderMap,
+    Path(tenant): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Response, (StatusCode, String)> {
+    let tenant = state
+        .tenants
+        .resolve(&headers, &tenant)
+        .await
+        .map_err(internal)?;
+    let base_url = format!("{}/@{tenant}", public_origin(&headers));
+    let sitemap_url = format!("{base_url}/sitemap.xml");
+
+    let configured = state
+        .repo
+        .tenant_settings(&tenant)
+        .and_then(|settings| settings.robots.clone());
+    let body = match configured {
+        Some(body) => body,
+        None => {
+            let env = state.tmpl.env_for(&tenant).await.map_err(internal)?;
+            match env.get_template("robots.txt") {
+                Ok(tpl) => tpl
+                    .render(minijinja::context! { sitemap_url, base_url, tenant })
+                    .map_err(internal)?,
+                Err(err) if err.kind() == TemplateErrorKind::TemplateNotFound => {
+                    permissive_robots(&[sitemap_url])
+                }
+                Err(err) => return Err(internal(err)),
+            }
+        }
+    };
+
+    Ok(plain_text(body))
 }
 
-fn infer_template_name(path: &str) -> String {
-    let normalized = path.trim().trim_start_matches('/');
-    if normalized.is_empty() || normalized.ends_with('/') {
-        "index.html".to_string()
-    } else if normalized.contains('.') {
-        normalized.to_string()
-    } else {
-        format!("{normalized}.html")
+/// Root `/robots.txt`: allows everything and lists every tenant's sitemap.
+async fn robots_index(headers: HeaderMap, State(state): State<AppState>) -> Response {
+    let origin = public_origin(&headers);
+    let sitemaps: Vec<String> = state
+        .repo
+        .tenants()
+        .iter()
+        .map(|tenant| format!("{origin}/@{tenant}/sitemap.xml"))
+        .collect();
+    plain_text(permissive_robots(&sitemaps))
+}
+
+fn permissive_robots(sitemaps: &[String]) -> String {
+    let mut body = String::from("User-agent: *\nAllow: /\n");
+    if !sitemaps.is_empty() {
+        body.push('\n');
     }
+    for sitemap in sitemaps {
+        body.push_str(&format!("Sitemap: {sitemap}\n"));
+    }
+    body
 }
 
-fn sanitize_path(path: &str) -> Option<PathBuf> {
-    let mut clean = PathBuf::new();
-    for component in StdPath::new(path).components() {
+fn plain_text(body: String) -> Response {
+    let mut response = body.into_response();
+    response.headers_mut().insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static("text/plain; charset=utf-8"),
+    );
+    response
+}
+
+fn render_sitemap(urls: &[SitemapUrl]) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    );
+    for url in urls {
+        xml.push_str("  <url>\n    <loc>");
+        xml.push_str(&xml_escape(&url.loc));
+        xml.push_str("</loc>\n");
+        if let Some(lastmod) = &url.lastmod {
+            xml.push_str("    <lastmod>");
+            xml.push_str(&xml_escape(lastmod));
+            xml.push_str("</lastmod>\n");
+        }
+        xml.push_str("  </url>\n");
+    }
+    xml.push_str("</urlset>\n");
+    xml
+}
+
+fn xml_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+/// Scheme and host used for absolute URLs: `PUBLIC_BASE_URL` when set,
+/// otherwise the request's `Host` (and `X-Forwarded-Proto`, default http).
+fn public_origin(headers: &HeaderMap) -> String {
+    if let Ok(base) = std::env::var("PUBLIC_BASE_URL")
+        && !base.trim().is_empty()
+    {
+        return base.trim().trim_end_matches('/').to_string();
+    }
+
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+    };
+    let scheme = header("x-forwarded-proto").unwrap_or("http");
+    let host = header("host").unwrap_or("localhost");
+    format!("{scheme}://{host}")
+}
+
+/// Returns 200 when every dependency check passes, otherwise 503. The body
+/// lists each check as `"ok"` or its error, e.g.
+/// `{ "status": "unavailable", "checks": { "config": "ok", "templates": "..." } }`,
+/// plus `env_cache_entries`, the number of tenant template environments
+/// currently cached.
+async fn readiness(State(state): State<AppState>) -> Response {
+    let checks = readiness_checks(&state).await;
+    let ready = checks.values().all(|check| check.is_ok());
+    state.tmpl.sync_cache().await;
+    let body = json!({
+        "status": if ready { "ok" } else { "unavailable" },
+        "checks": checks
+            .into_iter()
+            .map(|(name, check)| (name.to_string(), json!(check.err().unwrap_or_else(|| "ok".to_string()))))
+            .collect::<Map<String, Value>>(),
+        "env_cache_entries": state.tmpl.cached_environments(),
+    });
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(body)).into_response()
+}
+
+async fn readiness_checks(state: &AppState) -> BTreeMap<&'static str, Result<(), String>> {
+    let mut checks = BTreeMap::new();
+
+    let config = if state.repo.has_tenants() {
+        Ok(())
+    } else {
+        Err("routes config defines no tenants".to_string())
+    };
+    checks.insert("config", config);
+
+    let template_dir = state.tmpl.template_dir();
+    let templates = fs::read_dir(template_dir)
+        .await
+        .map(|_| ())
+        .map_err(|err| format!("reading {}: {err}", template_dir.display()));
+    checks.insert("templates", templates);
+
+    checks
+}
+
+/// The tenant's own `catalog_path` catalog, else the `CATALOG_PATH` one.
+async fn tenant_catalog(
+    state: &AppState,
+    tenant: &str,
+) -> anyhow::Result<Option<Arc<SearchEngine>>> {
+    state
+        .catalog
+        .for_tenant(state.repo.tenant_settings(tenant))
+        .await
+}
+
+/// Template rendered for `?q=` searches with results filled in server-side.
+const SEARCH_TEMPLATE: &str = "pages/search.html";
+
+/// The `results` variable of a server-rendered search page: one page of
+/// ranked products as `{ query, items, page, per_page, total, total_pages }`,
+/// where `items` are the wasm module's `search()` results and the page comes
+/// from `?page=` / `?per_page=`. `None` without a query or a catalog, leaving
+/// the page to the client.
+async fn search_results(
+    state: &AppState,
+    tenant: &str,
+    params: &Map<String, Value>,
+) -> anyhow::Result<Option<Value>> {
+    let query = data::param_str(params, "q")
+        .map(str::trim)
+        .unwrap_or_default();
+    if query.is_empty() {
+        return Ok(None);
+    }
+    let Some(search) = tenant_catalog(state, tenant).await? else {
+        tracing::warn!("no catalog configured for tenant '{tenant}'; skipping server-side search");
+        return Ok(None);
+    };
+
+    let items = search
+        .search_results(query)
+        .into_iter()
+        .map(serde_json::to_value)
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut results = data::paginate(params, items);
+    results["query"] = json!(query);
+    Ok(Some(results))
+}
+
+#[derive(Deserialize)]
+struct SearchParams {
+    #[serde(default)]
+    q: String,
+    #[serde(default)]
+    limit: Option<usize>,
+}
+
+/// Server-side catalog search for crawlers and clients without JS. Returns
+/// the same result array as the wasm module's `search()`.
+async fn api_search(
+    headers: HeaderMap,
+    Path(tenant): Path<String>,
+    Query(params): Query<SearchParams>,
+    State(state): State<AppState>,
+) -> Result<Response, (StatusCode, String)> {
+    let tenant = state
+        .tenants
+        .resolve(&headers, &tenant)
+        .await
+        .map_err(internal)?;
+    let search = tenant_catalog(&state, &tenant)
+        .await
+        .map_err(internal)?
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                format!("no catalog configured for tenant '{tenant}'"),
+            )
+        })?;
+
+    let mut results = search.search_results(&params.q);
+    if let Some(limit) = params.limit {
+        results.truncate(limit);
+    }
+    Ok(Json(results).into_response())
+}
+
+async fn lint_templates(
+    headers: HeaderMap,
+    Path(tenant): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Response, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    let tenant = state
+        .tenants
+        .resolve(&headers, &tenant)
+        .await
+        .map_err(internal)?;
+    let report = state.tmpl.lint(&tenant).await.map_err(internal)?;
+    Ok(Json(report).into_response())
+}
+
+async fn list_routes(
+    headers: HeaderMap,
+    Path(tenant): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Response, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    let tenant = state
+        .tenants
+        .resolve(&headers, &tenant)
+        .await
+        .map_err(internal)?;
+    Ok(Json(state.repo.route_summaries(&tenant)).into_response())
+}
+
+#[derive(Deserialize)]
+struct PreviewRequest {
+    template: String,
+    #[serde(default)]
+    context: Map<String, Value>,
+}
+
+/// Renders one of the tenant's templates with an inline context instead of a
+/// route's data source, so templates can be previewed before they're wired
+/// up. Names resolve like `?template=`: only the tenant's own and `_shared`
+/// templates. Render errors come back as `422` with the details.
+async fn preview_template(
+    headers: HeaderMap,
+    Path(tenant): Path<String>,
+    State(state): State<AppState>,
+    Json(preview): Json<PreviewRequest>,
+) -> Result<Response, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    let tenant = state
+        .tenants
+        .resolve(&headers, &tenant)
+        .await
+        .map_err(internal)?;
+    let env = state.tmpl.env_for(&tenant).await.map_err(internal)?;
+    let tpl = env
+        .get_template(&preview.template)
+        .map_err(|err| match err.kind() {
+            TemplateErrorKind::TemplateNotFound => (StatusCode::NOT_FOUND, err.to_string()),
+            _ => internal(err),
+        })?;
+    let ctx = minijinja::context! {
+        request => request_context(&headers),
+        ..minijinja::Value::from_serialize(&preview.context)
+    };
+    let html = tpl
+        .render(ctx)
+        .map_err(|err| (StatusCode::UNPROCESSABLE_ENTITY, format!("{err:#}")))?;
+    Ok(rendered(&preview.template, html))
+}
+
+/// Admin endpoints are disabled unless `ADMIN_TOKEN` is set, and then require
+/// a matching `X-Admin-Token` header.
+fn require_admin(state: &AppState, headers: &HeaderMap) -> Result<(), (StatusCode, String)> {
+    let Some(expected) = state.admin_token.as_deref() else {
+        return Err((StatusCode::NOT_FOUND, "not found".to_string()));
+    };
+
+    let provided = headers
+        .get("x-admin-token")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    // Constant-time, so response timing doesn't reveal how much of a guess
+    // matched.
+    if !bool::from(provided.as_bytes().ct_eq(expected.as_bytes())) {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            "invalid or missing admin token".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Bare `/static/` paths serve the host's (or the default) tenant's assets,
+/// like bare page paths.
+async fn serve_static(
+    headers: HeaderMap,
+    Path(path): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Response, (StatusCode, String)> {
+    let tenant = state
+        .tenants
+        .resolve_bare(&headers)
+        .await
+        .map_err(internal)?;
+    read_static(StdPath::new("static"), tenant.as_deref(), &path).await
+}
+
+#[derive(Deserialize)]
+struct TenantAsset {
+    tenant: String,
+    path: String,
+}
+
+async fn serve_tenant_static(
+    headers: HeaderMap,
+    Path(TenantAsset { tenant, path }): Path<TenantAsset>,
+    State(state): State<AppState>,
+) -> Result<Response, (StatusCode, String)> {
+    let tenant = state
+        .tenants
+        .resolve(&headers, &tenant)
+        .await
+        .map_err(internal)?;
+    read_static(StdPath::new("static"), Some(&tenant), &path).await
+}
+
+/// Serves `path` from the first of the tenant's [`templates::static_dirs`]
+/// that has it, so tenants can override shared assets such as `logo.png`.
+async fn read_static(
+    root: &StdPath,
+    tenant: Option<&str>,
+    path: &str,
+) -> Result<Response, (StatusCode, String)> {
+    let clean_path = sanitize_path(path).ok_or_else(|| {
+        (
+            StatusCode::BAD_REQUEST,
+            "invalid path segment requested".to_string(),
+        )
+    })?;
+
+    for dir in templates::static_dirs(root, tenant) {
+        let full_path = dir.join(&clean_path);
+        match fs::read(&full_path).await {
+            Ok(data) => return Ok(static_response(&full_path, data)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(err) => {
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("failed to read static asset: {err}"),
+                ));
+            }
+        }
+    }
+
+    Err((
+        StatusCode::NOT_FOUND,
+        format!("static asset not found: {}", clean_path.display()),
+    ))
+}
+
+fn static_response(full_path: &StdPath, data: Vec<u8>) -> Response {
+    let mime = mime_for(full_path);
+    let mut response = Response::new(Body::from(data));
+    response.headers_mut().insert(
+        CONTENT_TYPE,
+        HeaderValue::from_str(mime)
+            .unwrap_or_else(|_| HeaderValue::from_static("application/octet-stream")),
+    );
+    response
+}
+
+/// Favicons rarely change, and revalidation is cheap when they do.
+const FAVICON_MAX_AGE_SECS: u64 = 30 * 24 * 60 * 60;
+
+async fn serve_favicon(headers: HeaderMap) -> Result<Response, (StatusCode, String)> {
+    read_favicon(StdPath::new("static"), &headers).await
+}
+
+/// Serves `root/favicon.ico` with long-lived caching and conditional request
+/// support.
+async fn read_favicon(
+    root: &StdPath,
+    headers: &HeaderMap,
+) -> Result<Response, (StatusCode, String)> {
+    let full_path = root.join("favicon.ico");
+
+    let data = fs::read(&full_path).await.map_err(|err| match err.kind() {
+        std::io::ErrorKind::NotFound => (
+            StatusCode::NOT_FOUND,
+            format!("favicon not found: {}", full_path.display()),
+        ),
+        _ => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("failed to read favicon: {err}"),
+        ),
+    })?;
+    let modified = fs::metadata(&full_path)
+        .await
+        .and_then(|meta| meta.modified())
+        .ok();
+    let validators = FileValidators::new(&data, modified);
+
+    if validators.fresh(headers) {
+        let mut response = StatusCode::NOT_MODIFIED.into_response();
+        validators.apply(&mut response, FAVICON_MAX_AGE_SECS);
+        return Ok(response);
+    }
+
+    let mut response = Response::new(Body::from(data));
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("image/x-icon"));
+    validators.apply(&mut response, FAVICON_MAX_AGE_SECS);
+
+    Ok(response)
+}
+
+/// `ETag` and `Last-Modified` of a file served from disk.
+struct FileValidators {
+    etag: String,
+    last_modified: Option<DateTime<Utc>>,
+}
+
+impl FileValidators {
+    fn new(data: &[u8], modified: Option<SystemTime>) -> Self {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        hasher.write(data);
+        Self {
+            etag: format!("\"{:016x}\"", hasher.finish()),
+            last_modified: modified.map(DateTime::<Utc>::from),
+        }
+    }
+
+    /// Whether the client's copy is current. `If-None-Match` takes
+    /// precedence, so `If-Modified-Since` only counts without it.
+    fn fresh(&self, headers: &HeaderMap) -> bool {
+        if headers.contains_key(IF_NONE_MATCH) {
+            return if_none_match(headers, &self.etag);
+        }
+        let Some(modified) = self.last_modified else {
+            return false;
+        };
+        headers
+            .get(IF_MODIFIED_SINCE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| DateTime::parse_from_rfc2822(value).ok())
+            // HTTP dates have whole-second precision.
+            .is_some_and(|since| modified.timestamp() <= since.timestamp())
+    }
+
+    fn apply(&self, response: &mut Response, max_age_secs: u64) {
+        let headers = response.headers_mut();
+        if let Ok(etag) = HeaderValue::from_str(&self.etag) {
+            headers.insert(ETAG, etag);
+        }
+        if let Some(modified) = self.last_modified
+            && let Ok(value) =
+                HeaderValue::from_str(&modified.format("%a, %d %b %Y %H:%M:%S GMT").to_string())
+        {
+            headers.insert(LAST_MODIFIED, value);
+        }
+        if let Ok(value) = HeaderValue::from_str(&format!("public, max-age={max_age_secs}")) {
+            headers.insert(CACHE_CONTROL, value);
+        }
+    }
+}
+
+fn internal<E: std::fmt::Display>(e: E) -> (StatusCode, String) {
+    (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+}
+
+fn bad_request<E: std::fmt::Display>(e: E) -> (StatusCode, String) {
+    (StatusCode::BAD_REQUEST, e.to_string())
+}
+
+fn infer_template_name(path: &str) -> String {
+    let normalized = path.trim().trim_start_matches('/');
+    if normalized.is_empty() || normalized.ends_with('/') {
+        "index.html".to_string()
+    } else if normalized.contains('.') {
+        normalized.to_string()
+    } else {
+        format!("{normalized}.html")
+    }
+}
+
+fn sanitize_path(path: &str) -> Option<PathBuf> {
+    let mut clean = PathBuf::new();
+    for component in StdPath::new(path).components() {
         match component {
             Component::Normal(part) => clean.push(part),
             Component::CurDir => {}
@@ -251,3 +1533,1289 @@ fn mime_for(path: &StdPath) -> &'static str {
         None => "application/octet-stream",
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::fixture::{self, Fixture, write_catalog};
+    use axum::response::Html;
+
+    #[tokio::test]
+    async fn configured_status_and_headers_apply_to_response() {
+        let fixture = Fixture::new(
+            r#"{
+                "tenants": ["shop"],
+                "routes": {
+                    "shop": [{
+                        "path": "/",
+                        "template_name": "maintenance.html",
+                        "status": 503,
+                        "headers": { "Retry-After": "{{env.MOBI_TEST_RETRY_AFTER}}" }
+                    }]
+                }
+            }"#,
+            &[],
+        )
+        .await
+        .unwrap();
+        let route = fixture
+            .state
+            .repo
+            .find_route("shop", "/")
+            .await
+            .unwrap()
+            .unwrap();
+
+        let mut response = Html("down for maintenance").into_response();
+        apply_route_response_with(&mut response, &route, |key| {
+            (key == "MOBI_TEST_RETRY_AFTER").then(|| "120".to_string())
+        })
+        .unwrap();
+
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()["retry-after"], "120");
+    }
+
+    #[test]
+    fn repeated_query_params_become_arrays() {
+        let uri: axum::http::Uri = "/catalog?color=red&q=sofa&color=blue".parse().unwrap();
+        let Query(query) = Query::<QueryParams>::try_from_uri(&uri).unwrap();
+
+        assert_eq!(
+            Value::Object(query.params),
+            json!({ "color": ["red", "blue"], "q": "sofa" })
+        );
+
+        let uri: axum::http::Uri = "/search?q=chair&q=sofa".parse().unwrap();
+        let Query(query) = Query::<QueryParams>::try_from_uri(&uri).unwrap();
+        assert_eq!(query.params["q"], json!(["chair", "sofa"]));
+        assert_eq!(data::param_str(&query.params, "q"), Some("sofa"));
+    }
+
+    #[tokio::test]
+    async fn route_variants_follow_the_configured_query_param() {
+        let fixture = Fixture::new(
+            r#"{
+                "tenants": ["shop"],
+                "routes": {
+                    "shop": [{
+                        "path": "/catalog",
+                        "template_name": "catalog-grid.html",
+                        "data_source": { "provider": "static", "payload": { "per_row": 4 } },
+                        "variant_param": "layout",
+                        "variants": {
+                            "list": { "template_name": "catalog-list.html" },
+                            "compact": {
+                                "data_source": { "provider": "static", "payload": { "per_row": 6 } }
+                            }
+                        }
+                    }]
+                }
+            }"#,
+            &[],
+        )
+        .await
+        .unwrap();
+        let route = fixture
+            .state
+            .repo
+            .find_route("shop", "/catalog")
+            .await
+            .unwrap()
+            .unwrap();
+        let query = |value: &str| {
+            let mut params = Map::new();
+            params.insert("layout".into(), json!(value));
+            params
+        };
+
+        let list = route.clone().with_variant(&query("list"));
+        assert_eq!(list.template_name, "catalog-list.html");
+        assert_eq!(list.data_source["payload"]["per_row"], 4);
+
+        let compact = route.clone().with_variant(&query("compact"));
+        assert_eq!(compact.template_name, "catalog-grid.html");
+        assert_eq!(compact.data_source["payload"]["per_row"], 6);
+
+        let unknown = route.clone().with_variant(&query("masonry"));
+        assert_eq!(unknown.template_name, "catalog-grid.html");
+        let default = route.with_variant(&Map::new());
+        assert_eq!(default.template_name, "catalog-grid.html");
+    }
+
+    #[tokio::test]
+    async fn route_name_and_meta_reach_templates() {
+        let page = "{{ page.route.name }}|{{ page.route.meta.body_class }}";
+        let fixture = Fixture::new(
+            r#"{
+                "tenants": ["shop"],
+                "routes": {
+                    "shop": [{
+                        "path": "/catalog",
+                        "name": "catalog",
+                        "template_name": "catalog.html",
+                        "meta": { "body_class": "wide" }
+                    }]
+                }
+            }"#,
+            &[("shop/catalog.html", page), ("shop/about.html", page)],
+        )
+        .await
+        .unwrap();
+        let state = fixture.state.clone();
+        let render =
+            |path: &str| fixture::render(&state, "shop", path, HeaderMap::new(), json!({}));
+        let body = |response: Response| async {
+            axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap()
+        };
+
+        let matched = render("catalog").await.unwrap();
+        assert_eq!(body(matched).await, "catalog|wide");
+        let inferred = render("about").await.unwrap();
+        assert_eq!(body(inferred).await, "about|");
+    }
+
+    #[tokio::test]
+    async fn content_type_follows_the_template_extension() {
+        let fixture = Fixture::new(
+            r#"{
+                "tenants": ["shop"],
+                "routes": {
+                    "shop": [{ "path": "/feed", "template_name": "feed.xml" }]
+                }
+            }"#,
+            &[
+                ("shop/feed.xml", "<feed/>"),
+                ("shop/about.html", "<p>about</p>"),
+            ],
+        )
+        .await
+        .unwrap();
+        let state = fixture.state.clone();
+        let content_type = |path: &str| {
+            let state = state.clone();
+            let path = path.to_string();
+            async move {
+                let response = fixture::render(&state, "shop", &path, HeaderMap::new(), json!({}))
+                    .await
+                    .unwrap();
+                response.headers()[CONTENT_TYPE]
+                    .to_str()
+                    .unwrap()
+                    .to_string()
+            }
+        };
+
+        assert_eq!(content_type("feed").await, "application/xml");
+        assert_eq!(content_type("about").await, "text/html; charset=utf-8");
+        assert_eq!(
+            templates::content_type("robots.txt"),
+            "text/plain; charset=utf-8"
+        );
+        assert_eq!(templates::content_type("data.json"), "application/json");
+    }
+
+    #[tokio::test]
+    async fn deterministic_pages_answer_conditional_requests() {
+        let fixture = Fixture::new(
+            r#"{
+                "tenants": ["shop"],
+                "routes": {
+                    "shop": [
+                        {
+                            "path": "/about",
+                            "template_name": "about.html",
+                            "data_source": { "provider": "static", "payload": { "title": "About" } }
+                        },
+                        {
+                            "path": "/team",
+                            "template_name": "about.html",
+                            "data_source": { "provider": "static", "payload": { "title": "Team" } },
+                            "etag": false
+                        }
+                    ]
+                }
+            }"#,
+            &[("shop/about.html", "{{ title }}")],
+        )
+        .await
+        .unwrap();
+        let state = fixture.state.clone();
+        let render = |path: &str, if_none_match: Option<&HeaderValue>| {
+            let mut headers = HeaderMap::new();
+            if let Some(tag) = if_none_match {
+                headers.insert(IF_NONE_MATCH, tag.clone());
+            }
+            fixture::render(&state, "shop", path, headers, json!({}))
+        };
+
+        let first = render("about", None).await.unwrap();
+        assert_eq!(first.status(), StatusCode::OK);
+        let etag = first.headers()[ETAG].clone();
+        assert!(etag.to_str().unwrap().starts_with("W/\""));
+
+        let cached = render("about", Some(&etag)).await.unwrap();
+        assert_eq!(cached.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(cached.headers()[ETAG], etag);
+
+        let stale = HeaderValue::from_static("W/\"0\"");
+        let changed = render("about", Some(&stale)).await.unwrap();
+        assert_eq!(changed.status(), StatusCode::OK);
+
+        let opted_out = render("team", Some(&etag)).await.unwrap();
+        assert_eq!(opted_out.status(), StatusCode::OK);
+        assert!(opted_out.headers().get(ETAG).is_none());
+
+        assert!(!data::is_deterministic(&json!({
+            "provider": "static",
+            "payload": { "product": { "provider": "http", "url": "https://example.com" } }
+        })));
+    }
+
+    #[tokio::test]
+    async fn allowed_cookies_reach_templates_and_templates_set_cookies() {
+        let fixture = Fixture::new(
+            r#"{
+                "tenants": ["shop"],
+                "settings": { "shop": { "cookies": ["recent"] } },
+                "routes": { "shop": [{ "path": "/product", "template_name": "product.html" }] }
+            }"#,
+            &[(
+                "shop/product.html",
+                "{{ cookies.recent }}|{{ 'session' in cookies }}\
+                 {{ set_cookie('recent', 'chair.table', max_age=3600) }}",
+            )],
+        )
+        .await
+        .unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            COOKIE,
+            HeaderValue::from_static("recent=chair; session=secret"),
+        );
+        let response = fixture::render(&fixture.state, "shop", "product", headers, json!({}))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            response.headers()[SET_COOKIE],
+            "recent=chair.table; Path=/; SameSite=Lax; Max-Age=3600; HttpOnly"
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body, "chair|false");
+    }
+
+    #[tokio::test]
+    async fn product_pages_prefer_the_product_route_over_a_catch_all() {
+        let fixture = Fixture::new(
+            r#"{
+                "tenants": ["shop"],
+                "routes": { "shop": [
+                    { "path": "/*", "template_name": "catch.html" },
+                    { "path": "/product", "template_name": "product.html" }
+                ] }
+            }"#,
+            &[
+                ("shop/catch.html", "catch"),
+                ("shop/product.html", "product"),
+            ],
+        )
+        .await
+        .unwrap();
+        let state = fixture.state.clone();
+        let render =
+            |path: &str| fixture::render(&state, "shop", path, HeaderMap::new(), json!({}));
+        for (path, expected) in [
+            ("products/oak-table", "product"),
+            ("product", "product"),
+            ("about", "catch"),
+        ] {
+            let response = render(path).await.unwrap();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert_eq!(body, expected, "{path}");
+        }
+    }
+
+    #[tokio::test]
+    async fn search_endpoint_uses_the_tenant_catalog_and_reloads_it() {
+        let catalogs = tempfile::tempdir().unwrap();
+        let blob = catalogs.path().join("catalog.bin");
+        write_catalog(
+            &blob,
+            &[
+                ("0", "Oak Table", "oak-table"),
+                ("1", "Linen Sofa", "linen-sofa"),
+            ],
+        )
+        .unwrap();
+        let fixture = Fixture::new(
+            &format!(
+                r#"{{ "tenants": ["shop", "blog"], "settings": {{ "shop": {{ "catalog_path": {:?} }} }} }}"#,
+                blob.to_str().unwrap()
+            ),
+            &[],
+        )
+        .await
+        .unwrap();
+        let state = fixture.state.clone();
+        let search = |tenant: &str, q: &str| {
+            api_search(
+                HeaderMap::new(),
+                Path(tenant.to_string()),
+                Query(SearchParams {
+                    q: q.to_string(),
+                    limit: None,
+                }),
+                State(state.clone()),
+            )
+        };
+        let names = |response: Response| async {
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let results: Vec<Value> = serde_json::from_slice(&body).unwrap();
+            results
+                .iter()
+                .map(|result| result["name"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let response = search("shop", "sofa").await.unwrap();
+        assert_eq!(names(response).await, ["Linen Sofa"]);
+
+        write_catalog(
+            &blob,
+            &[
+                ("0", "Oak Table", "oak-table"),
+                ("1", "Linen Sofa", "linen-sofa"),
+                ("2", "Velvet Sofa Bed", "velvet-sofa-bed"),
+            ],
+        )
+        .unwrap();
+        let response = search("shop", "sofa").await.unwrap();
+        assert_eq!(names(response).await.len(), 2);
+
+        // Requests racing after a change share a single load.
+        write_catalog(
+            &blob,
+            &[
+                ("0", "Oak Table", "oak-table"),
+                ("1", "Linen Sofa", "linen-sofa"),
+                ("2", "Velvet Sofa Bed", "velvet-sofa-bed"),
+                ("3", "Sofa Table", "sofa-table"),
+            ],
+        )
+        .unwrap();
+        let (first, second) =
+            tokio::join!(state.catalog.for_path(&blob), state.catalog.for_path(&blob));
+        let (first, second) = (first.unwrap(), second.unwrap());
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(first.catalog().items.len(), 4);
+
+        let missing = search("blog", "sofa").await.unwrap_err();
+        assert_eq!(missing.0, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn the_default_catalog_reloads_when_its_file_changes() {
+        let catalogs = tempfile::tempdir().unwrap();
+        let blob = catalogs.path().join("catalog.bin");
+        write_catalog(&blob, &[("0", "Linen Sofa", "linen-sofa")]).unwrap();
+        let fixture = Fixture::new(r#"{ "tenants": ["shop"] }"#, &[])
+            .await
+            .unwrap();
+        let state = AppState {
+            catalog: crate::catalog::CatalogStore::from_path(blob.clone())
+                .await
+                .unwrap(),
+            ..fixture.state.clone()
+        };
+        let count = || async {
+            let response = api_search(
+                HeaderMap::new(),
+                Path("shop".to_string()),
+                Query(SearchParams {
+                    q: "sofa".to_string(),
+                    limit: None,
+                }),
+                State(state.clone()),
+            )
+            .await
+            .unwrap();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            serde_json::from_slice::<Vec<Value>>(&body).unwrap().len()
+        };
+
+        assert_eq!(count().await, 1);
+        write_catalog(
+            &blob,
+            &[
+                ("0", "Linen Sofa", "linen-sofa"),
+                ("1", "Velvet Sofa Bed", "velvet-sofa-bed"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(count().await, 2);
+    }
+
+    #[tokio::test]
+    async fn search_pages_render_paginated_results_server_side() {
+        let catalogs = tempfile::tempdir().unwrap();
+        let blob = catalogs.path().join("catalog.bin");
+        write_catalog(
+            &blob,
+            &[
+                ("0", "Linen Sofa", "linen-sofa"),
+                ("1", "Velvet Sofa", "velvet-sofa"),
+                ("2", "Corner Sofa", "corner-sofa"),
+                ("3", "Oak Table", "oak-table"),
+            ],
+        )
+        .unwrap();
+        let fixture = Fixture::new(
+            &format!(
+                r#"{{ "tenants": ["shop"], "settings": {{ "shop": {{ "catalog_path": {:?} }} }} }}"#,
+                blob.to_str().unwrap()
+            ),
+            &[(
+                "shop/pages/search.html",
+                "{% if results is defined %}{{ results.query }}:{{ results.page }}/{{ results.total_pages }}:\
+                 {% for item in results['items'] %}{{ item.name }},{% endfor %}{% else %}client{% endif %}",
+            )],
+        )
+        .await
+        .unwrap();
+        let state = fixture.state.clone();
+        let render =
+            |params: Value| fixture::render(&state, "shop", "search", HeaderMap::new(), params);
+        let body = |response: Response| async {
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            String::from_utf8(bytes.to_vec()).unwrap()
+        };
+
+        let first = body(
+            render(json!({ "q": "sofa", "per_page": "2" }))
+                .await
+                .unwrap(),
+        )
+        .await;
+        assert!(first.starts_with("sofa:1/2:"), "{first}");
+        assert_eq!(first.matches("Sofa,").count(), 2);
+        let second = body(
+            render(json!({ "q": "sofa", "page": "2", "per_page": "2" }))
+                .await
+                .unwrap(),
+        )
+        .await;
+        assert!(second.starts_with("sofa:2/2:"), "{second}");
+        assert_eq!(second.matches("Sofa,").count(), 1);
+
+        // A repeated `q` searches its last value.
+        let repeated = body(
+            render(json!({ "q": ["table", "sofa"], "per_page": "2" }))
+                .await
+                .unwrap(),
+        )
+        .await;
+        assert!(repeated.starts_with("sofa:1/2:"), "{repeated}");
+
+        let empty = body(render(json!({})).await.unwrap()).await;
+        assert_eq!(empty, "client");
+    }
+
+    #[tokio::test]
+    async fn store_search_page_links_only_to_existing_pages() {
+        let catalogs = tempfile::tempdir().unwrap();
+        let blob = catalogs.path().join("catalog.bin");
+        write_catalog(
+            &blob,
+            &[
+                ("1", "Sofa 1", "sofa-1"),
+                ("2", "Sofa 2", "sofa-2"),
+                ("3", "Sofa 3", "sofa-3"),
+                ("4", "Sofa 4", "sofa-4"),
+                ("5", "Sofa 5", "sofa-5"),
+            ],
+        )
+        .unwrap();
+        let page: Value =
+            serde_json::from_str(include_str!("../../mock-data/store/pages/search.json")).unwrap();
+        let routes = json!({
+            "tenants": ["store"],
+            "settings": { "store": { "catalog_path": blob } },
+            "routes": { "store": [{
+                "path": "/search",
+                "template_name": "pages/search.html",
+                "data_source": { "provider": "static", "payload": page }
+            }] }
+        });
+        let fixture = Fixture::new(&routes.to_string(), &[]).await.unwrap();
+        let state = AppState {
+            tmpl: crate::templates::TemplateService::new(
+                concat!(env!("CARGO_MANIFEST_DIR"), "/templates"),
+                Default::default(),
+            ),
+            ..fixture.state.clone()
+        };
+        let render = |page: &'static str| {
+            let state = state.clone();
+            async move {
+                let params = json!({ "q": "sofa", "page": page, "per_page": "2" });
+                let response = fixture::render(&state, "store", "search", HeaderMap::new(), params)
+                    .await
+                    .unwrap();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                String::from_utf8(body.to_vec()).unwrap()
+            }
+        };
+
+        let first = render("1").await;
+        assert!(first.contains("Page 1 of 3"), "{first}");
+        assert!(!first.contains(">Previous</button>"), "{first}");
+        assert!(first.contains(">Next</button>"), "{first}");
+        assert!(!first.contains(r#"name="page" value="0""#), "{first}");
+
+        let last = render("3").await;
+        assert!(last.contains("Page 3 of 3"), "{last}");
+        assert!(last.contains(">Previous</button>"), "{last}");
+        assert!(!last.contains(">Next</button>"), "{last}");
+        assert!(!last.contains(r#"name="page" value="4""#), "{last}");
+    }
+
+    #[tokio::test]
+    async fn readiness_reports_failing_checks() {
+        let fixture = Fixture::new(r#"{ "tenants": ["shop"] }"#, &[])
+            .await
+            .unwrap();
+
+        let ready = readiness(State(fixture.state.clone())).await;
+        assert_eq!(ready.status(), StatusCode::OK);
+        fixture.state.tmpl.env_for("shop").await.unwrap();
+        let body = axum::body::to_bytes(
+            readiness(State(fixture.state.clone())).await.into_body(),
+            usize::MAX,
+        )
+        .await
+        .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["env_cache_entries"], 1);
+
+        let missing = AppState {
+            tmpl: crate::templates::TemplateService::new(
+                fixture.path().join("missing"),
+                Default::default(),
+            ),
+            ..fixture.state.clone()
+        };
+        let response = readiness(State(missing)).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["status"], "unavailable");
+        assert_eq!(body["checks"]["config"], "ok");
+        assert!(
+            body["checks"]["templates"]
+                .as_str()
+                .unwrap()
+                .starts_with("reading")
+        );
+    }
+
+    #[test]
+    fn default_robots_allows_all_and_lists_sitemaps() {
+        assert_eq!(
+            permissive_robots(&["https://shop.example/@shop/sitemap.xml".into()]),
+            "User-agent: *\nAllow: /\n\nSitemap: https://shop.example/@shop/sitemap.xml\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn sitemaps_and_catalog_sources_use_the_tenant_catalog() {
+        let catalogs = tempfile::tempdir().unwrap();
+        let blob = catalogs.path().join("catalog.bin");
+        write_catalog(&blob, &[("1", "Oak Table", "oak-table")]).unwrap();
+        let fixture = Fixture::new(
+            &format!(
+                r#"{{ "tenants": ["shop", "blog"], "settings": {{ "shop": {{ "catalog_path": {:?} }} }} }}"#,
+                blob.to_str().unwrap()
+            ),
+            &[],
+        )
+        .await
+        .unwrap();
+        let state = fixture.state.clone();
+        let sitemap_xml = |tenant: &str| {
+            let state = state.clone();
+            let tenant = tenant.to_string();
+            async move {
+                let response = sitemap(HeaderMap::new(), Path(tenant), State(state))
+                    .await
+                    .unwrap();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                String::from_utf8(body.to_vec()).unwrap()
+            }
+        };
+        assert!(
+            sitemap_xml("shop")
+                .await
+                .contains("/@shop/products/oak-table")
+        );
+        assert!(!sitemap_xml("blog").await.contains("/products/"));
+
+        let source = json!({ "provider": "catalog", "query": "oak" });
+        let results = data::ContextBuilder::from_source(
+            &state.repo,
+            &state.catalog,
+            "shop",
+            &json!({ "results": source }),
+            &SourceRequest::default(),
+            json!({}),
+        )
+        .await
+        .unwrap();
+        let results = serde_json::to_value(results.get_attr("results").unwrap()).unwrap();
+        assert_eq!(results[0]["name"], "Oak Table");
+    }
+
+    #[test]
+    fn sitemap_escapes_locations() {
+        let xml = render_sitemap(&[
+            SitemapUrl {
+                loc: "https://shop.example/@shop/".into(),
+                lastmod: None,
+            },
+            SitemapUrl {
+                loc: "https://shop.example/@shop/products/a&b".into(),
+                lastmod: Some("2024-05-01T12:00:00Z".into()),
+            },
+        ]);
+
+        assert!(xml.contains("<loc>https://shop.example/@shop/</loc>\n  </url>"));
+        assert!(xml.contains("<loc>https://shop.example/@shop/products/a&amp;b</loc>"));
+        assert!(xml.contains("<lastmod>2024-05-01T12:00:00Z</lastmod>"));
+    }
+
+    #[tokio::test]
+    async fn form_posts_render_success_template_with_fields() {
+        let fixture = Fixture::new(
+            r#"{
+                "tenants": ["shop"],
+                "routes": {
+                    "shop": [
+                        {
+                            "path": "/contact",
+                            "template_name": "contact.html",
+                            "form": { "success_template": "thanks.html", "max_body_bytes": 64 }
+                        },
+                        { "path": "/about", "template_name": "about.html" }
+                    ]
+                }
+            }"#,
+            &[(
+                "shop/thanks.html",
+                "Thanks {{ form.name }} ({{ form.topics | join(',') }})",
+            )],
+        )
+        .await
+        .unwrap();
+        let state = fixture.state.clone();
+        let post = |path: &str, body: &str| {
+            let request = Request::builder()
+                .method("POST")
+                .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+                .body(Body::from(body.to_string()))
+                .unwrap();
+            submit_form(
+                HeaderMap::new(),
+                Query(QueryParams::default()),
+                Path(TenantPath {
+                    tenant: Some("shop".into()),
+                    path: Some(path.into()),
+                }),
+                State(state.clone()),
+                request,
+            )
+        };
+
+        let response = post("contact", "name=Ana&topics=sofas&topics=tables")
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body, "Thanks Ana (sofas,tables)");
+
+        let too_large = post("contact", &format!("name={}", "a".repeat(100))).await;
+        assert_eq!(too_large.unwrap_err().0, StatusCode::PAYLOAD_TOO_LARGE);
+
+        let no_form = post("about", "name=Ana").await;
+        assert_eq!(no_form.unwrap_err().0, StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[tokio::test]
+    async fn static_assets_resolve_per_tenant_before_shared_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for (file, body) in [
+            ("shop/logo.png", "shop logo"),
+            ("_shared/logo.png", "shared logo"),
+            ("_shared/app.css", "shared css"),
+            ("favicon.ico", "flat icon"),
+        ] {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, body).unwrap();
+        }
+        let body = |response: Result<Response, (StatusCode, String)>| async {
+            let body = axum::body::to_bytes(response.unwrap().into_body(), usize::MAX)
+                .await
+                .unwrap();
+            String::from_utf8(body.to_vec()).unwrap()
+        };
+
+        assert_eq!(
+            body(read_static(root, Some("shop"), "logo.png").await).await,
+            "shop logo"
+        );
+        assert_eq!(
+            body(read_static(root, Some("blog"), "logo.png").await).await,
+            "shared logo"
+        );
+        assert_eq!(
+            body(read_static(root, Some("shop"), "app.css").await).await,
+            "shared css"
+        );
+        assert_eq!(
+            body(read_static(root, Some("shop"), "favicon.ico").await).await,
+            "flat icon"
+        );
+        assert_eq!(
+            body(read_static(root, None, "logo.png").await).await,
+            "shared logo"
+        );
+
+        let status = |response: Result<Response, (StatusCode, String)>| response.unwrap_err().0;
+        assert_eq!(
+            status(read_static(root, Some("shop"), "../shop/logo.png").await),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status(read_static(root, Some("shop"), "missing.png").await),
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    async fn favicon_is_cacheable_and_answers_conditional_requests() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::write(root.join("favicon.ico"), "icon").unwrap();
+
+        let response = read_favicon(root, &HeaderMap::new()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], "image/x-icon");
+        assert_eq!(
+            response.headers()[CACHE_CONTROL],
+            format!("public, max-age={FAVICON_MAX_AGE_SECS}")
+        );
+        let etag = response.headers()[ETAG].clone();
+        let last_modified = response.headers()[LAST_MODIFIED].clone();
+
+        let conditional = |name: HeaderName, value: HeaderValue| {
+            let mut headers = HeaderMap::new();
+            headers.insert(name, value);
+            async move { read_favicon(root, &headers).await }
+        };
+        let response = conditional(IF_NONE_MATCH, etag.clone()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[ETAG], etag);
+
+        let response = conditional(IF_MODIFIED_SINCE, last_modified).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        let stale = HeaderValue::from_static("Thu, 01 Jan 1970 00:00:00 GMT");
+        let response = conditional(IF_MODIFIED_SINCE, stale).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let other = HeaderValue::from_static("\"0000000000000000\"");
+        let response = conditional(IF_NONE_MATCH, other).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let empty = tempfile::tempdir().unwrap();
+        let missing = read_favicon(empty.path(), &HeaderMap::new()).await;
+        assert_eq!(missing.unwrap_err().0, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn bare_paths_resolve_to_the_host_or_default_tenant() {
+        use tower::ServiceExt;
+
+        let fixture = Fixture::new(
+            r#"{ "tenants": ["shop", "blog"] }"#,
+            &[("shop/about.html", "shop"), ("blog/about.html", "blog")],
+        )
+        .await
+        .unwrap();
+        let router = |default_tenant: Option<&str>| {
+            let mut state = fixture.state.clone();
+            state.tenants.default_tenant = default_tenant.map(String::from);
+            build_router(state, RequestLimits::default())
+        };
+        let get = |router: Router, host: &'static str| async move {
+            let request = Request::get("/about")
+                .header(axum::http::header::HOST, host)
+                .body(Body::empty())
+                .unwrap();
+            let response = router.oneshot(request).await.unwrap();
+            let status = response.status();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (status, String::from_utf8(body.to_vec()).unwrap())
+        };
+
+        let (status, body) = get(router(Some("shop")), "www.example.com").await;
+        assert_eq!((status, body.as_str()), (StatusCode::OK, "shop"));
+        let (status, body) = get(router(Some("shop")), "blog.example.com:8080").await;
+        assert_eq!((status, body.as_str()), (StatusCode::OK, "blog"));
+
+        let (status, _) = get(router(None), "www.example.com").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = get(router(Some("missing")), "localhost").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn errors_render_the_tenant_error_page_for_browsers() {
+        use tower::ServiceExt;
+
+        let fixture = Fixture::new(
+            r#"{ "tenants": ["shop"] }"#,
+            &[
+                ("_shared/500.html", "shared {{ status }} {{ reason }}"),
+                (
+                    "shop/400.html",
+                    "shop {{ status }}{% if message %}: {{ message }}{% endif %}",
+                ),
+                ("shop/broken.html", "{{ missing.field }}"),
+            ],
+        )
+        .await
+        .unwrap();
+        let router = build_router(fixture.state.clone(), RequestLimits::default());
+        let get = |uri: &str, accept: &str| {
+            let request = Request::get(uri)
+                .header(ACCEPT, accept)
+                .body(Body::empty())
+                .unwrap();
+            router.clone().oneshot(request)
+        };
+        let text = |response: Response| async {
+            let status = response.status();
+            let content_type = response.headers()[CONTENT_TYPE].clone();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (
+                status,
+                content_type,
+                String::from_utf8(body.to_vec()).unwrap(),
+            )
+        };
+
+        // A missing template is a 404, which falls back to 400.html.
+        let (status, content_type, body) =
+            text(get("/@shop/missing", "text/html").await.unwrap()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(content_type, "text/html; charset=utf-8");
+        assert_eq!(body, "shop 404");
+
+        // Clients that don't accept HTML keep the plain-text error.
+        let (status, content_type, _) =
+            text(get("/@shop/missing", "application/json").await.unwrap()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(content_type.to_str().unwrap().starts_with("text/plain"));
+
+        // A render error is a 500; the tenant has no 500.html, so _shared's
+        // is used.
+        let (status, _, body) = text(get("/@shop/broken", "text/html").await.unwrap()).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body, "shared 500 Internal Server Error");
+
+        // Paths with a directory traversal hit a 400 from the static handler.
+        let (status, _, body) = text(
+            get("/@shop/static/..%2F..%2Fetc", "text/html")
+                .await
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body, "shop 400");
+    }
+
+    #[tokio::test]
+    async fn oversized_requests_are_rejected() {
+        use tower::ServiceExt;
+
+        let fixture = Fixture::new(
+            r#"{
+                "tenants": ["shop"],
+                "routes": {
+                    "shop": [{
+                        "path": "/contact",
+                        "template_name": "thanks.html",
+                        "form": { "max_body_bytes": 1024 }
+                    }]
+                }
+            }"#,
+            &[("shop/thanks.html", "Thanks {{ form.name }}")],
+        )
+        .await
+        .unwrap();
+        let router = build_router(
+            fixture.state.clone(),
+            RequestLimits {
+                max_url_bytes: 64,
+                max_query_params: 2,
+                max_body_bytes: 32,
+            },
+        );
+        let status = |request: Request| {
+            let router = router.clone();
+            async move { router.oneshot(request).await.unwrap().status() }
+        };
+        let get = |uri: &str| Request::get(uri).body(Body::empty()).unwrap();
+        let post = |body: &str, content_length: Option<usize>| {
+            let mut request = Request::post("/@shop/contact")
+                .header(CONTENT_TYPE, "application/x-www-form-urlencoded");
+            if let Some(len) = content_length {
+                request = request.header(CONTENT_LENGTH, len);
+            }
+            request.body(Body::from(body.to_string())).unwrap()
+        };
+
+        let long = format!("/@shop/contact?q={}", "a".repeat(64));
+        assert_eq!(status(get(&long)).await, StatusCode::URI_TOO_LONG);
+        assert_eq!(
+            status(get("/@shop/contact?a=1&b=2&c=3")).await,
+            StatusCode::URI_TOO_LONG
+        );
+
+        let body = format!("name={}", "a".repeat(40));
+        assert_eq!(
+            status(post(&body, Some(body.len()))).await,
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+        // Without a Content-Length the cap applies while the body is read,
+        // even though the route's own form limit is higher.
+        assert_eq!(
+            status(post(&body, None)).await,
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+        assert_eq!(status(post("name=Ana", None)).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn streamed_routes_send_the_same_page_in_chunks() {
+        let fixture = Fixture::new(
+            r#"{
+                "tenants": ["shop"],
+                "routes": {
+                    "shop": [
+                        { "path": "/list", "template_name": "list.html", "stream": true },
+                        { "path": "/buffered", "template_name": "list.html" },
+                        { "path": "/broken", "template_name": "broken.html", "stream": true }
+                    ]
+                }
+            }"#,
+            &[
+                (
+                    "shop/list.html",
+                    "{% for i in range(5000) %}<li>item {{ i }}</li>{% endfor %}",
+                ),
+                (
+                    "shop/broken.html",
+                    "{% for i in range(5000) %}<li>{{ i }}</li>{% endfor %}{{ missing.field }}",
+                ),
+            ],
+        )
+        .await
+        .unwrap();
+        let state = fixture.state.clone();
+        let get = |path: &str| fixture::render(&state, "shop", path, HeaderMap::new(), json!({}));
+
+        let streamed = get("list").await.unwrap();
+        assert_eq!(streamed.status(), StatusCode::OK);
+        assert_eq!(streamed.headers()[CONTENT_TYPE], "text/html; charset=utf-8");
+        let streamed = axum::body::to_bytes(streamed.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let buffered = axum::body::to_bytes(get("buffered").await.unwrap().into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(streamed.len() > STREAM_CHUNK_BYTES);
+        assert_eq!(streamed, buffered);
+
+        // The status is already sent, so a render error fails the body.
+        let broken = get("broken").await.unwrap();
+        assert_eq!(broken.status(), StatusCode::OK);
+        assert!(
+            axum::body::to_bytes(broken.into_body(), usize::MAX)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn lazy_sources_are_only_fetched_when_the_template_uses_them() {
+        let hits = Arc::new(std::sync::Mutex::new(Vec::<String>::new()));
+        let recorded = hits.clone();
+        let app = axum::Router::new().route(
+            "/:name",
+            axum::routing::get(move |Path(name): Path<String>| {
+                let recorded = recorded.clone();
+                async move {
+                    recorded.lock().unwrap().push(name.clone());
+                    Json(json!([{ "name": name }]))
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let routes = json!({
+            "tenants": ["shop"],
+            "routes": {
+                "shop": [{
+                    "path": "/",
+                    "template_name": "home.html",
+                    "lazy_sources": true,
+                    "data_source": {
+                        "provider": "static",
+                        "payload": {
+                            "title": "Home",
+                            "featured": { "provider": "http", "url": format!("{base}/featured") },
+                            "sale": { "provider": "http", "url": format!("{base}/sale") }
+                        }
+                    }
+                }]
+            }
+        });
+        let fixture = Fixture::new(
+            &routes.to_string(),
+            &[(
+                "shop/home.html",
+                "{{ title }}:{% for p in featured %}{{ p.name }}{% endfor %}",
+            )],
+        )
+        .await
+        .unwrap();
+        let response = fixture::render(&fixture.state, "shop", "", HeaderMap::new(), json!({}))
+            .await
+            .unwrap();
+
+        assert!(response.headers().get(ETAG).is_none());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body, "Home:featured");
+        assert_eq!(*hits.lock().unwrap(), ["featured"]);
+    }
+
+    #[tokio::test]
+    async fn admin_route_listing_omits_data_source_details() {
+        let fixture = Fixture::new(
+            r#"{
+                "tenants": ["shop"],
+                "routes": {
+                    "shop": [
+                        {
+                            "path": "/",
+                            "name": "home",
+                            "template_name": "home.html",
+                            "data_source": {
+                                "provider": "http",
+                                "url": "https://api.example/home",
+                                "headers": { "Authorization": "Bearer secret" }
+                            }
+                        },
+                        { "path": "/about", "template_name": "shop-about.html" }
+                    ],
+                    "_shared": [
+                        { "path": "/about", "template_name": "about.html" },
+                        {
+                            "path": "/catalog",
+                            "template_name": "catalog.html",
+                            "data_source": { "provider": "catalog", "limit": 10 }
+                        }
+                    ]
+                }
+            }"#,
+            &[],
+        )
+        .await
+        .unwrap();
+        let state = AppState {
+            admin_token: Some("let-me-in".into()),
+            ..fixture.state.clone()
+        };
+        let list = |token: Option<&'static str>| {
+            let mut headers = HeaderMap::new();
+            if let Some(token) = token {
+                headers.insert("x-admin-token", HeaderValue::from_static(token));
+            }
+            list_routes(headers, Path("shop".to_string()), State(state.clone()))
+        };
+
+        assert_eq!(list(None).await.unwrap_err().0, StatusCode::UNAUTHORIZED);
+        for wrong in ["let-me-out", "let-me", "let-me-in!"] {
+            assert_eq!(
+                list(Some(wrong)).await.unwrap_err().0,
+                StatusCode::UNAUTHORIZED
+            );
+        }
+
+        let response = list(Some("let-me-in")).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(!String::from_utf8_lossy(&body).contains("secret"));
+        let routes: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            routes,
+            json!([
+                { "path": "/", "name": "home", "template_name": "home.html", "provider": "http", "shared": false },
+                { "path": "/about", "name": "/about", "template_name": "shop-about.html", "provider": null, "shared": false },
+                { "path": "/catalog", "name": "/catalog", "template_name": "catalog.html", "provider": "catalog", "shared": true }
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn templates_preview_with_an_inline_context() {
+        let fixture = Fixture::new(
+            r#"{ "tenants": ["shop", "other"] }"#,
+            &[
+                ("shop/card.html", "{{ product.name }} {{ product.price }}"),
+                ("shop/broken.html", "{{ product.name | nope }}"),
+                ("other/secret.html", "other tenant"),
+            ],
+        )
+        .await
+        .unwrap();
+        let state = AppState {
+            admin_token: Some("let-me-in".into()),
+            ..fixture.state.clone()
+        };
+        let preview = |token: Option<&'static str>, template: &str| {
+            let mut headers = HeaderMap::new();
+            if let Some(token) = token {
+                headers.insert("x-admin-token", HeaderValue::from_static(token));
+            }
+            preview_template(
+                headers,
+                Path("shop".to_string()),
+                State(state.clone()),
+                Json(PreviewRequest {
+                    template: template.to_string(),
+                    context: json!({ "product": { "name": "Sofa", "price": 10 } })
+                        .as_object()
+                        .cloned()
+                        .unwrap(),
+                }),
+            )
+        };
+
+        assert_eq!(
+            preview(None, "card.html").await.unwrap_err().0,
+            StatusCode::UNAUTHORIZED
+        );
+
+        // Without a configured token the endpoint doesn't exist.
+        let disabled = preview_template(
+            HeaderMap::new(),
+            Path("shop".to_string()),
+            State(fixture.state.clone()),
+            Json(PreviewRequest {
+                template: "card.html".to_string(),
+                context: Map::new(),
+            }),
+        )
+        .await;
+        assert_eq!(disabled.unwrap_err().0, StatusCode::NOT_FOUND);
+
+        let response = preview(Some("let-me-in"), "card.html").await.unwrap();
+        assert_eq!(response.headers()[CONTENT_TYPE], "text/html; charset=utf-8");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body, "Sofa 10");
+
+        let (status, message) = preview(Some("let-me-in"), "broken.html").await.unwrap_err();
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(message.contains("nope"), "{message}");
+
+        // Only the tenant's own (and shared) templates resolve.
+        for name in ["secret.html", "../other/secret.html"] {
+            assert_eq!(
+                preview(Some("let-me-in"), name).await.unwrap_err().0,
+                StatusCode::NOT_FOUND
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn request_ids_are_propagated_to_responses_and_templates() {
+        use tower::ServiceExt;
+
+        let fixture = Fixture::new(
+            r#"{ "tenants": ["shop"], "routes": { "shop": [{ "path": "/", "template_name": "index.html" }] } }"#,
+            &[("shop/index.html", "id={{ request.id }}")],
+        )
+        .await
+        .unwrap();
+        let router = build_router(fixture.state.clone(), RequestLimits::default());
+        let get = |id: Option<&str>| {
+            let mut request = Request::get("/@shop/");
+            if let Some(id) = id {
+                request = request.header(REQUEST_ID, id);
+            }
+            router.clone().oneshot(request.body(Body::empty()).unwrap())
+        };
+        let id_and_body = |response: Response| async {
+            let id = response.headers()[REQUEST_ID].to_str().unwrap().to_string();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (id, String::from_utf8(body.to_vec()).unwrap())
+        };
+
+        let (id, body) = id_and_body(get(Some("abc-123")).await.unwrap()).await;
+        assert_eq!(id, "abc-123");
+        assert_eq!(body, "id=abc-123");
+
+        let (id, body) = id_and_body(get(None).await.unwrap()).await;
+        assert!(uuid::Uuid::parse_str(&id).is_ok());
+        assert_eq!(body, format!("id={id}"));
+
+        let (id, _) = id_and_body(get(Some("<script>")).await.unwrap()).await;
+        assert!(uuid::Uuid::parse_str(&id).is_ok());
+    }
+}
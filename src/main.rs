@@ -1,4 +1,5 @@
 mod app;
+mod catalog;
 mod data;
 mod db;
 mod http;
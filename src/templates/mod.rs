@@ -1,21 +1,188 @@
 use anyhow::{Context, Result};
-use chrono::Utc;
-use minijinja::{AutoEscape, Environment, Error, ErrorKind, value::Value};
+use chrono::{
+    Utc,
+    format::{Item, StrftimeItems},
+};
+use chrono_tz::Tz;
+use minijinja::{
+    AutoEscape, Environment, Error, ErrorKind, State,
+    value::{Kwargs, Value},
+};
 use moka::future::Cache;
+use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
-    fs,
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt, fs,
     hash::{Hash, Hasher},
-    path::{Path, PathBuf},
-    sync::Arc,
-    time::{Duration, UNIX_EPOCH},
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 use tokio::task;
 
+/// Autoescape mode for templates with a given extension. `xml` uses the HTML
+/// escaper, whose output is also valid XML.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EscapeMode {
+    Html,
+    Xml,
+    None,
+}
+
+impl From<EscapeMode> for AutoEscape {
+    fn from(mode: EscapeMode) -> Self {
+        match mode {
+            EscapeMode::Html | EscapeMode::Xml => AutoEscape::Html,
+            EscapeMode::None => AutoEscape::None,
+        }
+    }
+}
+
+/// Layout conventions of the template directory. Defaults to a `_shared`
+/// directory and the html/jinja/j2/jinja2/txt/xml extensions (files without
+/// an extension are always loaded).
+#[derive(Debug, Clone)]
+pub struct TemplateSettings {
+    pub shared_dir: String,
+    pub extensions: Vec<String>,
+    /// Maximum number of tenant environments kept compiled. Each entry holds
+    /// every parsed template of one tenant (plus the shared set), so memory
+    /// grows roughly linearly with this value; set it at or above the number
+    /// of active tenants to avoid rebuilding environments on every request.
+    pub cache_capacity: u64,
+    /// Evicts environments that haven't been used for this long, trading a
+    /// rebuild on the next request for memory held by idle tenants.
+    pub cache_idle_ttl: Option<Duration>,
+    /// Per-tenant autoescape overrides, keyed by tenant slug then extension.
+    pub autoescape: HashMap<String, BTreeMap<String, EscapeMode>>,
+    /// Tenants whose templates live outside the template directory (e.g. on
+    /// their own volume), keyed by tenant slug. Others use
+    /// `<template_dir>/<tenant>`.
+    pub tenant_dirs: HashMap<String, PathBuf>,
+}
+
+impl Default for TemplateSettings {
+    fn default() -> Self {
+        Self {
+            shared_dir: "_shared".to_string(),
+            extensions: ["html", "jinja", "j2", "txt", "jinja2", "xml"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            cache_capacity: 128,
+            cache_idle_ttl: None,
+            autoescape: HashMap::new(),
+            tenant_dirs: HashMap::new(),
+        }
+    }
+}
+
+impl TemplateSettings {
+    /// Reads `TEMPLATE_SHARED_DIR`, `TEMPLATE_EXTENSIONS` (comma-separated,
+    /// e.g. `html,tmpl`), `TEMPLATE_CACHE_CAPACITY` and
+    /// `TEMPLATE_CACHE_IDLE_TTL_SECS` and `TEMPLATE_TENANT_DIRS`
+    /// (comma-separated `tenant=/absolute/dir` pairs), falling back to the
+    /// defaults for anything unset.
+    pub fn from_env() -> Self {
+        let mut settings = Self::default();
+
+        if let Ok(shared_dir) = std::env::var("TEMPLATE_SHARED_DIR")
+            && !shared_dir.trim().is_empty()
+        {
+            settings.shared_dir = shared_dir.trim().to_string();
+        }
+
+        if let Ok(extensions) = std::env::var("TEMPLATE_EXTENSIONS") {
+            let extensions: Vec<String> = extensions
+                .split(',')
+                .map(|ext| ext.trim().trim_start_matches('.').to_ascii_lowercase())
+                .filter(|ext| !ext.is_empty())
+                .collect();
+            if !extensions.is_empty() {
+                settings.extensions = extensions;
+            }
+        }
+
+        if let Some(capacity) = env_parse::<u64>("TEMPLATE_CACHE_CAPACITY") {
+            settings.cache_capacity = capacity;
+        }
+
+        if let Some(secs) = env_parse::<u64>("TEMPLATE_CACHE_IDLE_TTL_SECS") {
+            settings.cache_idle_ttl = Some(Duration::from_secs(secs)).filter(|ttl| !ttl.is_zero());
+        }
+
+        if let Ok(dirs) = std::env::var("TEMPLATE_TENANT_DIRS") {
+            settings.tenant_dirs = parse_tenant_dirs(&dirs);
+        }
+
+        settings
+    }
+
+    /// Where `tenant`'s own templates live.
+    pub fn tenant_dir(&self, template_dir: &Path, tenant: &str) -> PathBuf {
+        self.tenant_dirs
+            .get(tenant)
+            .cloned()
+            .unwrap_or_else(|| template_dir.join(tenant))
+    }
+
+    fn should_include(&self, path: &Path) -> bool {
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .map(|name| !name.starts_with('.'))
+            .unwrap_or(false)
+            && path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .map(|ext| self.extensions.iter().any(|allowed| allowed == ext))
+                .unwrap_or(true)
+    }
+}
+
 #[derive(Clone)]
 pub struct TemplateService {
     template_dir: PathBuf,
+    settings: Arc<TemplateSettings>,
     env_cache: Cache<String, Arc<CachedEnvironment>>,
+    assets: Arc<AssetFingerprints>,
+}
+
+/// A template that failed to parse when its environment was built.
+#[derive(Debug, Clone, Serialize)]
+pub struct BrokenTemplate {
+    pub name: String,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for BrokenTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}", self.name, line, self.message),
+            None => write!(f, "{}: {}", self.name, self.message),
+        }
+    }
+}
+
+/// `{% include %}`/`{% extends %}`/`{% import %}` target that doesn't exist in
+/// the tenant's (or the shared) template set.
+#[derive(Debug, Clone, Serialize)]
+pub struct MissingInclude {
+    pub template: String,
+    pub include: String,
+}
+
+/// Template health for one tenant, as returned by [`TemplateService::lint`].
+/// `undefined_variables` lists, per template, the top-level variables it
+/// reads without defining them; those have to come from the route context.
+#[derive(Debug, Clone, Serialize)]
+pub struct LintReport {
+    pub tenant: String,
+    pub templates: usize,
+    pub compile_errors: Vec<BrokenTemplate>,
+    pub missing_includes: Vec<MissingInclude>,
+    pub undefined_variables: BTreeMap<String, Vec<String>>,
 }
 
 #[derive(Clone)]
@@ -25,24 +192,54 @@ struct CachedEnvironment {
 }
 
 impl TemplateService {
-    pub fn new(template_dir: impl Into<PathBuf>) -> Self {
+    pub fn new(template_dir: impl Into<PathBuf>, settings: TemplateSettings) -> Self {
+        let mut env_cache = Cache::builder().max_capacity(settings.cache_capacity);
+        if let Some(ttl) = settings.cache_idle_ttl {
+            env_cache = env_cache.time_to_idle(ttl);
+        }
+
         Self {
             template_dir: template_dir.into(),
-            env_cache: Cache::builder().max_capacity(128).build(),
+            settings: Arc::new(settings),
+            env_cache: env_cache.build(),
+            assets: Arc::new(AssetFingerprints::new("static")),
         }
     }
 
+    pub fn template_dir(&self) -> &Path {
+        &self.template_dir
+    }
+
     pub async fn env_for(&self, tenant_slug: &str) -> Result<Arc<Environment<'static>>> {
+        self.env_with_fingerprint(tenant_slug)
+            .await
+            .map(|(env, _)| env)
+    }
+
+    /// Like [`Self::env_for`], also returning the fingerprint of the tenant's
+    /// template files, which changes whenever one of them does.
+    pub async fn env_with_fingerprint(
+        &self,
+        tenant_slug: &str,
+    ) -> Result<(Arc<Environment<'static>>, u64)> {
         let fingerprint = self.scan_fingerprint(tenant_slug).await?;
 
-        if let Some(cached) = self.env_cache.get(tenant_slug).await {
-            if cached.fingerprint == fingerprint {
-                return Ok(cached.env.clone());
-            }
+        if let Some(cached) = self.env_cache.get(tenant_slug).await
+            && cached.fingerprint == fingerprint
+        {
+            return Ok((cached.env.clone(), fingerprint));
         }
 
         let templates = self.read_templates(tenant_slug).await?;
-        let env = Self::build_environment(templates)?;
+        let (env, broken) = Self::build_environment(
+            tenant_slug,
+            templates,
+            self.assets.clone(),
+            self.autoescape_for(tenant_slug),
+        );
+        for broken in &broken {
+            tracing::warn!("template compile error in {tenant_slug}/{broken}");
+        }
         let env = Arc::new(env);
 
         let cached = Arc::new(CachedEnvironment {
@@ -52,21 +249,96 @@ impl TemplateService {
 
         self.env_cache.insert(tenant_slug.to_string(), cached).await;
 
-        Ok(env)
+        Ok((env, fingerprint))
+    }
+
+    /// Number of tenant environments currently cached. The count is updated
+    /// lazily by the cache; call [`Self::sync_cache`] first for an exact value.
+    pub fn cached_environments(&self) -> u64 {
+        self.env_cache.entry_count()
+    }
+
+    pub async fn sync_cache(&self) {
+        self.env_cache.run_pending_tasks().await;
+    }
+
+    pub async fn lint(&self, tenant_slug: &str) -> Result<LintReport> {
+        let templates = self.read_templates(tenant_slug).await?;
+        let sources = templates.clone();
+        let (env, names) = Self::configure_environment(
+            tenant_slug,
+            templates,
+            Vec::new(),
+            self.assets.clone(),
+            self.autoescape_for(tenant_slug),
+        );
+
+        let compile_errors = compile_errors(&env, &names);
+
+        let mut missing_includes = Vec::new();
+        for name in &names {
+            for include in referenced_templates(&sources[name]) {
+                if !sources.contains_key(&include) {
+                    missing_includes.push(MissingInclude {
+                        template: name.clone(),
+                        include,
+                    });
+                }
+            }
+        }
+
+        let globals: HashSet<&str> = env.globals().map(|(name, _)| name).collect();
+        let mut undefined_variables = BTreeMap::new();
+        for name in &names {
+            let Ok(template) = env.get_template(name) else {
+                continue;
+            };
+            let mut vars: Vec<String> = template
+                .undeclared_variables(false)
+                .into_iter()
+                .filter(|var| !globals.contains(var.as_str()))
+                .collect();
+            if !vars.is_empty() {
+                vars.sort();
+                undefined_variables.insert(name.clone(), vars);
+            }
+        }
+
+        Ok(LintReport {
+            tenant: tenant_slug.to_string(),
+            templates: names.len(),
+            compile_errors,
+            missing_includes,
+            undefined_variables,
+        })
+    }
+
+    fn autoescape_for(&self, tenant_slug: &str) -> BTreeMap<String, EscapeMode> {
+        self.settings
+            .autoescape
+            .get(tenant_slug)
+            .map(|rules| {
+                rules
+                    .iter()
+                    .map(|(ext, mode)| (ext.trim_start_matches('.').to_ascii_lowercase(), *mode))
+                    .collect()
+            })
+            .unwrap_or_default()
     }
 
     async fn scan_fingerprint(&self, tenant_slug: &str) -> Result<u64> {
         let base = self.template_dir.clone();
+        let settings = self.settings.clone();
         let tenant = tenant_slug.to_string();
 
         task::spawn_blocking(move || {
             let mut hasher = std::collections::hash_map::DefaultHasher::new();
 
-            let shared_root = canonicalize_or(base.join("_shared"));
-            let tenant_root = canonicalize_or(base.join(&tenant));
+            let shared_root = canonicalize_or(base.join(&settings.shared_dir));
+            let tenant_root = canonicalize_or(settings.tenant_dir(&base, &tenant));
 
-            hasher.write_u64(fingerprint_for(&shared_root)?);
-            hasher.write_u64(fingerprint_for(&tenant_root)?);
+            hasher.write_u64(fingerprint_for(&shared_root, &settings)?);
+            hasher.write_u64(fingerprint_for(&tenant_root, &settings)?);
 
             Ok::<_, anyhow::Error>(hasher.finish())
         })
@@ -76,14 +348,15 @@ impl TemplateService {
 
     async fn read_templates(&self, tenant_slug: &str) -> Result<HashMap<String, String>> {
         let base = self.template_dir.clone();
+        let settings = self.settings.clone();
         let tenant = tenant_slug.to_string();
 
         task::spawn_blocking(move || {
-            let shared_root = canonicalize_or(base.join("_shared"));
-            let tenant_root = canonicalize_or(base.join(&tenant));
+            let shared_root = canonicalize_or(base.join(&settings.shared_dir));
+            let tenant_root = canonicalize_or(settings.tenant_dir(&base, &tenant));
 
-            let mut map = load_templates(&shared_root)?;
-            map.extend(load_templates(&tenant_root)?);
+            let mut map = load_templates(&shared_root, &settings)?;
+            map.extend(load_templates(&tenant_root, &settings)?);
 
             Ok::<_, anyhow::Error>(map)
         })
@@ -91,38 +364,403 @@ impl TemplateService {
         .context("template load task failed")?
     }
 
-    fn build_environment(templates: HashMap<String, String>) -> Result<Environment<'static>> {
+    /// Compiles every template eagerly and returns the environment without
+    /// the ones that failed, so a single syntax error doesn't take down the
+    /// tenant's other pages. Loading a broken template reports its error.
+    fn build_environment(
+        tenant_slug: &str,
+        mut templates: HashMap<String, String>,
+        assets: Arc<AssetFingerprints>,
+        autoescape: BTreeMap<String, EscapeMode>,
+    ) -> (Environment<'static>, Vec<BrokenTemplate>) {
+        let (env, names) = Self::configure_environment(
+            tenant_slug,
+            templates.clone(),
+            Vec::new(),
+            assets.clone(),
+            autoescape.clone(),
+        );
+
+        let broken = compile_errors(&env, &names);
+        if broken.is_empty() {
+            return (env, broken);
+        }
+
+        for template in &broken {
+            templates.remove(&template.name);
+        }
+        let (env, _) =
+            Self::configure_environment(tenant_slug, templates, broken.clone(), assets, autoescape);
+        (env, broken)
+    }
+
+    /// Sets up the environment without compiling anything; returns it along
+    /// with the sorted names of the loaded templates. `broken` templates are
+    /// left out and fail to load with their compile error.
+    fn configure_environment(
+        tenant_slug: &str,
+        templates: HashMap<String, String>,
+        broken: Vec<BrokenTemplate>,
+        assets: Arc<AssetFingerprints>,
+        autoescape: BTreeMap<String, EscapeMode>,
+    ) -> (Environment<'static>, Vec<String>) {
         let mut env = Environment::new();
 
-        env.set_auto_escape_callback(|name| {
-            if name.ends_with(".html") {
-                AutoEscape::Html
-            } else {
-                AutoEscape::None
+        env.set_auto_escape_callback(move |name| {
+            let ext = Path::new(name)
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .unwrap_or_default();
+            match autoescape.get(ext) {
+                Some(mode) => (*mode).into(),
+                None if ext == "html" => AutoEscape::Html,
+                None => AutoEscape::None,
             }
         });
 
         env.set_fuel(Some(1_000_000));
 
+        let mut names: Vec<String> = templates.keys().cloned().collect();
+        names.sort();
+
         let loader_map = Arc::new(templates);
         env.set_loader(move |name| {
-            loader_map.get(name).cloned().map(Some).ok_or_else(|| {
-                Error::new(
+            if let Some(source) = loader_map.get(name) {
+                return Ok(Some(source.clone()));
+            }
+            match broken.iter().find(|template| template.name == name) {
+                Some(template) => Err(Error::new(ErrorKind::SyntaxError, template.to_string())),
+                None => Err(Error::new(
                     ErrorKind::TemplateNotFound,
                     format!("template '{name}' not found"),
-                )
-            })
+                )),
+            }
         });
 
-        env.add_function("now", |_args: &[Value]| {
-            Ok(Value::from_serialize(Utc::now()))
+        env.add_function("now", now);
+
+        let tenant = tenant_slug.to_string();
+        env.add_function("asset", move |path: String| assets.url_for(&tenant, &path));
+
+        env.add_function("set_cookie", set_cookie);
+
+        env.add_filter("tojson", tojson);
+        env.add_filter("json", tojson);
+        env.add_filter("slugify", |value: String| {
+            catalog_search::slug::slugify(&value)
         });
 
-        Ok(env)
+        (env, names)
     }
 }
 
-fn fingerprint_for(root: &Path) -> Result<u64> {
+fn compile_errors(env: &Environment<'static>, names: &[String]) -> Vec<BrokenTemplate> {
+    names
+        .iter()
+        .filter_map(|name| env.get_template(name).err().map(|err| (name, err)))
+        .map(|(name, err)| BrokenTemplate {
+            name: name.clone(),
+            line: err.line(),
+            message: match err.detail() {
+                Some(detail) => format!("{}: {detail}", err.kind()),
+                None => err.kind().to_string(),
+            },
+        })
+        .collect()
+}
+
+/// Names referenced by `include`, `extends`, `import` and `from` tags with a
+/// string literal target. Dynamic targets are skipped.
+fn referenced_templates(source: &str) -> Vec<String> {
+    let mut found = Vec::new();
+    let mut rest = source;
+
+    while let Some(start) = rest.find("{%") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("%}") else {
+            break;
+        };
+        let tag = after[..end].trim_matches(|c: char| c == '-' || c == '+' || c.is_whitespace());
+        rest = &after[end + 2..];
+
+        let keyword = tag.split_whitespace().next().unwrap_or_default();
+        if !matches!(keyword, "include" | "extends" | "import" | "from") {
+            continue;
+        }
+
+        let args = &tag[keyword.len()..];
+        if let Some(open) = args.find(['\'', '"']) {
+            let quote = args[open..].chars().next().unwrap_or('"');
+            let literal = &args[open + 1..];
+            if let Some(close) = literal.find(quote) {
+                found.push(literal[..close].to_string());
+            }
+        }
+    }
+
+    found
+}
+
+/// Parses `tenant=/dir,other=/dir`. Relative directories would depend on the
+/// working directory, so those entries are skipped with a warning.
+fn parse_tenant_dirs(value: &str) -> HashMap<String, PathBuf> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| {
+            let parsed = entry
+                .split_once('=')
+                .map(|(tenant, dir)| (tenant.trim(), PathBuf::from(dir.trim())))
+                .filter(|(tenant, dir)| !tenant.is_empty() && dir.is_absolute());
+            if parsed.is_none() {
+                tracing::warn!("ignoring invalid TEMPLATE_TENANT_DIRS entry {entry:?}");
+            }
+            parsed.map(|(tenant, dir)| (tenant.to_string(), dir))
+        })
+        .collect()
+}
+
+pub(crate) fn env_parse<T: std::str::FromStr>(name: &str) -> Option<T> {
+    let value = std::env::var(name).ok()?;
+    match value.trim().parse() {
+        Ok(parsed) => Some(parsed),
+        Err(_) => {
+            tracing::warn!("ignoring invalid {name}={value:?}");
+            None
+        }
+    }
+}
+
+/// `now()` keeps returning the serialized UTC timestamp. `now(tz)` and
+/// `now(tz, fmt)` return a preformatted string in the given IANA timezone
+/// (`none` means UTC), using RFC 3339 unless a strftime format is passed.
+fn now(tz: Option<String>, fmt: Option<String>) -> Result<Value, Error> {
+    if tz.is_none() && fmt.is_none() {
+        return Ok(Value::from_serialize(Utc::now()));
+    }
+
+    let tz: Tz = match tz {
+        Some(name) => name.parse().map_err(|_| {
+            Error::new(
+                ErrorKind::InvalidOperation,
+                format!("now(): unknown timezone '{name}'"),
+            )
+        })?,
+        None => Tz::UTC,
+    };
+    let local = Utc::now().with_timezone(&tz);
+
+    let Some(fmt) = fmt else {
+        return Ok(Value::from(local.to_rfc3339()));
+    };
+    let items: Vec<Item> = StrftimeItems::new(&fmt).collect();
+    if items.iter().any(|item| matches!(item, Item::Error)) {
+        return Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!("now(): invalid format string '{fmt}'"),
+        ));
+    }
+
+    Ok(Value::from(
+        local.format_with_items(items.into_iter()).to_string(),
+    ))
+}
+
+const SET_COOKIES_TEMP: &str = "set_cookies";
+
+/// `set_cookie(name, value, max_age=None, path="/", http_only=true)` queues
+/// a `Set-Cookie` header (always `SameSite=Lax`) for the response and
+/// renders nothing. The handler collects them with [`response_cookies`].
+fn set_cookie(state: &State, name: String, value: String, kwargs: Kwargs) -> Result<Value, Error> {
+    let invalid = |what: &str| {
+        Error::new(
+            ErrorKind::InvalidOperation,
+            format!("set_cookie(): invalid cookie {what} for '{name}'"),
+        )
+    };
+    let is_token = |c: char| c.is_ascii_graphic() && !"()<>@,;:\\\"/[]?={}".contains(c);
+    if name.is_empty() || !name.chars().all(is_token) {
+        return Err(invalid("name"));
+    }
+    if !value
+        .chars()
+        .all(|c| c.is_ascii_graphic() && !matches!(c, '"' | ',' | ';' | '\\'))
+    {
+        return Err(invalid("value"));
+    }
+
+    let max_age: Option<i64> = kwargs.get("max_age")?;
+    let path: Option<String> = kwargs.get("path")?;
+    let http_only: Option<bool> = kwargs.get("http_only")?;
+    kwargs.assert_all_used()?;
+
+    let path = path.unwrap_or_else(|| "/".to_string());
+    if path.contains(';') {
+        return Err(invalid("path"));
+    }
+    let mut header = format!("{name}={value}; Path={path}; SameSite=Lax");
+    if let Some(max_age) = max_age {
+        header.push_str(&format!("; Max-Age={max_age}"));
+    }
+    if http_only.unwrap_or(true) {
+        header.push_str("; HttpOnly");
+    }
+
+    let mut queued: Vec<Value> = state
+        .get_temp(SET_COOKIES_TEMP)
+        .and_then(|cookies| cookies.try_iter().ok().map(|iter| iter.collect()))
+        .unwrap_or_default();
+    queued.push(Value::from(header));
+    state.set_temp(SET_COOKIES_TEMP, Value::from(queued));
+    Ok(Value::from(""))
+}
+
+/// `Set-Cookie` header values queued by `set_cookie()` during a render.
+pub fn response_cookies(state: &State) -> Vec<String> {
+    state
+        .get_temp(SET_COOKIES_TEMP)
+        .and_then(|cookies| cookies.try_iter().ok())
+        .map(|iter| {
+            iter.filter_map(|cookie| cookie.as_str().map(String::from))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// `Content-Type` for a rendered template, from its extension: plain text,
+/// XML and JSON templates get their own types, everything else is HTML.
+pub fn content_type(name: &str) -> &'static str {
+    match Path::new(name).extension().and_then(|ext| ext.to_str()) {
+        Some("txt") => "text/plain; charset=utf-8",
+        Some("xml") => "application/xml",
+        Some("json") => "application/json",
+        _ => "text/html; charset=utf-8",
+    }
+}
+
+/// Serializes a value to JSON that is safe to embed inside `<script>`: the
+/// HTML-sensitive characters are written as unicode escapes so a string such
+/// as `</script>` can't terminate the tag. `tojson(true)` pretty-prints.
+fn tojson(value: Value, pretty: Option<bool>) -> Result<Value, Error> {
+    let serialized = if pretty.unwrap_or(false) {
+        serde_json::to_string_pretty(&value)
+    } else {
+        serde_json::to_string(&value)
+    }
+    .map_err(|err| {
+        Error::new(
+            ErrorKind::InvalidOperation,
+            "value cannot be serialized to JSON",
+        )
+        .with_source(err)
+    })?;
+
+    let mut escaped = String::with_capacity(serialized.len());
+    for ch in serialized.chars() {
+        match ch {
+            '<' => escaped.push_str("\\u003c"),
+            '>' => escaped.push_str("\\u003e"),
+            '&' => escaped.push_str("\\u0026"),
+            '/' => escaped.push_str("\\u002f"),
+            '\u{2028}' => escaped.push_str("\\u2028"),
+            '\u{2029}' => escaped.push_str("\\u2029"),
+            _ => escaped.push(ch),
+        }
+    }
+
+    Ok(Value::from_safe_string(escaped))
+}
+
+/// Assets every tenant falls back to before the flat `static/` directory.
+pub const SHARED_STATIC_DIR: &str = "_shared";
+
+/// Directories a tenant's static assets are looked up in, in order:
+/// `root/<tenant>`, then `root/_shared`, then `root` itself, so tenants can
+/// override shared assets such as `logo.png`.
+pub fn static_dirs(root: &Path, tenant: Option<&str>) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Some(tenant) = tenant {
+        let mut components = Path::new(tenant).components();
+        if let (Some(Component::Normal(_)), None) = (components.next(), components.next()) {
+            dirs.push(root.join(tenant));
+        }
+    }
+    dirs.push(root.join(SHARED_STATIC_DIR));
+    dirs.push(root.to_path_buf());
+    dirs
+}
+
+/// Content hashes for files under the static directory, used by the `asset()`
+/// template function to build cache-busting URLs. Hashes are recomputed only
+/// when a file's mtime changes.
+struct AssetFingerprints {
+    root: PathBuf,
+    hashes: Mutex<HashMap<PathBuf, (SystemTime, String)>>,
+}
+
+impl AssetFingerprints {
+    fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            hashes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// `/@<tenant>/static/<rel>`, versioned with the hash of the file that
+    /// route serves for the tenant (see [`static_dirs`]).
+    fn url_for(&self, tenant: &str, path: &str) -> String {
+        let rel = path.trim_start_matches('/');
+        let rel = rel.strip_prefix("static/").unwrap_or(rel);
+        let url = format!("/@{tenant}/static/{rel}");
+
+        match self.hash_for(tenant, Path::new(rel)) {
+            Ok(hash) => format!("{url}?v={hash}"),
+            Err(err) => {
+                tracing::warn!("asset fingerprint unavailable for {url}: {err:#}");
+                url
+            }
+        }
+    }
+
+    fn hash_for(&self, tenant: &str, rel: &Path) -> Result<String> {
+        if !rel
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            anyhow::bail!("invalid asset path");
+        }
+
+        let full_path = static_dirs(&self.root, Some(tenant))
+            .into_iter()
+            .map(|dir| dir.join(rel))
+            .find(|path| path.is_file())
+            .with_context(|| {
+                format!("{} not found under {}", rel.display(), self.root.display())
+            })?;
+        let modified = fs::metadata(&full_path)
+            .and_then(|metadata| metadata.modified())
+            .with_context(|| format!("reading {}", full_path.display()))?;
+
+        let mut hashes = self.hashes.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some((cached_mtime, hash)) = hashes.get(&full_path)
+            && *cached_mtime == modified
+        {
+            return Ok(hash.clone());
+        }
+
+        let bytes =
+            fs::read(&full_path).with_context(|| format!("reading {}", full_path.display()))?;
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        bytes.hash(&mut hasher);
+        let hash = format!("{:016x}", hasher.finish());
+
+        hashes.insert(full_path, (modified, hash.clone()));
+        Ok(hash)
+    }
+}
+
+fn fingerprint_for(root: &Path, settings: &TemplateSettings) -> Result<u64> {
     if !root.exists() {
         return Ok(0);
     }
@@ -139,7 +777,7 @@ fn fingerprint_for(root: &Path) -> Result<u64> {
                 continue;
             }
 
-            if !should_include(&path) {
+            if !settings.should_include(&path) {
                 continue;
             }
 
@@ -152,7 +790,7 @@ fn fingerprint_for(root: &Path) -> Result<u64> {
             if let Ok(modified) = metadata.modified() {
                 let nanos = modified
                     .duration_since(UNIX_EPOCH)
-                    .unwrap_or_else(|_| Duration::ZERO)
+                    .unwrap_or(Duration::ZERO)
                     .as_nanos();
                 hasher.write_u64(nanos as u64);
             }
@@ -162,7 +800,7 @@ fn fingerprint_for(root: &Path) -> Result<u64> {
     Ok(hasher.finish())
 }
 
-fn load_templates(root: &Path) -> Result<HashMap<String, String>> {
+fn load_templates(root: &Path, settings: &TemplateSettings) -> Result<HashMap<String, String>> {
     if !root.exists() {
         return Ok(HashMap::new());
     }
@@ -180,7 +818,7 @@ fn load_templates(root: &Path) -> Result<HashMap<String, String>> {
                 continue;
             }
 
-            if !should_include(&path) {
+            if !settings.should_include(&path) {
                 continue;
             }
 
@@ -200,18 +838,209 @@ fn load_templates(root: &Path) -> Result<HashMap<String, String>> {
     Ok(map)
 }
 
-fn should_include(path: &Path) -> bool {
-    path.file_name()
-        .and_then(|name| name.to_str())
-        .map(|name| !name.starts_with('.'))
-        .unwrap_or(false)
-        && path
-            .extension()
-            .and_then(|ext| ext.to_str())
-            .map(|ext| matches!(ext, "html" | "jinja" | "j2" | "txt" | "jinja2"))
-            .unwrap_or(true)
-}
-
 fn canonicalize_or(path: PathBuf) -> PathBuf {
     path.canonicalize().unwrap_or(path)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::fixture::Fixture;
+
+    fn render(name: &str, source: &str, ctx: Value) -> String {
+        render_with(name, source, ctx, BTreeMap::new())
+    }
+
+    fn render_with(
+        name: &str,
+        source: &str,
+        ctx: Value,
+        autoescape: BTreeMap<String, EscapeMode>,
+    ) -> String {
+        let templates = HashMap::from([(name.to_string(), source.to_string())]);
+        let (env, _) = TemplateService::build_environment(
+            "shop",
+            templates,
+            Arc::new(AssetFingerprints::new("static")),
+            autoescape,
+        );
+        env.get_template(name).unwrap().render(ctx).unwrap()
+    }
+
+    #[test]
+    fn tojson_escapes_script_terminators() {
+        let ctx = Value::from_serialize(serde_json::json!({
+            "product": { "name": "</script><script>alert(1)</script>", "price": 10 }
+        }));
+        let html = render(
+            "page.html",
+            "<script>const p = {{ product | tojson }};</script>",
+            ctx,
+        );
+
+        assert_eq!(
+            html,
+            "<script>const p = {\"name\":\"\\u003c\\u002fscript\\u003e\\u003cscript\\u003ealert(1)\\u003c\\u002fscript\\u003e\",\"price\":10};</script>"
+        );
+        let json = html
+            .trim_start_matches("<script>const p = ")
+            .trim_end_matches(";</script>");
+        let parsed: serde_json::Value = serde_json::from_str(json).unwrap();
+        assert_eq!(parsed["name"], "</script><script>alert(1)</script>");
+    }
+
+    #[test]
+    fn xml_templates_escape_when_configured() {
+        let ctx = Value::from_serialize(serde_json::json!({ "name": "Tom & Jerry <3" }));
+        let source = "<name>{{ name }}</name>";
+
+        assert_eq!(
+            render("feed.xml", source, ctx.clone()),
+            "<name>Tom & Jerry <3</name>"
+        );
+        assert_eq!(
+            render_with(
+                "feed.xml",
+                source,
+                ctx,
+                BTreeMap::from([("xml".to_string(), EscapeMode::Xml)]),
+            ),
+            "<name>Tom &amp; Jerry &lt;3</name>"
+        );
+    }
+
+    #[test]
+    fn now_formats_in_timezone() {
+        let html = render(
+            "clock.txt",
+            "{{ now('America/Sao_Paulo', '%Z') }}|{{ now(none, '%Z') }}",
+            Value::UNDEFINED,
+        );
+        assert_eq!(html, "-03|UTC");
+
+        let templates = HashMap::from([(
+            "bad.txt".to_string(),
+            "{{ now('Mars/Olympus') }}".to_string(),
+        )]);
+        let (env, _) = TemplateService::build_environment(
+            "shop",
+            templates,
+            Arc::new(AssetFingerprints::new("static")),
+            BTreeMap::new(),
+        );
+        let err = env.get_template("bad.txt").unwrap().render(()).unwrap_err();
+        assert!(err.to_string().contains("unknown timezone 'Mars/Olympus'"));
+    }
+
+    #[test]
+    fn broken_templates_are_reported_without_failing_the_environment() {
+        let templates = HashMap::from([
+            ("index.html".to_string(), "home".to_string()),
+            ("broken.html".to_string(), "{% if %}".to_string()),
+            (
+                "uses_broken.html".to_string(),
+                "{% include 'broken.html' %}".to_string(),
+            ),
+        ]);
+        let (env, broken) = TemplateService::build_environment(
+            "shop",
+            templates,
+            Arc::new(AssetFingerprints::new("static")),
+            BTreeMap::new(),
+        );
+
+        assert_eq!(
+            broken.iter().map(|t| t.name.as_str()).collect::<Vec<_>>(),
+            ["broken.html"]
+        );
+        assert_eq!(
+            env.get_template("index.html").unwrap().render(()).unwrap(),
+            "home"
+        );
+        let err = env.get_template("broken.html").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::SyntaxError);
+        assert!(err.to_string().contains("broken.html:1"));
+        let err = env
+            .get_template("uses_broken.html")
+            .unwrap()
+            .render(())
+            .unwrap_err();
+        assert!(err.to_string().contains("broken.html"));
+    }
+
+    #[test]
+    fn asset_urls_point_at_the_file_the_tenant_is_served() {
+        let dir = tempfile::tempdir().unwrap();
+        for (file, body) in [
+            ("shop/logo.png", "shop logo"),
+            ("_shared/logo.png", "shared logo"),
+            ("app.css", "flat css"),
+        ] {
+            let path = dir.path().join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, body).unwrap();
+        }
+        let assets = AssetFingerprints::new(dir.path());
+        let version = |url: String| url.split_once("?v=").unwrap().1.to_string();
+
+        let shop_logo = assets.url_for("shop", "logo.png");
+        assert!(
+            shop_logo.starts_with("/@shop/static/logo.png?v="),
+            "{shop_logo}"
+        );
+        let blog_logo = assets.url_for("blog", "/static/logo.png");
+        assert!(
+            blog_logo.starts_with("/@blog/static/logo.png?v="),
+            "{blog_logo}"
+        );
+        assert_ne!(version(shop_logo), version(blog_logo));
+        assert!(assets.url_for("shop", "app.css").contains("?v="));
+        assert_eq!(
+            assets.url_for("shop", "missing.js"),
+            "/@shop/static/missing.js"
+        );
+    }
+
+    #[tokio::test]
+    async fn mapped_tenants_load_templates_from_their_own_directory() {
+        let fixture = Fixture::new(
+            r#"{ "tenants": ["shop"] }"#,
+            &[
+                ("shop/index.html", "unmapped"),
+                ("_shared/footer.html", "shared"),
+            ],
+        )
+        .await
+        .unwrap();
+        let mounted = fixture.path().join("volume/shop");
+        std::fs::create_dir_all(&mounted).unwrap();
+        std::fs::write(mounted.join("index.html"), "mounted").unwrap();
+
+        let dirs = parse_tenant_dirs(&format!(
+            "shop={}, other=relative/dir, broken",
+            mounted.display()
+        ));
+        assert_eq!(dirs, HashMap::from([("shop".to_string(), mounted.clone())]));
+        let service = TemplateService::new(
+            fixture.template_dir(),
+            TemplateSettings {
+                tenant_dirs: dirs,
+                ..Default::default()
+            },
+        );
+
+        let (env, fingerprint) = service.env_with_fingerprint("shop").await.unwrap();
+        let render = |name: &str| env.get_template(name).unwrap().render(()).unwrap();
+        assert_eq!(render("index.html"), "mounted");
+        assert_eq!(render("footer.html"), "shared");
+
+        // Changes under the mapped directory are picked up.
+        std::fs::write(mounted.join("index.html"), "remounted").unwrap();
+        let (env, changed) = service.env_with_fingerprint("shop").await.unwrap();
+        assert_ne!(changed, fingerprint);
+        assert_eq!(
+            env.get_template("index.html").unwrap().render(()).unwrap(),
+            "remounted"
+        );
+    }
+}
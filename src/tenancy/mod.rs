@@ -1,16 +1,33 @@
 use anyhow::{Result, anyhow};
-use axum::http::HeaderMap;
+use axum::http::{HeaderMap, header::HOST};
 
 use crate::db::Repo;
 
 #[derive(Clone)]
 pub struct TenantResolver {
     pub repo: Repo,
+    /// Tenant serving paths without an `@tenant` prefix when the host
+    /// doesn't name one (`DEFAULT_TENANT`).
+    pub default_tenant: Option<String>,
 }
 
 impl TenantResolver {
     pub fn new(repo: Repo) -> Self {
-        Self { repo }
+        Self {
+            repo,
+            default_tenant: None,
+        }
+    }
+
+    pub fn from_env(repo: Repo) -> Self {
+        let default_tenant = std::env::var("DEFAULT_TENANT")
+            .ok()
+            .map(|tenant| tenant.trim().to_string())
+            .filter(|tenant| !tenant.is_empty());
+        Self {
+            default_tenant,
+            ..Self::new(repo)
+        }
     }
 
     pub async fn resolve(&self, _headers: &HeaderMap, tenant_slug: &str) -> Result<String> {
@@ -21,4 +38,24 @@ impl TenantResolver {
             Err(anyhow!("tenant not found"))
         }
     }
+
+    /// Tenant for a path without an `@tenant` prefix: the one named by the
+    /// first label of the `Host` header (`store.example.com` -> `store`),
+    /// else the default tenant. `None` when neither names a known tenant.
+    pub async fn resolve_bare(&self, headers: &HeaderMap) -> Result<Option<String>> {
+        let host_tenant = headers
+            .get(HOST)
+            .and_then(|host| host.to_str().ok())
+            .and_then(|host| host.split(['.', ':']).next())
+            .filter(|label| !label.is_empty());
+        for candidate in host_tenant
+            .into_iter()
+            .chain(self.default_tenant.as_deref())
+        {
+            if self.repo.tenant_exists(candidate).await? {
+                return Ok(Some(candidate.to_string()));
+            }
+        }
+        Ok(None)
+    }
 }
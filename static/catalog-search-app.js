@@ -14,6 +14,8 @@ async function bootstrap() {
   const formSelector = loaderScript?.dataset?.formSelector ?? ".search-form";
   const inputSelector = loaderScript?.dataset?.inputSelector ?? "#search-query";
   const catalogUrl = loaderScript?.dataset?.catalogUrl ?? "/static/catalog.bin";
+  const updatedTarget =
+    loaderScript?.dataset?.updatedTarget ?? "#catalog-updated";
 
   let wasmModule;
   try {
@@ -86,6 +88,8 @@ async function bootstrap() {
     return;
   }
 
+  renderCatalogAge(updatedTarget, engine);
+
   let allItems;
   try {
     allItems = engine.all();
@@ -152,8 +156,11 @@ async function bootstrap() {
     }
 
     let matches;
+    let suggestion = null;
     try {
-      matches = engine.search(query);
+      const response = engine.search_with_suggestion(query);
+      matches = response && response.results;
+      suggestion = response && response.suggestion;
     } catch (error) {
       console.error("CatalogSearch::search failed", error);
       showStaticError(
@@ -177,7 +184,9 @@ async function bootstrap() {
     if (matches.length === 0) {
       resultsContainer.innerHTML = "";
       if (emptyNode) {
-        emptyNode.textContent = `We couldn't find anything for "${query}". Try a different search term.`;
+        emptyNode.textContent = suggestion
+          ? `We couldn't find anything for "${query}". Did you mean "${suggestion}"?`
+          : `We couldn't find anything for "${query}". Try a different search term.`;
         emptyNode.hidden = false;
       }
       if (summaryNode) {
@@ -322,6 +331,49 @@ function buildProductCard(product, siteSlug) {
   return article;
 }
 
+function renderCatalogAge(selector, engine) {
+  const node = document.querySelector(selector);
+  if (!node || typeof engine.meta !== "function") {
+    return;
+  }
+
+  let meta;
+  try {
+    meta = engine.meta();
+  } catch (error) {
+    console.warn("CatalogSearch::meta failed", error);
+    return;
+  }
+
+  const generatedAt = meta ? Date.parse(meta.generated_at) : NaN;
+  if (Number.isNaN(generatedAt)) {
+    node.hidden = true;
+    return;
+  }
+  node.textContent = `Catalog updated ${formatAge(Date.now() - generatedAt)}`;
+  node.title = new Date(generatedAt).toLocaleString();
+  node.hidden = false;
+}
+
+function formatAge(elapsedMs) {
+  const minutes = Math.max(0, Math.floor(elapsedMs / 60000));
+  if (minutes < 1) {
+    return "just now";
+  }
+  const units = [
+    [60 * 24, "day"],
+    [60, "hour"],
+    [1, "minute"],
+  ];
+  for (const [size, label] of units) {
+    if (minutes >= size) {
+      const count = Math.floor(minutes / size);
+      return `${count} ${label}${count === 1 ? "" : "s"} ago`;
+    }
+  }
+  return "just now";
+}
+
 function truncate(text, limit) {
   if (typeof text !== "string") {
     return "";
@@ -0,0 +1,164 @@
+// @ts-check
+// Decoder for the bytes returned by `CatalogSearch.search_bin`. The layout
+// mirrors `encode_results` in catalog-search/src/lib.rs: a little-endian u16
+// format version followed by bincode with fixed-width integers. Field order
+// must match `ProductResult` and `VariationResult` exactly.
+
+/** @typedef {import("./pkg/catalog_search.js").ProductResult} ProductResult */
+/** @typedef {import("./pkg/catalog_search.js").VariationResult} VariationResult */
+
+export const RESULTS_FORMAT_VERSION = 1;
+
+const utf8 = new TextDecoder();
+
+class Reader {
+  /** @param {Uint8Array} bytes */
+  constructor(bytes) {
+    this.bytes = bytes;
+    this.view = new DataView(bytes.buffer, bytes.byteOffset, bytes.byteLength);
+    this.offset = 0;
+  }
+
+  u8() {
+    const value = this.view.getUint8(this.offset);
+    this.offset += 1;
+    return value;
+  }
+
+  u16() {
+    const value = this.view.getUint16(this.offset, true);
+    this.offset += 2;
+    return value;
+  }
+
+  length() {
+    const value = this.view.getBigUint64(this.offset, true);
+    this.offset += 8;
+    if (value > BigInt(this.bytes.length - this.offset)) {
+      throw new Error("search results are truncated");
+    }
+    return Number(value);
+  }
+
+  i64() {
+    const value = this.view.getBigInt64(this.offset, true);
+    this.offset += 8;
+    return Number(value);
+  }
+
+  f32() {
+    const value = this.view.getFloat32(this.offset, true);
+    this.offset += 4;
+    return value;
+  }
+
+  f64() {
+    const value = this.view.getFloat64(this.offset, true);
+    this.offset += 8;
+    return value;
+  }
+
+  bool() {
+    return this.u8() !== 0;
+  }
+
+  string() {
+    const len = this.length();
+    const value = utf8.decode(
+      this.bytes.subarray(this.offset, this.offset + len)
+    );
+    this.offset += len;
+    return value;
+  }
+
+  /**
+   * @template T
+   * @param {() => T} read
+   * @returns {T | null}
+   */
+  option(read) {
+    return this.u8() === 0 ? null : read();
+  }
+
+  /**
+   * @template T
+   * @param {() => T} read
+   * @returns {T[]}
+   */
+  vec(read) {
+    const len = this.length();
+    const items = new Array(len);
+    for (let i = 0; i < len; i++) {
+      items[i] = read();
+    }
+    return items;
+  }
+}
+
+/**
+ * @param {Reader} r
+ * @returns {VariationResult}
+ */
+function readVariation(r) {
+  const string = () => r.string();
+  const f64 = () => r.f64();
+  return {
+    id: r.string(),
+    name: r.option(string),
+    price: r.option(f64),
+    color: r.option(string),
+    secondary_color: r.option(string),
+    quick_description: r.option(string),
+    quick_specifications: r.option(string),
+    is_promotional: r.option(() => r.bool()),
+    promotional_price: r.option(f64),
+    discount_percent: r.option(f64),
+  };
+}
+
+/**
+ * @param {Reader} r
+ * @returns {ProductResult}
+ */
+function readProduct(r) {
+  const string = () => r.string();
+  const f64 = () => r.f64();
+  return {
+    id: r.string(),
+    name: r.option(string),
+    slug: r.option(string),
+    description: r.option(string),
+    quick_description: r.option(string),
+    quick_specifications: r.option(string),
+    price: r.option(f64),
+    is_promotional: r.option(() => r.bool()),
+    promotional_price: r.option(f64),
+    discount_percent: r.option(f64),
+    priority: r.option(() => r.i64()),
+    category: r.option(string),
+    variations: r.vec(() => readVariation(r)),
+    matched_variation_ids: r.vec(string),
+    score: r.f32(),
+    from_price: r.option(f64),
+  };
+}
+
+/**
+ * Decodes the output of `search_bin` into the same objects `search` returns.
+ * @param {Uint8Array} bytes
+ * @returns {ProductResult[]}
+ */
+export function decodeProductResults(bytes) {
+  const reader = new Reader(bytes);
+  const version = reader.u16();
+  if (version !== RESULTS_FORMAT_VERSION) {
+    throw new Error(
+      `unsupported search results version ${version}, expected ${RESULTS_FORMAT_VERSION}`
+    );
+  }
+  const results = reader.vec(() => readProduct(reader));
+  if (reader.offset !== bytes.length) {
+    throw new Error("unexpected trailing bytes in search results");
+  }
+  return results;
+}